@@ -0,0 +1,330 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Aggregates over the Statements matching a pattern, optionally grouped by Entity, Attribute or Value.
+//! Counts only read keys and the other aggregates only use Integer and Float Values,
+//! ungrouped Min and Max read a single key per number type from the value ordered permutations.
+
+use crate::bgp::Binding;
+use crate::keys::{decode_permutation, range_prefix};
+use crate::query_tx::LigatureSledQueryTx;
+use crate::value::Value;
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::{FLOAT_VALUE_PREFIX, INTEGER_VALUE_PREFIX};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// What to compute over the matching Statements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Count,
+    Min,
+    Max,
+    Sum,
+    Average,
+}
+
+/// What to group matching Statements by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Entity,
+    Attribute,
+    Value,
+}
+
+/// The result of an Aggregate.
+/// Min, Max, Sum and Average only use Integers and Floats, other Values are ignored.
+/// Min, Max and Average are None when no Integers or Floats matched.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregateResult {
+    Count(usize),
+    Min(Option<Value>),
+    Max(Option<Value>),
+    /// An IntegerLiteral if only Integers were summed, otherwise a FloatLiteral.
+    Sum(Value),
+    Average(Option<f64>),
+}
+
+/// Folds Values into an AggregateResult.
+struct Accumulator {
+    aggregate: Aggregate,
+    count: usize,
+    extreme: Option<Value>,
+    numbers: usize,
+    integer_sum: i128,
+    float_sum: f64,
+    has_floats: bool,
+}
+
+impl Accumulator {
+    fn new(aggregate: Aggregate) -> Self {
+        Accumulator {
+            aggregate: aggregate,
+            count: 0,
+            extreme: None,
+            numbers: 0,
+            integer_sum: 0,
+            float_sum: 0.0,
+            has_floats: false,
+        }
+    }
+
+    /// Whether add needs to be passed the Value of each Statement.
+    fn needs_values(&self) -> bool {
+        self.aggregate != Aggregate::Count
+    }
+
+    fn add(&mut self, value: Option<Value>) {
+        self.count += 1;
+        let value = match value {
+            Some(value) => value,
+            None => return,
+        };
+        match self.aggregate {
+            Aggregate::Count => (),
+            Aggregate::Min | Aggregate::Max if is_number(&value) => {
+                let wanted = if self.aggregate == Aggregate::Min {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+                let replace = match &self.extreme {
+                    Some(extreme) => compare_values(&value, extreme) == wanted,
+                    None => true,
+                };
+                if replace {
+                    self.extreme = Some(value);
+                }
+            }
+            Aggregate::Min | Aggregate::Max => (),
+            Aggregate::Sum | Aggregate::Average => match value {
+                Value::IntegerLiteral(value) => {
+                    self.numbers += 1;
+                    self.integer_sum += value as i128;
+                }
+                Value::FloatLiteral(value) => {
+                    self.numbers += 1;
+                    self.float_sum += value;
+                    self.has_floats = true;
+                }
+                _ => (),
+            },
+        }
+    }
+
+    fn finish(self) -> Result<AggregateResult, LigatureError> {
+        Ok(match self.aggregate {
+            Aggregate::Count => AggregateResult::Count(self.count),
+            Aggregate::Min => AggregateResult::Min(self.extreme),
+            Aggregate::Max => AggregateResult::Max(self.extreme),
+            Aggregate::Sum if self.has_floats => AggregateResult::Sum(Value::FloatLiteral(
+                self.integer_sum as f64 + self.float_sum,
+            )),
+            Aggregate::Sum => AggregateResult::Sum(Value::IntegerLiteral(
+                i64::try_from(self.integer_sum)
+                    .map_err(|_| LigatureError("Sum of Integers is out of range.".to_string()))?,
+            )),
+            Aggregate::Average if self.numbers == 0 => AggregateResult::Average(None),
+            Aggregate::Average => AggregateResult::Average(Some(
+                (self.integer_sum as f64 + self.float_sum) / self.numbers as f64,
+            )),
+        })
+    }
+}
+
+fn is_number(value: &Value) -> bool {
+    matches!(value, Value::IntegerLiteral(_) | Value::FloatLiteral(_))
+}
+
+/// The rank of a Value's type, Integers and Floats are ranked together.
+fn value_rank(value: &Value) -> usize {
+    match value {
+        Value::Entity(_) => 0,
+        Value::StringLiteral(_) => 1,
+        Value::IntegerLiteral(_) | Value::FloatLiteral(_) => 2,
+        Value::BooleanLiteral(_) => 3,
+        Value::BytesLiteral(_) => 4,
+        Value::TimestampLiteral(_) => 5,
+        Value::LanguageLiteral(_, _) => 6,
+        Value::TypedLiteral(_, _) => 7,
+    }
+}
+
+/// Orders Values by their type's rank and then by value.
+/// Integers and Floats compare numerically with Integers first when equal.
+pub(crate) fn compare_values(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Entity(left), Value::Entity(right)) => left.0.cmp(&right.0),
+        (Value::StringLiteral(left), Value::StringLiteral(right)) => left.cmp(right),
+        (Value::IntegerLiteral(left), Value::IntegerLiteral(right)) => left.cmp(right),
+        (Value::FloatLiteral(left), Value::FloatLiteral(right)) => left.total_cmp(right),
+        (Value::IntegerLiteral(left), Value::FloatLiteral(right)) => {
+            (*left as f64).total_cmp(right).then(Ordering::Less)
+        }
+        (Value::FloatLiteral(left), Value::IntegerLiteral(right)) => {
+            left.total_cmp(&(*right as f64)).then(Ordering::Greater)
+        }
+        (Value::BooleanLiteral(left), Value::BooleanLiteral(right)) => left.cmp(right),
+        (Value::BytesLiteral(left), Value::BytesLiteral(right)) => left.cmp(right),
+        (Value::TimestampLiteral(left), Value::TimestampLiteral(right)) => left.cmp(right),
+        (Value::LanguageLiteral(left, left_tag), Value::LanguageLiteral(right, right_tag))
+        | (Value::TypedLiteral(left, left_tag), Value::TypedLiteral(right, right_tag)) => {
+            (left, left_tag).cmp(&(right, right_tag))
+        }
+        _ => value_rank(left).cmp(&value_rank(right)),
+    }
+}
+
+impl LigatureSledQueryTx {
+    /// Computes an Aggregate over the Statements matching a pattern.
+    pub fn aggregate(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<Value>,
+        aggregate: Aggregate,
+    ) -> Result<AggregateResult, LigatureError> {
+        if value.is_some() || aggregate == Aggregate::Count {
+            // counting only needs keys, and with a bound Value every match has the same Value
+            let mut accumulator = Accumulator::new(aggregate);
+            let prefix =
+                self.statement_prefix(entity.as_ref(), attribute.as_ref(), value.as_ref())?;
+            let value = if accumulator.needs_values() {
+                value
+            } else {
+                None
+            };
+            if let Some(prefix) = prefix {
+                for key in self.store.scan_prefix(prefix).keys() {
+                    key.map_err(|_| LigatureError("Error aggregating Statements.".to_string()))?;
+                    accumulator.add(value.clone());
+                }
+            }
+            return accumulator.finish();
+        }
+        let attribute_id = match &attribute {
+            Some(attribute) => match self.lookup_attribute(attribute)? {
+                Some(id) => Some(id),
+                None => return Accumulator::new(aggregate).finish(),
+            },
+            None => None,
+        };
+        let base = range_prefix(entity.map(|e| e.0), attribute_id);
+        match aggregate {
+            Aggregate::Min | Aggregate::Max => {
+                let max = aggregate == Aggregate::Max;
+                let mut accumulator = Accumulator::new(aggregate);
+                for value_prefix in [INTEGER_VALUE_PREFIX, FLOAT_VALUE_PREFIX].iter() {
+                    if let Some(value) = self.extreme_value(&base, *value_prefix, max)? {
+                        accumulator.add(Some(value));
+                    }
+                }
+                accumulator.finish()
+            }
+            _ => {
+                let mut accumulator = Accumulator::new(aggregate);
+                for value_prefix in [INTEGER_VALUE_PREFIX, FLOAT_VALUE_PREFIX].iter() {
+                    let mut prefix = base.clone();
+                    prefix.push(*value_prefix);
+                    for key in self.store.scan_prefix(prefix).keys() {
+                        let key = key.map_err(|_| {
+                            LigatureError("Error aggregating Statements.".to_string())
+                        })?;
+                        accumulator.add(Some(self.load_key_value(&key)?));
+                    }
+                }
+                accumulator.finish()
+            }
+        }
+    }
+
+    /// Computes an Aggregate for each group of the Statements matching a pattern.
+    /// Groups are returned in the order of their ids, Entities are bound as `Value::Entity`.
+    pub fn aggregate_by(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<Value>,
+        aggregate: Aggregate,
+        group_by: GroupBy,
+    ) -> Result<Vec<(Binding, AggregateResult)>, LigatureError> {
+        let prefix =
+            match self.statement_prefix(entity.as_ref(), attribute.as_ref(), value.as_ref())? {
+                Some(prefix) => prefix,
+                None => return Ok(vec![]),
+            };
+        // groups are keyed by the encoded id of what they are grouped by
+        let mut groups: BTreeMap<Vec<u8>, Accumulator> = BTreeMap::new();
+        for key in self.store.scan_prefix(prefix).keys() {
+            let key =
+                key.map_err(|_| LigatureError("Error aggregating Statements.".to_string()))?;
+            let statement_id_set = decode_permutation(&key)?;
+            let group = match group_by {
+                GroupBy::Entity => statement_id_set.entity_id.to_be_bytes().to_vec(),
+                GroupBy::Attribute => statement_id_set.attribute_id.to_be_bytes().to_vec(),
+                GroupBy::Value => {
+                    let mut group = vec![statement_id_set.value_prefix];
+                    group.extend_from_slice(&statement_id_set.value_body);
+                    group
+                }
+            };
+            let accumulator = groups
+                .entry(group)
+                .or_insert_with(|| Accumulator::new(aggregate));
+            if accumulator.needs_values() {
+                accumulator.add(Some(self.load_value(
+                    statement_id_set.value_prefix,
+                    statement_id_set.value_body,
+                )?));
+            } else {
+                accumulator.add(None);
+            }
+        }
+        let mut results = vec![];
+        for (group, accumulator) in groups {
+            let binding = match group_by {
+                GroupBy::Entity => Binding::Value(Value::Entity(Entity(read_id(&group)))),
+                GroupBy::Attribute => Binding::Attribute(self.load_attribute(read_id(&group))?),
+                GroupBy::Value => Binding::Value(self.load_value(group[0], group[1..].to_vec())?),
+            };
+            results.push((binding, accumulator.finish()?));
+        }
+        Ok(results)
+    }
+
+    /// Returns the smallest or largest Integer or Float among the keys starting with base.
+    /// Integers and Floats are encoded in order so only one key is read.
+    fn extreme_value(
+        &self,
+        base: &[u8],
+        value_prefix: u8,
+        max: bool,
+    ) -> Result<Option<Value>, LigatureError> {
+        let mut prefix = base.to_vec();
+        prefix.push(value_prefix);
+        let mut iter = self.store.scan_prefix(prefix);
+        let entry = if max { iter.next_back() } else { iter.next() };
+        match entry {
+            Some(entry) => {
+                let (key, _) =
+                    entry.map_err(|_| LigatureError("Error aggregating Statements.".to_string()))?;
+                Ok(Some(self.load_key_value(&key)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Loads the Value of a Statement permutation key.
+    fn load_key_value(&self, key: &[u8]) -> Result<Value, LigatureError> {
+        let statement_id_set = decode_permutation(key)?;
+        self.load_value(statement_id_set.value_prefix, statement_id_set.value_body)
+    }
+}
+
+fn read_id(bytes: &[u8]) -> u64 {
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&bytes[..8]);
+    u64::from_be_bytes(id)
+}
@@ -0,0 +1,226 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Basic graph pattern queries, a list of Statement patterns with shared variables.
+//! Patterns are joined with index nested loops, each one is matched with the variables bound so far
+//! substituted in so every lookup uses the best permutation for what is known.
+
+use crate::query_tx::LigatureSledQueryTx;
+use crate::value::{Statement, Value};
+use ligature::{Attribute, Entity, LigatureError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+/// Patterns matching more Statements than this are all treated as the same size when planning.
+const CARDINALITY_SCAN_LIMIT: usize = 10_000;
+
+/// A part of a pattern that is either known or a named variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term<T> {
+    Variable(String),
+    Bound(T),
+}
+
+/// A pattern matching Statements, variables with the same name have to match the same thing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementPattern {
+    pub entity: Term<Entity>,
+    pub attribute: Term<Attribute>,
+    pub value: Term<Value>,
+}
+
+impl StatementPattern {
+    fn variables(&self) -> BTreeSet<String> {
+        let mut variables = BTreeSet::new();
+        if let Term::Variable(name) = &self.entity {
+            variables.insert(name.clone());
+        }
+        if let Term::Variable(name) = &self.attribute {
+            variables.insert(name.clone());
+        }
+        if let Term::Variable(name) = &self.value {
+            variables.insert(name.clone());
+        }
+        variables
+    }
+}
+
+/// What a variable is bound to.
+/// Variables in the Entity position are bound to `Value::Entity` so they can be joined with Values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    Attribute(Attribute),
+    Value(Value),
+}
+
+/// A set of variable bindings that matches every pattern in a query.
+pub type Bindings = BTreeMap<String, Binding>;
+
+/// The known parts of a pattern after substituting bound variables,
+/// or None if a variable is bound to something that can't be used in its position.
+type Substituted = Option<(Option<Entity>, Option<Attribute>, Option<Value>)>;
+
+fn substitute(pattern: &StatementPattern, bindings: &Bindings) -> Substituted {
+    let entity = match &pattern.entity {
+        Term::Bound(entity) => Some(entity.clone()),
+        Term::Variable(name) => match bindings.get(name) {
+            Some(Binding::Value(Value::Entity(entity))) => Some(entity.clone()),
+            Some(_) => return None,
+            None => None,
+        },
+    };
+    let attribute = match &pattern.attribute {
+        Term::Bound(attribute) => Some(attribute.clone()),
+        Term::Variable(name) => match bindings.get(name) {
+            Some(Binding::Attribute(attribute)) => Some(attribute.clone()),
+            Some(_) => return None,
+            None => None,
+        },
+    };
+    let value = match &pattern.value {
+        Term::Bound(value) => Some(value.clone()),
+        Term::Variable(name) => match bindings.get(name) {
+            Some(Binding::Value(value)) => Some(value.clone()),
+            Some(_) => return None,
+            None => None,
+        },
+    };
+    Some((entity, attribute, value))
+}
+
+/// Binds a variable, returns false if it is already bound to something else.
+fn bind(bindings: &mut Bindings, term_name: Option<&String>, binding: Binding) -> bool {
+    match term_name {
+        Some(name) => match bindings.get(name) {
+            Some(existing) => *existing == binding,
+            None => {
+                bindings.insert(name.clone(), binding);
+                true
+            }
+        },
+        None => true,
+    }
+}
+
+fn variable_name<T>(term: &Term<T>) -> Option<&String> {
+    match term {
+        Term::Variable(name) => Some(name),
+        Term::Bound(_) => None,
+    }
+}
+
+/// Extends bindings with the variables of a pattern matched by a Statement.
+/// Returns None if a variable used more than once in the pattern matched different things.
+fn extend(
+    pattern: &StatementPattern,
+    statement: &Statement,
+    bindings: &Bindings,
+) -> Option<Bindings> {
+    let mut bindings = bindings.clone();
+    let bound = bind(
+        &mut bindings,
+        variable_name(&pattern.entity),
+        Binding::Value(Value::Entity(statement.entity.clone())),
+    ) && bind(
+        &mut bindings,
+        variable_name(&pattern.attribute),
+        Binding::Attribute(statement.attribute.clone()),
+    ) && bind(
+        &mut bindings,
+        variable_name(&pattern.value),
+        Binding::Value(statement.value.clone()),
+    );
+    if bound {
+        Some(bindings)
+    } else {
+        None
+    }
+}
+
+impl LigatureSledQueryTx {
+    /// Returns every set of variable bindings that matches all of the given patterns.
+    /// Patterns are reordered by plan_patterns before they are evaluated.
+    pub fn match_patterns(
+        &self,
+        patterns: Vec<StatementPattern>,
+    ) -> Box<dyn Iterator<Item = Result<Bindings, LigatureError>>> {
+        match self.plan_patterns(patterns) {
+            Ok(plan) => join(self.clone(), Rc::new(plan), 0, Bindings::new()),
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+
+    /// Orders patterns for evaluation.
+    /// The first pattern is the one matching the fewest Statements,
+    /// then each next pattern is the smallest one sharing a variable with the patterns before it,
+    /// falling back to the smallest pattern left when none do.
+    pub fn plan_patterns(
+        &self,
+        patterns: Vec<StatementPattern>,
+    ) -> Result<Vec<StatementPattern>, LigatureError> {
+        let mut remaining = patterns
+            .into_iter()
+            .map(|pattern| Ok((self.estimate_cardinality(&pattern)?, pattern)))
+            .collect::<Result<Vec<(usize, StatementPattern)>, LigatureError>>()?;
+        let mut bound = BTreeSet::new();
+        let mut plan = vec![];
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (cardinality, pattern))| {
+                    let connected = pattern.variables().iter().any(|v| bound.contains(v));
+                    (!connected, *cardinality)
+                })
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+            let (_, pattern) = remaining.remove(next);
+            bound.extend(pattern.variables());
+            plan.push(pattern);
+        }
+        Ok(plan)
+    }
+
+    /// Counts the Statements matching the bound parts of a pattern, up to CARDINALITY_SCAN_LIMIT.
+    fn estimate_cardinality(&self, pattern: &StatementPattern) -> Result<usize, LigatureError> {
+        let bound = substitute(pattern, &Bindings::new());
+        let (entity, attribute, value) = match &bound {
+            Some(bound) => bound,
+            None => return Ok(0),
+        };
+        match self.statement_prefix(entity.as_ref(), attribute.as_ref(), value.as_ref())? {
+            Some(prefix) => Ok(self
+                .store
+                .scan_prefix(prefix)
+                .keys()
+                .take(CARDINALITY_SCAN_LIMIT)
+                .count()),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Matches the pattern at index with the current bindings and joins each match with the rest of the patterns.
+fn join(
+    tx: LigatureSledQueryTx,
+    patterns: Rc<Vec<StatementPattern>>,
+    index: usize,
+    bindings: Bindings,
+) -> Box<dyn Iterator<Item = Result<Bindings, LigatureError>>> {
+    if index == patterns.len() {
+        return Box::new(std::iter::once(Ok(bindings)));
+    }
+    let (entity, attribute, value) = match substitute(&patterns[index], &bindings) {
+        Some(bound) => bound,
+        None => return Box::new(std::iter::empty()),
+    };
+    let matches = tx.match_statements(entity, attribute, value);
+    Box::new(matches.flat_map(move |statement| match statement {
+        Ok(statement) => match extend(&patterns[index], &statement.statement, &bindings) {
+            Some(extended) => join(tx.clone(), patterns.clone(), index + 1, extended),
+            None => Box::new(std::iter::empty()),
+        },
+        Err(e) => Box::new(std::iter::once(Err(e))),
+    }))
+}
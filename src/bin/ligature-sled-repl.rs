@@ -0,0 +1,571 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! An interactive shell for querying and editing a single LigatureSled store.
+//!
+//! Run with `cargo run --features repl --bin ligature-sled-repl -- <path to store>`
+//! and type `.help` to see the supported commands.
+
+use ligature::{Attribute, Dataset, Entity, Ligature, LigatureError};
+use ligature_sled::{LigatureSled, LigatureSledWriteTx, PersistedStatement, Statement, Value};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+const HISTORY_FILE: &str = ".ligature_sled_history";
+
+const TX_COMMANDS_ERROR: &str =
+    "Only new, add, commit, cancel, .help and .exit can be used in an open transaction.";
+
+const HELP: &str = "\
+.help                              show this message
+.datasets [prefix]                 list Datasets, optionally filtered by a prefix
+.create <dataset>                  create a Dataset
+.delete <dataset>                  delete a Dataset
+.use <dataset>                     switch the current Dataset
+all                                list all Statements in the current Dataset
+match <entity> <attribute> <value> match Statements, use ? as a wildcard
+begin                              open a write transaction on the current Dataset
+new                                create an Entity
+add <entity> <attribute> <value>   add a Statement
+commit                             commit the open transaction
+cancel                             roll back the open transaction
+.exit                              leave the shell, rolling back an open transaction
+
+new and add only work in a transaction, which holds the store's write lock until commit or cancel.
+Only new, add, commit, cancel, .help and .exit can be used while it is open.
+Writes that fail roll back the whole transaction.
+
+Entities are written as @42.
+Values can be Entities, \"strings\", integers like 42, floats like 4.2, true or false,
+bytes like 0xcafe, or timestamps in nanoseconds since the Unix epoch like ts:1600000000000000000.
+Strings can have a language tag like \"chat\"@fr or a datatype like \"1.5\"^^<http://example.com/meters>.";
+
+/// How an open transaction was ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TxEnd {
+    Commit,
+    Cancel,
+    Exit,
+}
+
+struct Repl {
+    instance: Rc<LigatureSled>,
+    dataset: Rc<RefCell<Option<Dataset>>>,
+    in_tx: Rc<Cell<bool>>,
+    editor: RefCell<Editor<ReplHelper>>,
+}
+
+impl Repl {
+    /// Reads the next line, returns None when the shell should be left.
+    fn read_line(&self) -> Option<String> {
+        let mut editor = self.editor.borrow_mut();
+        match editor.readline(&self.prompt()) {
+            Ok(line) => {
+                editor.add_history_entry(line.as_str());
+                Some(line)
+            }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                None
+            }
+        }
+    }
+
+    fn eval(&self, line: &str) -> Result<bool, LigatureError> {
+        let tokens = tokenize(line)?;
+        let args: Vec<&str> = tokens.iter().map(|t| t.as_str()).collect();
+        match args.as_slice() {
+            [] => {}
+            [".exit"] => return Ok(false),
+            [".help"] => println!("{}", HELP),
+            [".datasets"] => print_datasets(self.instance.all_datasets())?,
+            [".datasets", prefix] => {
+                print_datasets(self.instance.match_datasets_prefix(prefix))?
+            }
+            [".create", name] => self.instance.create_dataset(&Dataset::new(name)?)?,
+            [".delete", name] => {
+                let dataset = Dataset::new(name)?;
+                self.instance.delete_dataset(&dataset)?;
+                if self.dataset.borrow().as_ref() == Some(&dataset) {
+                    self.dataset.replace(None);
+                }
+            }
+            [".use", name] => {
+                let dataset = Dataset::new(name)?;
+                if !self.instance.dataset_exists(&dataset)? {
+                    return Err(LigatureError(format!("Dataset {} doesn't exist.", name)));
+                }
+                self.dataset.replace(Some(dataset));
+            }
+            ["all"] => {
+                let dataset = self.current_dataset()?;
+                let statements: Vec<PersistedStatement> = self
+                    .instance
+                    .query_sled(&dataset, |tx| tx.all_statements().collect())?;
+                print_statements(&statements);
+            }
+            ["match", entity, attribute, value] => {
+                let dataset = self.current_dataset()?;
+                let entity = parse_wildcard(entity, parse_entity)?;
+                let attribute = parse_wildcard(attribute, Attribute::new)?;
+                let value = parse_wildcard(value, parse_value)?;
+                let statements: Vec<PersistedStatement> =
+                    self.instance.query_sled(&dataset, |tx| {
+                        tx.match_statements(entity, attribute, value).collect()
+                    })?;
+                print_statements(&statements);
+            }
+            ["begin"] => return self.begin(),
+            ["new"] | ["add", _, _, _] | ["commit"] | ["cancel"] => {
+                return Err(LigatureError(
+                    "No transaction is open, start one with begin.".to_string(),
+                ))
+            }
+            _ => {
+                return Err(LigatureError(format!(
+                    "Unknown command `{}`, type .help for a list of commands.",
+                    line.trim()
+                )))
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs a write transaction on the current Dataset until it is committed or cancelled.
+    /// Returns false if the shell was left while the transaction was open.
+    fn begin(&self) -> Result<bool, LigatureError> {
+        let dataset = self.current_dataset()?;
+        let ended = Cell::new(None);
+        self.in_tx.set(true);
+        // sled only retries a transaction that conflicts with another write,
+        // which can't happen while this one holds the store's write lock, so no line is read twice
+        let res = self.instance.write_sled(&dataset, |tx| {
+            let end = self.transaction(tx)?;
+            ended.set(Some(end));
+            if end != TxEnd::Commit {
+                tx.cancel()?;
+            }
+            Ok(())
+        });
+        self.in_tx.set(false);
+        match ended.get() {
+            Some(TxEnd::Commit) => {
+                res?;
+                println!("Committed.");
+                Ok(true)
+            }
+            Some(TxEnd::Cancel) => {
+                println!("Rolled back.");
+                Ok(true)
+            }
+            Some(TxEnd::Exit) => Ok(false),
+            None => res
+                .map(|_| true)
+                .map_err(|e| LigatureError(format!("{} The transaction was rolled back.", e.0))),
+        }
+    }
+
+    /// Reads and runs the commands of an open transaction.
+    /// Failed writes end the transaction, commands that can't be parsed are only reported.
+    fn transaction(&self, tx: &LigatureSledWriteTx) -> Result<TxEnd, LigatureError> {
+        loop {
+            let line = match self.read_line() {
+                Some(line) => line,
+                None => return Ok(TxEnd::Exit),
+            };
+            let tokens = match tokenize(&line) {
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    println!("Error: {}", e.0);
+                    continue;
+                }
+            };
+            let args: Vec<&str> = tokens.iter().map(|t| t.as_str()).collect();
+            match args.as_slice() {
+                [] => {}
+                ["commit"] => return Ok(TxEnd::Commit),
+                ["cancel"] => return Ok(TxEnd::Cancel),
+                [".exit"] => return Ok(TxEnd::Exit),
+                [".help"] => println!("{}", HELP),
+                ["new"] => println!("@{}", tx.new_entity()?.0),
+                ["add", entity, attribute, value] => {
+                    match parse_statement(entity, attribute, value) {
+                        Ok(statement) => print_statements(&[tx.add_statement(&statement)?]),
+                        Err(e) => println!("Error: {}", e.0),
+                    }
+                }
+                _ => println!("Error: {}", TX_COMMANDS_ERROR),
+            }
+        }
+    }
+
+    fn current_dataset(&self) -> Result<Dataset, LigatureError> {
+        self.dataset.borrow().clone().ok_or_else(|| {
+            LigatureError("No Dataset selected, select one with .use <dataset>.".to_string())
+        })
+    }
+
+    fn prompt(&self) -> String {
+        let dataset = match self.dataset.borrow().as_ref() {
+            Some(dataset) => dataset.name().to_string(),
+            None => "".to_string(),
+        };
+        if self.in_tx.get() {
+            format!("{}*> ", dataset)
+        } else {
+            format!("{}> ", dataset)
+        }
+    }
+}
+
+fn parse_statement(entity: &str, attribute: &str, value: &str) -> Result<Statement, LigatureError> {
+    Ok(Statement {
+        entity: parse_entity(entity)?,
+        attribute: Attribute::new(attribute)?,
+        value: parse_value(value)?,
+    })
+}
+
+/// Splits a line on whitespace, keeping double quoted strings together (quotes included).
+fn tokenize(line: &str) -> Result<Vec<String>, LigatureError> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        if in_string {
+            current.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            if c == '"' {
+                in_string = true;
+            }
+            current.push(c);
+        }
+    }
+    if in_string {
+        return Err(LigatureError("Unterminated string.".to_string()));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn parse_wildcard<T, F>(token: &str, f: F) -> Result<Option<T>, LigatureError>
+where
+    F: Fn(&str) -> Result<T, LigatureError>,
+{
+    if token == "?" {
+        Ok(None)
+    } else {
+        f(token).map(Some)
+    }
+}
+
+fn parse_entity(token: &str) -> Result<Entity, LigatureError> {
+    match token.strip_prefix('@') {
+        Some(id) => id
+            .parse::<u64>()
+            .map(Entity)
+            .map_err(|_| LigatureError(format!("Invalid Entity `{}`.", token))),
+        None => Err(LigatureError(format!(
+            "Invalid Entity `{}`, Entities are written as @42.",
+            token
+        ))),
+    }
+}
+
+fn parse_value(token: &str) -> Result<Value, LigatureError> {
+    if token.starts_with('@') {
+        Ok(Value::Entity(parse_entity(token)?))
+    } else if token.starts_with('"') {
+        let end = token.rfind('"').unwrap_or(0);
+        if end == 0 {
+            return Err(LigatureError(format!("Invalid Value `{}`.", token)));
+        }
+        let value = unescape(&token[1..end]);
+        let suffix = &token[end + 1..];
+        if suffix.is_empty() {
+            Ok(Value::StringLiteral(value))
+        } else if let Some(language) = suffix.strip_prefix('@') {
+            Ok(Value::LanguageLiteral(value, language.to_string()))
+        } else if let Some(datatype) = suffix
+            .strip_prefix("^^<")
+            .and_then(|datatype| datatype.strip_suffix('>'))
+        {
+            Ok(Value::TypedLiteral(value, datatype.to_string()))
+        } else {
+            Err(LigatureError(format!("Invalid Value `{}`.", token)))
+        }
+    } else if token == "true" || token == "false" {
+        Ok(Value::BooleanLiteral(token == "true"))
+    } else if let Some(hex) = token.strip_prefix("0x") {
+        Ok(Value::BytesLiteral(parse_hex(hex)?))
+    } else if let Some(timestamp) = token.strip_prefix("ts:") {
+        match timestamp.parse::<i64>() {
+            Ok(timestamp) => Ok(Value::TimestampLiteral(timestamp)),
+            Err(_) => Err(LigatureError(format!("Invalid Timestamp `{}`.", token))),
+        }
+    } else if let Ok(integer) = token.parse::<i64>() {
+        Ok(Value::IntegerLiteral(integer))
+    } else if let Ok(float) = token.parse::<f64>() {
+        Ok(Value::FloatLiteral(float))
+    } else {
+        Err(LigatureError(format!("Invalid Value `{}`.", token)))
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, LigatureError> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(LigatureError(format!("Invalid Bytes `0x{}`.", hex)));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| LigatureError(format!("Invalid Bytes `0x{}`.", hex)))
+        })
+        .collect()
+}
+
+fn unescape(value: &str) -> String {
+    let mut res = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            res.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Entity(entity) => format!("@{}", entity.0),
+        Value::StringLiteral(value) => format!("{:?}", value),
+        Value::IntegerLiteral(value) => value.to_string(),
+        Value::FloatLiteral(value) => format!("{:?}", value),
+        Value::BooleanLiteral(value) => value.to_string(),
+        Value::BytesLiteral(value) => {
+            let hex: String = value.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("0x{}", hex)
+        }
+        Value::TimestampLiteral(value) => format!("ts:{}", value),
+        Value::LanguageLiteral(value, language) => format!("{:?}@{}", value, language),
+        Value::TypedLiteral(value, datatype) => format!("{:?}^^<{}>", value, datatype),
+    }
+}
+
+fn print_statements(statements: &[PersistedStatement]) {
+    for ps in statements {
+        println!(
+            "@{} {} {} @{}",
+            ps.statement.entity.0,
+            ps.statement.attribute.name(),
+            format_value(&ps.statement.value),
+            ps.context.0
+        );
+    }
+}
+
+fn print_datasets(
+    datasets: Box<dyn Iterator<Item = Result<Dataset, LigatureError>>>,
+) -> Result<(), LigatureError> {
+    for dataset in datasets {
+        println!("{}", dataset?.name());
+    }
+    Ok(())
+}
+
+/// Completes Dataset names for the Dataset commands and Attribute names for `match` and `add`.
+/// Nothing is completed in an open transaction, reading the store would wait on its write lock.
+struct ReplHelper {
+    instance: Rc<LigatureSled>,
+    dataset: Rc<RefCell<Option<Dataset>>>,
+    in_tx: Rc<Cell<bool>>,
+}
+
+impl ReplHelper {
+    fn complete_datasets(&self, prefix: &str) -> Vec<String> {
+        self.instance
+            .match_datasets_prefix(prefix)
+            .filter_map(|dataset| dataset.ok())
+            .map(|dataset| dataset.name().to_string())
+            .collect()
+    }
+
+    fn complete_attributes(&self, prefix: &str) -> Vec<String> {
+        let dataset = match self.dataset.borrow().clone() {
+            Some(dataset) => dataset,
+            None => return vec![],
+        };
+        self.instance
+            .query_sled(&dataset, |tx| {
+                Ok(tx
+                    .match_attributes_prefix(prefix)
+                    .filter_map(|attribute| attribute.ok())
+                    .map(|attribute| attribute.name().to_string())
+                    .collect())
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let word = &line[start..];
+        let previous: Vec<&str> = line[..start].split_whitespace().collect();
+        let candidates = match previous.as_slice() {
+            _ if self.in_tx.get() => vec![],
+            [".use"] | [".delete"] | [".datasets"] => self.complete_datasets(word),
+            ["match", _] | ["add", _] => self.complete_attributes(word),
+            _ => vec![],
+        };
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .map(|candidate| Pair {
+                    display: candidate.clone(),
+                    replacement: candidate,
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: ligature-sled-repl <path to store>");
+            std::process::exit(1);
+        }
+    };
+    let instance = match LigatureSled::new(path) {
+        Ok(instance) => Rc::new(instance),
+        Err(e) => {
+            eprintln!("Could not open store - {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    let dataset = Rc::new(RefCell::new(None));
+    let in_tx = Rc::new(Cell::new(false));
+    let mut editor = Editor::<ReplHelper>::new();
+    editor.set_helper(Some(ReplHelper {
+        instance: instance.clone(),
+        dataset: dataset.clone(),
+        in_tx: in_tx.clone(),
+    }));
+    let _ = editor.load_history(HISTORY_FILE);
+    let repl = Repl {
+        instance: instance,
+        dataset: dataset,
+        in_tx: in_tx,
+        editor: RefCell::new(editor),
+    };
+    while let Some(line) = repl.read_line() {
+        match repl.eval(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("Error: {}", e.0),
+        }
+    }
+    if let Err(e) = repl.editor.borrow_mut().save_history(HISTORY_FILE) {
+        eprintln!("Could not save history - {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_value, tokenize};
+    use ligature::{Entity, LigatureError};
+    use ligature_sled::Value;
+
+    #[test]
+    fn tokenize_keeps_strings_together() -> Result<(), LigatureError> {
+        assert_eq!(tokenize("  ")?, Vec::<String>::new());
+        assert_eq!(
+            tokenize("add @1  name \"Juniper Berry\"@en")?,
+            vec!["add", "@1", "name", "\"Juniper Berry\"@en"]
+        );
+        assert_eq!(
+            tokenize(r#"add @1 quote "say \"hi\" now""#)?,
+            vec!["add", "@1", "quote", r#""say \"hi\" now""#]
+        );
+        assert!(tokenize("add @1 name \"Juniper").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_values() -> Result<(), LigatureError> {
+        assert_eq!(parse_value("@42")?, Value::Entity(Entity(42)));
+        assert_eq!(
+            parse_value(r#""say \"hi\"""#)?,
+            Value::StringLiteral("say \"hi\"".to_string())
+        );
+        assert_eq!(
+            parse_value("\"chat\"@fr")?,
+            Value::LanguageLiteral("chat".to_string(), "fr".to_string())
+        );
+        assert_eq!(
+            parse_value("\"1.5\"^^<http://example.com/meters>")?,
+            Value::TypedLiteral("1.5".to_string(), "http://example.com/meters".to_string())
+        );
+        assert_eq!(parse_value("-42")?, Value::IntegerLiteral(-42));
+        assert_eq!(parse_value("4.2")?, Value::FloatLiteral(4.2));
+        assert_eq!(parse_value("true")?, Value::BooleanLiteral(true));
+        assert_eq!(
+            parse_value("0xcafe")?,
+            Value::BytesLiteral(vec![0xca, 0xfe])
+        );
+        assert_eq!(parse_value("ts:-5")?, Value::TimestampLiteral(-5));
+        for invalid in &["$1", "@x", "\"", "\"chat\"fr", "0xcaf", "ts:x", "Juniper"] {
+            assert!(parse_value(invalid).is_err(), "{}", invalid);
+        }
+        Ok(())
+    }
+}
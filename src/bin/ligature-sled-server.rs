@@ -0,0 +1,41 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Serves a LigatureSled store over HTTP/JSON.
+//!
+//! Run with `cargo run --features server --bin ligature-sled-server -- <path to store> [address]`.
+
+use ligature_sled::server::LigatureServer;
+use ligature_sled::LigatureSled;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:4200";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: ligature-sled-server <path to store> [address]");
+            std::process::exit(1);
+        }
+    };
+    let address = args.next().unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let instance = match LigatureSled::new(path) {
+        Ok(instance) => instance,
+        Err(e) => {
+            eprintln!("Could not open store - {:?}", e);
+            std::process::exit(1);
+        }
+    };
+    match LigatureServer::new(instance, &address) {
+        Ok(server) => {
+            println!("Listening on {}", server.address());
+            server.run();
+        }
+        Err(e) => {
+            eprintln!("{}", e.0);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Metadata about a Statement, like its source or confidence,
+//! is stored as Statements with the Statement's context as their Entity.
+
+use crate::entity_registry::EntityKind;
+use crate::keys::decode_permutation;
+use crate::query_tx::LigatureSledQueryTx;
+use crate::write_tx::LigatureSledWriteTx;
+use crate::value::{PersistedStatement, Statement, Value};
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::{decode_id, encode_id, prepend, AVEC_PREFIX, CEAV_PREFIX, EAVC_PREFIX};
+
+impl LigatureSledQueryTx {
+    /// Returns the metadata Statements of the Statement with the given context.
+    pub fn context_metadata(
+        &self,
+        context: &Entity,
+    ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
+        let tx = self.clone();
+        let iter = self
+            .store
+            .scan_prefix(prepend(EAVC_PREFIX, encode_id(context.0)));
+        Box::new(iter.map(move |entry| match entry {
+            Ok((key, _)) => tx.load_statement(key.to_vec()),
+            Err(_) => Err(LigatureError("Error reading context metadata.".to_string())),
+        }))
+    }
+
+    /// Returns the Statements whose context has the given metadata, like all Statements from a source.
+    /// Contexts are found with AVEC and then their Statements with CEAV.
+    pub fn match_statements_by_metadata(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
+        match self.scan_statements_by_metadata(attribute, value) {
+            Ok(iter) => iter,
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+
+    fn scan_statements_by_metadata(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Result<Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>>, LigatureError>
+    {
+        let attribute_id = match self.lookup_attribute(attribute)? {
+            Some(id) => id,
+            None => return Ok(Box::new(std::iter::empty())),
+        };
+        let (value_prefix, value_body) = match self.lookup_value(value)? {
+            Some(value) => value,
+            None => return Ok(Box::new(std::iter::empty())),
+        };
+        let mut prefix = prepend(AVEC_PREFIX, encode_id(attribute_id));
+        prefix.push(value_prefix);
+        prefix.extend(value_body);
+        let mut contexts = vec![];
+        for key in self.store.scan_prefix(prefix).keys() {
+            let key =
+                key.map_err(|_| LigatureError("Error reading context metadata.".to_string()))?;
+            // AVEC keys have the Entity after the prefix, Attribute and Value
+            contexts.push(decode_id(key[18..26].to_vec())?);
+        }
+        let store = self.store.clone();
+        let tx = self.clone();
+        let iter = contexts
+            .into_iter()
+            .flat_map(move |context| store.scan_prefix(prepend(CEAV_PREFIX, encode_id(context))));
+        Ok(Box::new(iter.map(move |entry| match entry {
+            Ok((key, _)) => tx.load_statement(key.to_vec()),
+            Err(_) => Err(LigatureError("Error matching Statements.".to_string())),
+        })))
+    }
+}
+
+impl LigatureSledWriteTx {
+    /// Adds a metadata Statement about the Statement with the given context.
+    pub fn add_context_metadata(
+        &self,
+        context: &Entity,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Result<PersistedStatement, LigatureError> {
+        let has_statement = self
+            .store
+            .scan_prefix(prepend(CEAV_PREFIX, encode_id(context.0)))
+            .next()
+            .transpose()?
+            .is_some();
+        if self.entity_kind(context)? != Some(EntityKind::Context) || !has_statement {
+            return Err(LigatureError(format!(
+                "Entity {} isn't the context of a Statement.",
+                context.0
+            )));
+        }
+        let metadata_context = self.allocate_entity(EntityKind::Context)?;
+        Ok(self.insert_statement(
+            &Statement {
+                entity: context.clone(),
+                attribute: attribute.clone(),
+                value: value.clone(),
+            },
+            metadata_context,
+        )?)
+    }
+    /// Removes the metadata Statements about a context once it holds no Statements,
+    /// along with the metadata about the metadata Statements' own contexts.
+    /// Returns the number of metadata Statements removed.
+    pub(crate) fn remove_unused_context_metadata(
+        &self,
+        context: &Entity,
+    ) -> Result<usize, LigatureError> {
+        let mut contexts = vec![context.0];
+        let mut removed = 0;
+        while let Some(context_id) = contexts.pop() {
+            let in_use = self
+                .store
+                .scan_prefix(prepend(CEAV_PREFIX, encode_id(context_id)))
+                .next()
+                .transpose()?
+                .is_some();
+            if in_use {
+                continue;
+            }
+            let keys = self
+                .store
+                .scan_prefix(prepend(EAVC_PREFIX, encode_id(context_id)))
+                .map(|entry| entry.map(|(key, _)| key))
+                .collect::<Result<Vec<sled::IVec>, LigatureError>>()?;
+            for key in keys {
+                let statement_id_set = decode_permutation(&key)?;
+                self.remove_statement_id_set(&statement_id_set)?;
+                contexts.push(statement_id_set.context_id);
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,604 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A Datalog engine over a Dataset.
+//! Every Attribute is a base relation of Entity and Value pairs read from the permutation indexes,
+//! and rules define derived relations that are computed bottom up with semi-naive evaluation.
+//! Negation is allowed as long as the rules can be stratified.
+
+use crate::bgp::{Binding, Bindings, Term};
+use crate::query_tx::LigatureSledQueryTx;
+use crate::value::Value;
+use ligature::{Attribute, LigatureError};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+/// A predicate applied to terms, like `ancestor(X, Y)`.
+/// Predicates that aren't the head of any rule are Attributes and have an Entity and a Value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atom {
+    pub predicate: String,
+    pub terms: Vec<Term<Value>>,
+}
+
+/// An Atom in the body of a rule, negated Atoms match when there is no matching fact.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Positive(Atom),
+    Negative(Atom),
+}
+
+/// Derives facts for the head when every Literal in the body matches.
+/// A rule without a body is a fact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub head: Atom,
+    pub body: Vec<Literal>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub rules: Vec<Rule>,
+}
+
+/// Bounds on how much work evaluating a Program can do, evaluation fails when one is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EvaluationLimits {
+    /// The most semi-naive iterations run for any one stratum.
+    pub max_iterations: Option<usize>,
+    /// The most derived facts across all relations.
+    /// While a rule is being solved its solutions count too, before duplicates are removed.
+    pub max_facts: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+type Tuple = Vec<Value>;
+
+/// Tuples keyed by an encoding of their Values so they can be deduplicated.
+type Relation = BTreeMap<Vec<u8>, Tuple>;
+
+type Variables = HashMap<String, Value>;
+
+pub(crate) fn push_bytes(key: &mut Vec<u8>, bytes: &[u8]) {
+    key.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+    key.extend_from_slice(bytes);
+}
+
+/// Encodes a tuple so equal tuples, and only equal tuples, have the same key.
+pub(crate) fn tuple_key(tuple: &[Value]) -> Vec<u8> {
+    let mut key = vec![];
+    for value in tuple {
+        match value {
+            Value::Entity(entity) => {
+                key.push(0);
+                key.extend_from_slice(&entity.0.to_be_bytes());
+            }
+            Value::StringLiteral(value) => {
+                key.push(1);
+                push_bytes(&mut key, value.as_bytes());
+            }
+            Value::IntegerLiteral(value) => {
+                key.push(2);
+                key.extend_from_slice(&value.to_be_bytes());
+            }
+            Value::FloatLiteral(value) => {
+                key.push(3);
+                key.extend_from_slice(&value.to_bits().to_be_bytes());
+            }
+            Value::BooleanLiteral(value) => key.extend_from_slice(&[4, *value as u8]),
+            Value::BytesLiteral(value) => {
+                key.push(5);
+                push_bytes(&mut key, value);
+            }
+            Value::TimestampLiteral(value) => {
+                key.push(6);
+                key.extend_from_slice(&value.to_be_bytes());
+            }
+            Value::LanguageLiteral(value, tag) => {
+                key.push(7);
+                push_bytes(&mut key, value.as_bytes());
+                push_bytes(&mut key, tag.as_bytes());
+            }
+            Value::TypedLiteral(value, datatype) => {
+                key.push(8);
+                push_bytes(&mut key, value.as_bytes());
+                push_bytes(&mut key, datatype.as_bytes());
+            }
+        }
+    }
+    key
+}
+
+fn atom_variables(atom: &Atom) -> BTreeSet<String> {
+    atom.terms
+        .iter()
+        .filter_map(|term| match term {
+            Term::Variable(name) => Some(name.clone()),
+            Term::Bound(_) => None,
+        })
+        .collect()
+}
+
+/// Returns the Value a term has with the given variables, None if it is an unbound variable.
+fn resolve(term: &Term<Value>, variables: &Variables) -> Option<Value> {
+    match term {
+        Term::Bound(value) => Some(value.clone()),
+        Term::Variable(name) => variables.get(name).cloned(),
+    }
+}
+
+/// Binds the terms of an Atom to a tuple, returns None if a bound term doesn't match.
+fn unify(terms: &[Term<Value>], tuple: &[Value], variables: &Variables) -> Option<Variables> {
+    let mut variables = variables.clone();
+    for (term, value) in terms.iter().zip(tuple.iter()) {
+        match term {
+            Term::Bound(bound) => {
+                if bound != value {
+                    return None;
+                }
+            }
+            Term::Variable(name) => match variables.get(name) {
+                Some(bound) if bound != value => return None,
+                Some(_) => (),
+                None => {
+                    variables.insert(name.clone(), value.clone());
+                }
+            },
+        }
+    }
+    Some(variables)
+}
+
+/// A Program that has been checked and split into strata.
+struct Plan {
+    /// The arity of every derived predicate.
+    derived: HashMap<String, usize>,
+    /// Every base predicate and the Attribute it reads.
+    attributes: HashMap<String, Attribute>,
+    /// Rules grouped by stratum, lower strata are evaluated first.
+    strata: Vec<Vec<Rule>>,
+}
+
+impl Plan {
+    /// Checks a Program and the query it will answer, keeping only the rules the query depends on.
+    fn new(program: &Program, query: &Atom) -> Result<Plan, LigatureError> {
+        let mut derived: HashMap<String, usize> = HashMap::new();
+        for rule in program.rules.iter() {
+            let arity = rule.head.terms.len();
+            if *derived.entry(rule.head.predicate.clone()).or_insert(arity) != arity {
+                return Err(LigatureError(format!(
+                    "Predicate {} is used with different numbers of terms.",
+                    rule.head.predicate
+                )));
+            }
+        }
+        let mut attributes = HashMap::new();
+        let mut atoms: Vec<&Atom> = vec![query];
+        for rule in program.rules.iter() {
+            check_safety(rule)?;
+            for literal in rule.body.iter() {
+                match literal {
+                    Literal::Positive(atom) | Literal::Negative(atom) => atoms.push(atom),
+                }
+            }
+        }
+        for atom in atoms {
+            match derived.get(&atom.predicate) {
+                Some(arity) if *arity != atom.terms.len() => {
+                    return Err(LigatureError(format!(
+                        "Predicate {} is used with different numbers of terms.",
+                        atom.predicate
+                    )))
+                }
+                Some(_) => (),
+                None => {
+                    if atom.terms.len() != 2 {
+                        return Err(LigatureError(format!(
+                            "Attribute {} has to be used with an Entity and a Value.",
+                            atom.predicate
+                        )));
+                    }
+                    let attribute = Attribute::new(&atom.predicate)?;
+                    attributes.insert(atom.predicate.clone(), attribute);
+                }
+            }
+        }
+        let rules = relevant_rules(program, &query.predicate);
+        let strata = stratify(&rules, &derived)?;
+        Ok(Plan {
+            derived: derived,
+            attributes: attributes,
+            strata: strata,
+        })
+    }
+}
+
+/// Every variable in the head or a negated Literal has to be bound by a positive Literal.
+/// Variables starting with `_` can be left unbound in negated Literals, where they match anything.
+fn check_safety(rule: &Rule) -> Result<(), LigatureError> {
+    let mut bound = BTreeSet::new();
+    for literal in rule.body.iter() {
+        if let Literal::Positive(atom) = literal {
+            bound.extend(atom_variables(atom));
+        }
+    }
+    let mut needed = atom_variables(&rule.head);
+    for literal in rule.body.iter() {
+        if let Literal::Negative(atom) = literal {
+            needed.extend(
+                atom_variables(atom)
+                    .into_iter()
+                    .filter(|variable| !variable.starts_with('_')),
+            );
+        }
+    }
+    match needed.difference(&bound).next() {
+        Some(variable) => Err(LigatureError(format!(
+            "Variable {} in a rule for {} isn't bound by a positive Literal.",
+            variable, rule.head.predicate
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Returns the rules for the given predicate and every predicate they depend on.
+fn relevant_rules(program: &Program, predicate: &str) -> Vec<Rule> {
+    let mut needed = BTreeSet::new();
+    let mut pending = vec![predicate.to_string()];
+    while let Some(predicate) = pending.pop() {
+        if !needed.insert(predicate.clone()) {
+            continue;
+        }
+        for rule in program
+            .rules
+            .iter()
+            .filter(|r| r.head.predicate == predicate)
+        {
+            for literal in rule.body.iter() {
+                match literal {
+                    Literal::Positive(atom) | Literal::Negative(atom) => {
+                        pending.push(atom.predicate.clone())
+                    }
+                }
+            }
+        }
+    }
+    program
+        .rules
+        .iter()
+        .filter(|rule| needed.contains(&rule.head.predicate))
+        .cloned()
+        .collect()
+}
+
+/// Assigns every derived predicate a stratum above the predicates it negates
+/// and at least as high as the ones it uses, failing if negation goes through recursion.
+fn stratify(
+    rules: &[Rule],
+    derived: &HashMap<String, usize>,
+) -> Result<Vec<Vec<Rule>>, LigatureError> {
+    let mut strata: HashMap<&str, usize> = HashMap::new();
+    for rule in rules.iter() {
+        strata.insert(&rule.head.predicate, 0);
+    }
+    let limit = strata.len();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in rules.iter() {
+            for literal in rule.body.iter() {
+                let (atom, offset) = match literal {
+                    Literal::Positive(atom) => (atom, 0),
+                    Literal::Negative(atom) => (atom, 1),
+                };
+                if !derived.contains_key(&atom.predicate) {
+                    continue;
+                }
+                let needed = strata[atom.predicate.as_str()] + offset;
+                if strata[rule.head.predicate.as_str()] < needed {
+                    if needed >= limit {
+                        return Err(LigatureError(format!(
+                            "Rules for {} can't be stratified, negation is used through recursion.",
+                            rule.head.predicate
+                        )));
+                    }
+                    strata.insert(&rule.head.predicate, needed);
+                    changed = true;
+                }
+            }
+        }
+    }
+    let count = strata.values().max().map(|max| max + 1).unwrap_or(0);
+    let mut grouped = vec![vec![]; count];
+    for rule in rules.iter() {
+        grouped[strata[rule.head.predicate.as_str()]].push(rule.clone());
+    }
+    Ok(grouped)
+}
+
+struct Evaluation<'a> {
+    tx: &'a LigatureSledQueryTx,
+    plan: Plan,
+    limits: EvaluationLimits,
+    started: Instant,
+    relations: HashMap<String, Relation>,
+    facts: usize,
+}
+
+impl<'a> Evaluation<'a> {
+    fn check_iterations(&self, iteration: usize) -> Result<(), LigatureError> {
+        if let Some(max_iterations) = self.limits.max_iterations {
+            if iteration > max_iterations {
+                return Err(LigatureError(format!(
+                    "Evaluation stopped after {} iterations.",
+                    max_iterations
+                )));
+            }
+        }
+        self.check_limits(0)
+    }
+
+    /// Checks the fact and time limits, counting solutions that haven't been merged yet as facts.
+    fn check_limits(&self, pending: usize) -> Result<(), LigatureError> {
+        if let Some(max_facts) = self.limits.max_facts {
+            if self.facts + pending > max_facts {
+                return Err(LigatureError(format!(
+                    "Evaluation stopped after deriving {} facts.",
+                    max_facts
+                )));
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.started.elapsed() > timeout {
+                return Err(LigatureError(format!(
+                    "Evaluation stopped after {:?}.",
+                    timeout
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes every stratum in order with semi-naive evaluation.
+    /// After the first iteration a rule is only rerun with one of its Literals reading the facts that are new
+    /// since the last iteration, so every derivation uses at least one new fact.
+    fn run(&mut self) -> Result<(), LigatureError> {
+        let strata = std::mem::take(&mut self.plan.strata);
+        for rules in strata.iter() {
+            let heads: BTreeSet<&str> = rules.iter().map(|r| r.head.predicate.as_str()).collect();
+            let mut derived: HashMap<String, Relation> = HashMap::new();
+            for head in heads.iter() {
+                derived.insert(head.to_string(), Relation::new());
+            }
+            for rule in rules.iter() {
+                for tuple in self.derive(rule, None, &HashMap::new())? {
+                    derived
+                        .entry(rule.head.predicate.clone())
+                        .or_default()
+                        .insert(tuple_key(&tuple), tuple);
+                }
+            }
+            let mut delta = self.merge(derived)?;
+            // only rules that read a head of this stratum can derive more facts
+            let recursive = rules.iter().any(|rule| {
+                rule.body.iter().any(|literal| match literal {
+                    Literal::Positive(atom) => heads.contains(atom.predicate.as_str()),
+                    Literal::Negative(_) => false,
+                })
+            });
+            if !recursive {
+                continue;
+            }
+            let mut iteration = 1;
+            while delta.values().any(|relation| !relation.is_empty()) {
+                iteration += 1;
+                self.check_iterations(iteration)?;
+                let mut derived: HashMap<String, Relation> = HashMap::new();
+                for rule in rules.iter() {
+                    let body = ordered_body(rule);
+                    for (index, literal) in body.iter().enumerate() {
+                        if let Literal::Positive(atom) = literal {
+                            if heads.contains(atom.predicate.as_str()) {
+                                for tuple in self.derive(rule, Some(index), &delta)? {
+                                    derived
+                                        .entry(rule.head.predicate.clone())
+                                        .or_default()
+                                        .insert(tuple_key(&tuple), tuple);
+                                }
+                            }
+                        }
+                    }
+                }
+                delta = self.merge(derived)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates a rule, reading the Literal at the delta index from the facts new in the last iteration.
+    fn derive(
+        &self,
+        rule: &Rule,
+        delta_index: Option<usize>,
+        delta: &HashMap<String, Relation>,
+    ) -> Result<Vec<Tuple>, LigatureError> {
+        let body = ordered_body(rule);
+        let mut solutions = vec![];
+        self.solve(
+            &body,
+            0,
+            Variables::new(),
+            delta_index,
+            delta,
+            &mut solutions,
+        )?;
+        solutions
+            .iter()
+            .map(|variables| {
+                rule.head
+                    .terms
+                    .iter()
+                    .map(|term| {
+                        resolve(term, variables).ok_or_else(|| {
+                            LigatureError(format!(
+                                "Unbound variable in head of {}",
+                                rule.head.predicate
+                            ))
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Adds derived facts to their relations and returns the ones that weren't already known.
+    fn merge(
+        &mut self,
+        derived: HashMap<String, Relation>,
+    ) -> Result<HashMap<String, Relation>, LigatureError> {
+        let mut delta = HashMap::new();
+        for (predicate, tuples) in derived {
+            let relation = self.relations.entry(predicate.clone()).or_default();
+            let new: Relation = tuples
+                .into_iter()
+                .filter(|(key, _)| !relation.contains_key(key))
+                .collect();
+            self.facts += new.len();
+            relation.extend(new.iter().map(|(key, tuple)| (key.clone(), tuple.clone())));
+            delta.insert(predicate, new);
+        }
+        self.check_limits(0)?;
+        Ok(delta)
+    }
+
+    /// Finds every way to bind the variables of the body from the Literal at index on.
+    fn solve(
+        &self,
+        body: &[&Literal],
+        index: usize,
+        variables: Variables,
+        delta_index: Option<usize>,
+        delta: &HashMap<String, Relation>,
+        solutions: &mut Vec<Variables>,
+    ) -> Result<(), LigatureError> {
+        self.check_limits(solutions.len())?;
+        if index == body.len() {
+            solutions.push(variables);
+            return Ok(());
+        }
+        match body[index] {
+            Literal::Positive(atom) => {
+                let matches = if delta_index == Some(index) {
+                    match_relation(delta.get(&atom.predicate), atom, &variables)
+                } else {
+                    self.match_atom(atom, &variables)?
+                };
+                for matched in matches {
+                    self.solve(body, index + 1, matched, delta_index, delta, solutions)?;
+                }
+                Ok(())
+            }
+            Literal::Negative(atom) => {
+                if self.match_atom(atom, &variables)?.is_empty() {
+                    self.solve(body, index + 1, variables, delta_index, delta, solutions)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Matches an Atom against a derived relation or, for Attributes, the Dataset.
+    fn match_atom(
+        &self,
+        atom: &Atom,
+        variables: &Variables,
+    ) -> Result<Vec<Variables>, LigatureError> {
+        if self.plan.derived.contains_key(&atom.predicate) {
+            return Ok(match_relation(
+                self.relations.get(&atom.predicate),
+                atom,
+                variables,
+            ));
+        }
+        let attribute = self.plan.attributes[&atom.predicate].clone();
+        let entity = match resolve(&atom.terms[0], variables) {
+            Some(Value::Entity(entity)) => Some(entity),
+            Some(_) => return Ok(vec![]),
+            None => None,
+        };
+        let value = resolve(&atom.terms[1], variables);
+        let mut matches = vec![];
+        for statement in self.tx.match_statements(entity, Some(attribute), value) {
+            self.check_limits(0)?;
+            let statement = statement?.statement;
+            let tuple = vec![Value::Entity(statement.entity), statement.value];
+            if let Some(matched) = unify(&atom.terms, &tuple, variables) {
+                matches.push(matched);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+fn match_relation(
+    relation: Option<&Relation>,
+    atom: &Atom,
+    variables: &Variables,
+) -> Vec<Variables> {
+    match relation {
+        Some(relation) => relation
+            .values()
+            .filter_map(|tuple| unify(&atom.terms, tuple, variables))
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Positive Literals are evaluated in order before the negated ones, which only check bound variables.
+fn ordered_body(rule: &Rule) -> Vec<&Literal> {
+    let mut body: Vec<&Literal> = rule
+        .body
+        .iter()
+        .filter(|l| matches!(l, Literal::Positive(_)))
+        .collect();
+    body.extend(
+        rule.body
+            .iter()
+            .filter(|l| matches!(l, Literal::Negative(_))),
+    );
+    body
+}
+
+impl LigatureSledQueryTx {
+    /// Evaluates the rules a query depends on and returns the bindings of the query's variables for every matching fact.
+    /// Anonymous variables and ones starting with `_` aren't returned.
+    pub fn evaluate_datalog(
+        &self,
+        program: &Program,
+        query: &Atom,
+        limits: EvaluationLimits,
+    ) -> Result<Vec<Bindings>, LigatureError> {
+        let plan = Plan::new(program, query)?;
+        let mut evaluation = Evaluation {
+            tx: self,
+            plan: plan,
+            limits: limits,
+            started: Instant::now(),
+            relations: HashMap::new(),
+            facts: 0,
+        };
+        evaluation.run()?;
+        let answers = evaluation.match_atom(query, &Variables::new())?;
+        Ok(answers
+            .into_iter()
+            .map(|variables| {
+                variables
+                    .into_iter()
+                    .filter(|(name, _)| !name.starts_with('_'))
+                    .map(|(name, value)| (name, Binding::Value(value)))
+                    .collect()
+            })
+            .collect())
+    }
+}
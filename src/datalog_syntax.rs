@@ -0,0 +1,259 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Reads Datalog Programs and queries from text.
+//!
+//! ```text
+//! % comments start with a percent sign
+//! ancestor(X, Y) :- parent(X, Y).
+//! ancestor(X, Z) :- parent(X, Y), ancestor(Y, Z).
+//! root(X) :- parent(_, X), not parent(X, _).
+//! ```
+//!
+//! Predicates start with a lower case letter, or are written in angle brackets like `<https://example.com/name>`.
+//! Variables start with an upper case letter, each `_` is a new variable.
+//! Entities are written `@1` and Values like `"text"`, `42`, `4.2`, `true` and `false`.
+
+use crate::bgp::Term;
+use crate::datalog::{Atom, Literal, Program, Rule};
+use crate::value::Value;
+use ligature::{Entity, LigatureError};
+
+impl Program {
+    /// Parses a list of rules, each ending with a period.
+    pub fn parse(text: &str) -> Result<Program, LigatureError> {
+        let mut parser = Parser::new(text);
+        let mut rules = vec![];
+        parser.skip_whitespace();
+        while !parser.at_end() {
+            rules.push(parser.rule()?);
+            parser.skip_whitespace();
+        }
+        Ok(Program { rules: rules })
+    }
+}
+
+impl Atom {
+    /// Parses a single Atom, like a query.
+    pub fn parse(text: &str) -> Result<Atom, LigatureError> {
+        let mut parser = Parser::new(text);
+        let atom = parser.atom()?;
+        parser.skip_whitespace();
+        if !parser.at_end() {
+            return Err(parser.error("end of query"));
+        }
+        Ok(atom)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    anonymous_variables: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            anonymous_variables: 0,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn error(&self, expected: &str) -> LigatureError {
+        LigatureError(format!(
+            "Expected {} at character {} of Datalog.",
+            expected, self.position
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '%' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.position += 1;
+                }
+            } else if c.is_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Skips whitespace and consumes the given text if it is next.
+    fn consume(&mut self, text: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + text.chars().count();
+        if end <= self.chars.len()
+            && self.chars[self.position..end]
+                .iter()
+                .copied()
+                .eq(text.chars())
+        {
+            self.position = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), LigatureError> {
+        if self.consume(text) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", text)))
+        }
+    }
+
+    fn rule(&mut self) -> Result<Rule, LigatureError> {
+        let head = self.atom()?;
+        let mut body = vec![];
+        if self.consume(":-") {
+            loop {
+                body.push(self.literal()?);
+                if !self.consume(",") {
+                    break;
+                }
+            }
+        }
+        self.expect(".")?;
+        Ok(Rule {
+            head: head,
+            body: body,
+        })
+    }
+
+    fn literal(&mut self) -> Result<Literal, LigatureError> {
+        self.skip_whitespace();
+        let start = self.position;
+        if self.consume("not") && matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            return Ok(Literal::Negative(self.atom()?));
+        }
+        self.position = start;
+        Ok(Literal::Positive(self.atom()?))
+    }
+
+    fn atom(&mut self) -> Result<Atom, LigatureError> {
+        self.skip_whitespace();
+        let predicate = match self.peek() {
+            Some('<') => {
+                self.position += 1;
+                let name = self.take_while(|c| c != '>');
+                self.expect(">")?;
+                name
+            }
+            Some(c) if c.is_ascii_lowercase() => {
+                self.take_while(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => return Err(self.error("a predicate")),
+        };
+        self.expect("(")?;
+        let mut terms = vec![];
+        if !self.consume(")") {
+            loop {
+                terms.push(self.term()?);
+                if !self.consume(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        Ok(Atom {
+            predicate: predicate,
+            terms: terms,
+        })
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if f(c)) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn term(&mut self) -> Result<Term<Value>, LigatureError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('_') => {
+                self.position += 1;
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                if name.is_empty() {
+                    self.anonymous_variables += 1;
+                    // the space keeps these from colliding with written variables
+                    Ok(Term::Variable(format!("_ {}", self.anonymous_variables)))
+                } else {
+                    Ok(Term::Variable(format!("_{}", name)))
+                }
+            }
+            Some(c) if c.is_ascii_uppercase() => Ok(Term::Variable(
+                self.take_while(|c| c.is_ascii_alphanumeric() || c == '_'),
+            )),
+            Some('@') => {
+                self.position += 1;
+                let id = self.take_while(|c| c.is_ascii_digit());
+                let id = id.parse::<u64>().map_err(|_| self.error("an Entity id"))?;
+                Ok(Term::Bound(Value::Entity(Entity(id))))
+            }
+            Some('"') => Ok(Term::Bound(Value::StringLiteral(self.string()?))),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '-' || c == '.');
+                if number.contains('.') {
+                    let float = number.parse::<f64>().map_err(|_| self.error("a number"))?;
+                    Ok(Term::Bound(Value::FloatLiteral(float)))
+                } else {
+                    let integer = number.parse::<i64>().map_err(|_| self.error("a number"))?;
+                    Ok(Term::Bound(Value::IntegerLiteral(integer)))
+                }
+            }
+            Some(c) if c.is_ascii_lowercase() => {
+                match self.take_while(|c| c.is_ascii_alphanumeric()).as_str() {
+                    "true" => Ok(Term::Bound(Value::BooleanLiteral(true))),
+                    "false" => Ok(Term::Bound(Value::BooleanLiteral(false))),
+                    _ => Err(self.error("a term")),
+                }
+            }
+            _ => Err(self.error("a term")),
+        }
+    }
+
+    /// Reads a quoted string, backslash escapes the next character.
+    fn string(&mut self) -> Result<String, LigatureError> {
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("'\"'")),
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some(c) => string.push(c),
+                        None => return Err(self.error("an escaped character")),
+                    }
+                    self.position += 1;
+                }
+                Some(c) => {
+                    string.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Each Dataset is stored in its own sled tree.
+//! Trees are named after an id that is allocated when the Dataset is created and stored as the value
+//! of the Dataset's entry in the root tree, so Dataset names never end up being used as tree names.
+
+use ligature::{Dataset, LigatureError};
+use ligature_kv::{
+    chomp_assert, decode_dataset, decode_id, encode_dataset, encode_id, prepend, DATASET_PREFIX,
+};
+use sled::Transactional;
+
+/// Key in the root tree of the counter used to allocate Dataset ids.
+pub const DATASET_ID_COUNTER_KEY: u8 = 1;
+
+/// All Dataset trees start with this prefix, no other trees are allowed to use it.
+const DATASET_TREE_PREFIX: &str = "__ligature_sled__dataset_";
+
+/// The key of a Dataset's entry in the root tree.
+pub fn dataset_key(dataset: &Dataset) -> Vec<u8> {
+    prepend(DATASET_PREFIX, encode_dataset(dataset))
+}
+
+pub fn tree_name(dataset_id: u64) -> String {
+    format!("{}{}", DATASET_TREE_PREFIX, dataset_id)
+}
+
+/// Allocates a new Dataset id.
+pub fn allocate_dataset_id(store: &sled::Db) -> Result<u64, LigatureError> {
+    let id = store
+        .update_and_fetch(vec![DATASET_ID_COUNTER_KEY], |old| {
+            let next = match old {
+                Some(old) => decode_id(old.to_vec()).unwrap_or(0) + 1,
+                None => 1,
+            };
+            Some(encode_id(next))
+        })
+        .map_err(|_| LigatureError("Could not increment Dataset ID Counter".to_string()))?
+        .ok_or_else(|| LigatureError("Could not increment Dataset ID Counter".to_string()))?;
+    decode_id(id.to_vec())
+}
+
+/// Opens the tree of an existing Dataset, returns None if the Dataset doesn't exist.
+pub fn open_dataset_tree(
+    store: &sled::Db,
+    dataset: &Dataset,
+) -> Result<Option<sled::Tree>, LigatureError> {
+    let entry = store
+        .get(dataset_key(dataset))
+        .map_err(|_| LigatureError("Error checking for Dataset".to_string()))?;
+    match entry {
+        Some(id) => store
+            .open_tree(tree_name(decode_id(id.to_vec())?))
+            .map(Some)
+            .map_err(|_| {
+                LigatureError(format!("Error opening dataset tree for {:?}.", dataset))
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Migrates format version 1, where Datasets were stored in a tree named after the Dataset,
+/// to trees named after an allocated id.
+/// Each Dataset is copied before its entry is updated so this can be rerun if it is interrupted.
+/// Ids whose tree would have the name of a legacy Dataset's tree are skipped.
+pub fn migrate_legacy_trees(store: &sled::Db) -> Result<(), LigatureError> {
+    let entries = store
+        .scan_prefix(vec![DATASET_PREFIX])
+        .collect::<Result<Vec<(sled::IVec, sled::IVec)>, sled::Error>>()
+        .map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+    let mut legacy_datasets = vec![];
+    for (key, value) in entries {
+        if value.is_empty() {
+            let dataset = decode_dataset(chomp_assert(DATASET_PREFIX, key.to_vec())?)?;
+            legacy_datasets.push((key, dataset.name().to_string()));
+        }
+    }
+    let legacy_names = legacy_datasets
+        .iter()
+        .map(|(_, name)| name.clone())
+        .collect::<std::collections::HashSet<String>>();
+    for (key, legacy_name) in legacy_datasets {
+        let mut dataset_id = allocate_dataset_id(store)?;
+        while legacy_names.contains(&tree_name(dataset_id)) {
+            dataset_id = allocate_dataset_id(store)?;
+        }
+        let legacy_tree = store
+            .open_tree(&legacy_name)
+            .map_err(|_| LigatureError(format!("Error opening dataset tree {}.", legacy_name)))?;
+        let tree = store
+            .open_tree(tree_name(dataset_id))
+            .map_err(|_| LigatureError(format!("Error creating dataset tree {}.", legacy_name)))?;
+        copy_tree(&legacy_tree, &tree)?;
+        store
+            .insert(key, encode_id(dataset_id))
+            .map_err(|_| LigatureError(format!("Error migrating dataset {}.", legacy_name)))?;
+        store
+            .drop_tree(&legacy_name)
+            .map_err(|_| LigatureError("Error dropping dataset tree.".to_string()))?;
+    }
+    Ok(())
+}
+
+/// Replaces the contents of one tree with the contents of another.
+pub fn copy_tree(from: &sled::Tree, to: &sled::Tree) -> Result<(), LigatureError> {
+    let batch = tree_contents(from)?;
+    to.clear()
+        .and_then(|_| to.apply_batch(batch))
+        .map_err(|_| LigatureError("Error copying dataset tree.".to_string()))
+}
+
+/// Reads every entry of a tree into a batch.
+pub fn tree_contents(tree: &sled::Tree) -> Result<sled::Batch, LigatureError> {
+    let mut batch = sled::Batch::default();
+    for entry in tree.iter() {
+        let (key, value) =
+            entry.map_err(|_| LigatureError("Error reading dataset tree.".to_string()))?;
+        batch.insert(key, value);
+    }
+    Ok(batch)
+}
+
+/// Writes the contents of a new Dataset's tree and its entry in the root tree in one transaction,
+/// so a Dataset is never left half created.
+/// If the process stops before this the tree stays empty and is removed the next time the store is opened.
+pub fn insert_dataset(
+    store: &sled::Db,
+    dataset: &Dataset,
+    dataset_id: u64,
+    tree: &sled::Tree,
+    contents: &sled::Batch,
+) -> Result<(), LigatureError> {
+    let key = dataset_key(dataset);
+    (&**store, tree)
+        .transaction(|(root, tree)| {
+            tree.apply_batch(contents)?;
+            root.insert(key.clone(), encode_id(dataset_id))?;
+            Ok(())
+        })
+        .map_err(|_: sled::transaction::TransactionError<()>| {
+            LigatureError(format!("Error inserting dataset {:?}.", dataset))
+        })
+}
+
+/// Drops Dataset trees that aren't referenced by an entry in the root tree.
+/// These are left behind if the process stops in the middle of creating, deleting or copying a Dataset.
+/// Has to run after migrating, it fails without dropping anything if there are legacy Datasets left.
+pub fn remove_orphaned_trees(store: &sled::Db) -> Result<(), LigatureError> {
+    let mut dataset_ids = std::collections::HashSet::new();
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        if id.is_empty() {
+            // the tree of a legacy Dataset can have any name, including one that looks orphaned
+            return Err(LigatureError(
+                "Dataset trees can only be removed after legacy Datasets are migrated.".to_string(),
+            ));
+        }
+        dataset_ids.insert(decode_id(id.to_vec())?);
+    }
+    for name in store.tree_names() {
+        let orphaned = match std::str::from_utf8(&name) {
+            Ok(name) => match name.strip_prefix(DATASET_TREE_PREFIX) {
+                Some(id) => !id
+                    .parse::<u64>()
+                    .map(|id| dataset_ids.contains(&id))
+                    .unwrap_or(false),
+                None => false,
+            },
+            Err(_) => false,
+        };
+        if orphaned {
+            store
+                .drop_tree(name)
+                .map_err(|_| LigatureError("Error dropping dataset tree.".to_string()))?;
+        }
+    }
+    Ok(())
+}
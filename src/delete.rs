@@ -0,0 +1,93 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Deleting Entities along with the Statements about them.
+
+use crate::keys::decode_permutation;
+use crate::write_tx::LigatureSledWriteTx;
+use ligature::{Entity, LigatureError};
+use ligature_kv::{
+    encode_id, prepend, StatementIDSet, CEAV_PREFIX, EAVC_PREFIX, ENTITY_VALUE_PREFIX, VEAC_PREFIX,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What to do with Statements from other Entities that have a deleted Entity as their Value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferencePolicy {
+    /// Remove the Statements that reference the Entity.
+    Cascade,
+    /// Fail the deletion if any Statements reference the Entity.
+    Error,
+    /// Keep the Statements that reference the Entity.
+    LeaveDangling,
+}
+
+impl LigatureSledWriteTx {
+    /// Removes all Statements with the given Entity as their Entity or context,
+    /// and handles Statements with the Entity as their Value with the given policy.
+    /// The Entity and its name are removed so no new Statements can use it,
+    /// and so is the context metadata of every context the removed Statements leave empty.
+    /// Returns the number of Statements removed, including metadata Statements.
+    pub fn delete_entity(
+        &self,
+        entity: &Entity,
+        policy: ReferencePolicy,
+    ) -> Result<usize, LigatureError> {
+        // Statements are keyed by all of their ids so ones found by more than one scan are only removed once,
+        // contexts can be shared so they don't identify a Statement on their own
+        let mut statements: BTreeMap<StatementKey, StatementIDSet> = BTreeMap::new();
+        for prefix in vec![
+            prepend(EAVC_PREFIX, encode_id(entity.0)),
+            prepend(CEAV_PREFIX, encode_id(entity.0)),
+        ] {
+            for entry in self.store.scan_prefix(prefix) {
+                let statement_id_set = decode_permutation(&entry?.0)?;
+                statements.insert(statement_key(&statement_id_set), statement_id_set);
+            }
+        }
+        if policy != ReferencePolicy::LeaveDangling {
+            let mut prefix = vec![VEAC_PREFIX, ENTITY_VALUE_PREFIX];
+            prefix.extend(encode_id(entity.0));
+            for entry in self.store.scan_prefix(prefix) {
+                let statement_id_set = decode_permutation(&entry?.0)?;
+                if statements.contains_key(&statement_key(&statement_id_set)) {
+                    continue;
+                }
+                if policy == ReferencePolicy::Error {
+                    return Err(LigatureError(format!(
+                        "Entity {} is referenced by Entity {}.",
+                        entity.0, statement_id_set.entity_id
+                    )));
+                }
+                statements.insert(statement_key(&statement_id_set), statement_id_set);
+            }
+        }
+        for statement_id_set in statements.values() {
+            self.remove_statement_id_set(statement_id_set)?;
+        }
+        let mut removed = statements.len();
+        let contexts: BTreeSet<u64> = statements
+            .values()
+            .map(|statement_id_set| statement_id_set.context_id)
+            .collect();
+        for context_id in contexts {
+            removed += self.remove_unused_context_metadata(&Entity(context_id))?;
+        }
+        self.unregister_entity(entity)?;
+        self.remove_entity_name(entity)?;
+        Ok(removed)
+    }
+}
+
+type StatementKey = (u64, u64, u8, Vec<u8>, u64);
+
+fn statement_key(statement_id_set: &StatementIDSet) -> StatementKey {
+    (
+        statement_id_set.entity_id,
+        statement_id_set.attribute_id,
+        statement_id_set.value_prefix,
+        statement_id_set.value_body.clone(),
+        statement_id_set.context_id,
+    )
+}
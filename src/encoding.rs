@@ -0,0 +1,140 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Prefixes and encodings used by ligature-sled in addition to the ones from ligature-kv.
+//! See the README for the full list of prefixes.
+
+use crate::value::Value;
+use ligature::LigatureError;
+
+pub const BOOLEAN_VALUE_PREFIX: u8 = 4;
+pub const BYTES_VALUE_PREFIX: u8 = 5;
+pub const TIMESTAMP_VALUE_PREFIX: u8 = 6;
+pub const LANGUAGE_VALUE_PREFIX: u8 = 7;
+pub const TYPED_VALUE_PREFIX: u8 = 8;
+
+pub const BYTES_LITERAL_ID_COUNTER_KEY: u8 = 15;
+pub const BYTES_LITERAL_VALUE_TO_ID_PREFIX: u8 = 16;
+pub const BYTES_LITERAL_ID_TO_VALUE_PREFIX: u8 = 17;
+pub const TAGGED_LITERAL_ID_COUNTER_KEY: u8 = 18;
+pub const TAGGED_LITERAL_VALUE_TO_ID_PREFIX: u8 = 19;
+pub const TAGGED_LITERAL_ID_TO_VALUE_PREFIX: u8 = 20;
+pub const LARGE_LITERAL_ID_TO_HASH_PREFIX: u8 = 21;
+pub const LARGE_LITERAL_CHUNK_PREFIX: u8 = 22;
+pub const ATTRIBUTE_SCHEMA_PREFIX: u8 = 23;
+pub const ENTITY_REGISTRY_PREFIX: u8 = 24;
+pub const ENTITY_NAME_TO_ID_PREFIX: u8 = 25;
+pub const ENTITY_ID_TO_NAME_PREFIX: u8 = 26;
+pub const DATASET_SIZE_KEY: u8 = 27;
+pub const STATEMENT_COUNT_KEY: u8 = 28;
+pub const ENTITY_COUNT_KEY: u8 = 29;
+pub const ATTRIBUTE_STATEMENT_COUNT_PREFIX: u8 = 30;
+pub const LARGE_LITERAL_HASH_TO_ID_PREFIX: u8 = 31;
+
+/// Booleans take up eight bytes like every other Value body, false sorts before true.
+pub fn encode_boolean(value: bool) -> Vec<u8> {
+    (value as u64).to_be_bytes().to_vec()
+}
+
+pub fn decode_boolean(body: Vec<u8>) -> Result<bool, LigatureError> {
+    match read_u64(&body)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        other => Err(LigatureError(format!("Invalid Boolean {}", other))),
+    }
+}
+
+/// The sign bit is flipped so negative Integers sort before positive ones.
+pub fn encode_integer(value: i64) -> Vec<u8> {
+    ((value as u64) ^ (1 << 63)).to_be_bytes().to_vec()
+}
+
+pub fn decode_integer(body: Vec<u8>) -> Result<i64, LigatureError> {
+    Ok((read_u64(&body)? ^ (1 << 63)) as i64)
+}
+
+/// Floats sort in IEEE 754 total order, negative Floats have every bit flipped
+/// and the others only have their sign bit flipped.
+pub fn encode_float(value: f64) -> Vec<u8> {
+    let bits = value.to_bits();
+    let ordered = if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) };
+    ordered.to_be_bytes().to_vec()
+}
+
+pub fn decode_float(body: Vec<u8>) -> Result<f64, LigatureError> {
+    let ordered = read_u64(&body)?;
+    let bits = if ordered >> 63 == 1 { ordered ^ (1 << 63) } else { !ordered };
+    Ok(f64::from_bits(bits))
+}
+
+/// Timestamps are nanoseconds since the Unix epoch in UTC, encoded like Integers.
+pub fn encode_timestamp(value: i64) -> Vec<u8> {
+    encode_integer(value)
+}
+
+pub fn decode_timestamp(body: Vec<u8>) -> Result<i64, LigatureError> {
+    decode_integer(body)
+}
+
+/// Encodes the start of a Language or Typed Literal's dictionary entry,
+/// the value prefix of the kind of tag followed by the tag and a zero byte.
+/// Entries with the same tag share this prefix so they can be found with a prefix scan.
+pub fn encode_literal_tag(value_prefix: u8, tag: &str) -> Result<Vec<u8>, LigatureError> {
+    if tag.is_empty() || tag.contains('\0') {
+        return Err(LigatureError(format!("Invalid Literal tag {:?}", tag)));
+    }
+    if value_prefix == LANGUAGE_VALUE_PREFIX
+        && !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(LigatureError(format!("Invalid language tag {:?}", tag)));
+    }
+    let mut encoded = vec![value_prefix];
+    encoded.extend_from_slice(tag.as_bytes());
+    encoded.push(0);
+    Ok(encoded)
+}
+
+/// Encodes a Language or Typed Literal's dictionary entry.
+pub fn encode_tagged_literal(value: &Value) -> Result<Vec<u8>, LigatureError> {
+    let (value_prefix, value, tag) = match value {
+        Value::LanguageLiteral(value, language) => (LANGUAGE_VALUE_PREFIX, value, language),
+        Value::TypedLiteral(value, datatype) => (TYPED_VALUE_PREFIX, value, datatype),
+        _ => {
+            return Err(LigatureError(format!(
+                "{:?} is not a tagged Literal",
+                value
+            )))
+        }
+    };
+    let mut encoded = encode_literal_tag(value_prefix, tag)?;
+    encoded.extend_from_slice(value.as_bytes());
+    Ok(encoded)
+}
+
+pub fn decode_tagged_literal(encoded: Vec<u8>) -> Result<Value, LigatureError> {
+    let invalid = || LigatureError("Invalid tagged Literal".to_string());
+    let separator = encoded.iter().position(|b| *b == 0).ok_or_else(invalid)?;
+    if separator == 0 {
+        return Err(invalid());
+    }
+    let tag = String::from_utf8(encoded[1..separator].to_vec()).map_err(|_| invalid())?;
+    let value = String::from_utf8(encoded[separator + 1..].to_vec()).map_err(|_| invalid())?;
+    match encoded[0] {
+        LANGUAGE_VALUE_PREFIX => Ok(Value::LanguageLiteral(value, tag)),
+        TYPED_VALUE_PREFIX => Ok(Value::TypedLiteral(value, tag)),
+        _ => Err(invalid()),
+    }
+}
+
+fn read_u64(body: &[u8]) -> Result<u64, LigatureError> {
+    if body.len() != 8 {
+        return Err(LigatureError(format!(
+            "Invalid Value body length {}",
+            body.len()
+        )));
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(body);
+    Ok(u64::from_be_bytes(bytes))
+}
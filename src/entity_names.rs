@@ -0,0 +1,112 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Entities can be given names, like UUIDs or URLs from other systems,
+//! in a dictionary that works the same way as the Attribute dictionary.
+
+use crate::encoding::{ENTITY_ID_TO_NAME_PREFIX, ENTITY_NAME_TO_ID_PREFIX};
+use crate::query_tx::LigatureSledQueryTx;
+use crate::write_tx::LigatureSledWriteTx;
+use ligature::{Entity, LigatureError};
+use ligature_kv::{chomp_assert, decode_id, encode_id, prepend};
+
+fn name_key(name: &str) -> Vec<u8> {
+    prepend(ENTITY_NAME_TO_ID_PREFIX, name.as_bytes().to_vec())
+}
+
+fn id_key(entity: &Entity) -> Vec<u8> {
+    prepend(ENTITY_ID_TO_NAME_PREFIX, encode_id(entity.0))
+}
+
+fn decode_name(encoded: Vec<u8>) -> Result<String, LigatureError> {
+    String::from_utf8(encoded).map_err(|_| LigatureError("Invalid Entity name.".to_string()))
+}
+
+impl LigatureSledQueryTx {
+    /// Returns the Entity with the given name if there is one.
+    pub fn lookup_named_entity(&self, name: &str) -> Result<Option<Entity>, LigatureError> {
+        let id = self
+            .store
+            .get(name_key(name))
+            .map_err(|_| LigatureError(format!("Error looking up Entity named {}", name)))?;
+        match id {
+            Some(id) => Ok(Some(Entity(decode_id(id.to_vec())?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the name of an Entity if it has one.
+    pub fn entity_name(&self, entity: &Entity) -> Result<Option<String>, LigatureError> {
+        let name = self
+            .store
+            .get(id_key(entity))
+            .map_err(|_| LigatureError(format!("Error looking up name of Entity {}", entity.0)))?;
+        match name {
+            Some(name) => Ok(Some(decode_name(name.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all named Entities whose name starts with the given prefix, in order of their names.
+    pub fn match_entity_names_prefix(
+        &self,
+        prefix: &str,
+    ) -> Box<dyn Iterator<Item = Result<(String, Entity), LigatureError>>> {
+        let iter = self.store.scan_prefix(name_key(prefix));
+        Box::new(iter.map(|entry| match entry {
+            Ok((name, id)) => Ok((
+                decode_name(chomp_assert(ENTITY_NAME_TO_ID_PREFIX, name.to_vec())?)?,
+                Entity(decode_id(id.to_vec())?),
+            )),
+            Err(_) => Err(LigatureError("Error iterating Entity names.".to_string())),
+        }))
+    }
+}
+
+impl LigatureSledWriteTx {
+    /// Returns the Entity with the given name, creating a new Entity with that name if there isn't one.
+    pub fn named_entity(&self, name: &str) -> Result<Entity, LigatureError> {
+        if name.is_empty() {
+            return Err(LigatureError("Entity names can't be empty.".to_string()));
+        }
+        let id = self
+            .store
+            .get(name_key(name))
+            .map_err(|_| LigatureError(format!("Error looking up Entity named {}", name)))?;
+        if let Some(id) = id {
+            return Ok(Entity(decode_id(id.to_vec())?));
+        }
+        let entity = self.new_entity()?;
+        let error = || LigatureError(format!("Error saving Entity named {}", name));
+        self.store
+            .insert(name_key(name), encode_id(entity.0))
+            .map_err(|_| error())?;
+        self.store
+            .insert(id_key(&entity), name.as_bytes().to_vec())
+            .map_err(|_| error())?;
+        Ok(entity)
+    }
+
+    /// Returns the name of an Entity if it has one.
+    pub fn entity_name(&self, entity: &Entity) -> Result<Option<String>, LigatureError> {
+        let name = self
+            .store
+            .get(id_key(entity))
+            .map_err(|_| LigatureError(format!("Error looking up name of Entity {}", entity.0)))?;
+        match name {
+            Some(name) => Ok(Some(decode_name(name.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes both dictionary entries for an Entity's name, if it has one.
+    pub(crate) fn remove_entity_name(&self, entity: &Entity) -> Result<(), LigatureError> {
+        if let Some(name) = self.entity_name(entity)? {
+            let error = || LigatureError(format!("Error removing name of Entity {}", entity.0));
+            self.store.remove(name_key(&name)).map_err(|_| error())?;
+            self.store.remove(id_key(entity)).map_err(|_| error())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Every allocated Entity id has an entry in the registry saying if it is a plain Entity or a Statement's context,
+//! so Statements can only be added about Entities that exist.
+
+use crate::dataset_tree;
+use crate::encoding::ENTITY_REGISTRY_PREFIX;
+use crate::query_tx::LigatureSledQueryTx;
+use crate::write_tx::LigatureSledWriteTx;
+use ligature::{Entity, LigatureError};
+use ligature_kv::{
+    decode_id, encode_id, prepend, CEAV_PREFIX, DATASET_PREFIX, ENTITY_ID_COUNTER_KEY,
+};
+use std::collections::BTreeSet;
+
+/// What an allocated Entity id is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntityKind {
+    /// An Entity made with new_entity.
+    Entity,
+    /// The context of a Statement.
+    Context,
+}
+
+impl EntityKind {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            EntityKind::Entity => vec![0],
+            EntityKind::Context => vec![1],
+        }
+    }
+
+    fn decode(entity_id: u64, encoded: &[u8]) -> Result<Self, LigatureError> {
+        match encoded {
+            [0] => Ok(EntityKind::Entity),
+            [1] => Ok(EntityKind::Context),
+            _ => Err(LigatureError(format!(
+                "Invalid registry entry for Entity {}",
+                entity_id
+            ))),
+        }
+    }
+}
+
+fn registry_key(entity_id: u64) -> Vec<u8> {
+    prepend(ENTITY_REGISTRY_PREFIX, encode_id(entity_id))
+}
+
+impl LigatureSledQueryTx {
+    /// Checks if an Entity was made with new_entity and hasn't been deleted.
+    /// Returns false for the contexts of Statements.
+    pub fn entity_exists(&self, entity: &Entity) -> Result<bool, LigatureError> {
+        let kind = self
+            .store
+            .get(registry_key(entity.0))
+            .map_err(|_| LigatureError(format!("Error looking up Entity {}", entity.0)))?;
+        match kind {
+            Some(kind) => Ok(EntityKind::decode(entity.0, &kind)? == EntityKind::Entity),
+            None => Ok(false),
+        }
+    }
+}
+
+impl LigatureSledWriteTx {
+    /// Allocates a new Entity id and registers it.
+    pub(crate) fn allocate_entity(&self, kind: EntityKind) -> Result<Entity, LigatureError> {
+        let next_id_value = self.read_id(ENTITY_ID_COUNTER_KEY)? + 1;
+        self.store
+            .insert(vec![ENTITY_ID_COUNTER_KEY], encode_id(next_id_value))
+            .map_err(|_| LigatureError("Could not increment Dataset Counter".to_string()))?;
+        self.store
+            .insert(registry_key(next_id_value), kind.encode())
+            .map_err(|_| LigatureError(format!("Could not register Entity {}", next_id_value)))?;
+        Ok(Entity(next_id_value))
+    }
+
+    /// Returns what an Entity id is used for, or None if it was never allocated or has been deleted.
+    pub(crate) fn entity_kind(&self, entity: &Entity) -> Result<Option<EntityKind>, LigatureError> {
+        let kind = self
+            .store
+            .get(registry_key(entity.0))
+            .map_err(|_| LigatureError(format!("Error looking up Entity {}", entity.0)))?;
+        match kind {
+            Some(kind) => Ok(Some(EntityKind::decode(entity.0, &kind)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes an Entity from the registry.
+    pub(crate) fn unregister_entity(&self, entity: &Entity) -> Result<(), LigatureError> {
+        self.store
+            .remove(registry_key(entity.0))
+            .map_err(|_| LigatureError(format!("Could not unregister Entity {}", entity.0)))?;
+        Ok(())
+    }
+}
+
+/// Version 7 added the Entity registry.
+/// Ids used as a context in CEAV are registered as contexts and every other allocated id as an Entity,
+/// since Entities that were never used can't be told apart from ones that were.
+pub fn register_entities(store: &sled::Db) -> Result<(), LigatureError> {
+    let error = || LigatureError("Error registering Entities.".to_string());
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        let tree = store
+            .open_tree(dataset_tree::tree_name(decode_id(id.to_vec())?))
+            .map_err(|_| LigatureError("Error opening dataset tree.".to_string()))?;
+        let last_id = match tree.get(vec![ENTITY_ID_COUNTER_KEY]).map_err(|_| error())? {
+            Some(last_id) => decode_id(last_id.to_vec())?,
+            None => continue,
+        };
+        let mut contexts = BTreeSet::new();
+        for key in tree.scan_prefix(vec![CEAV_PREFIX]).keys() {
+            let key = key.map_err(|_| error())?;
+            contexts.insert(decode_id(key[1..9].to_vec())?);
+        }
+        for entity_id in 1..=last_id {
+            let kind = if contexts.contains(&entity_id) {
+                EntityKind::Context
+            } else {
+                EntityKind::Entity
+            };
+            tree.insert(registry_key(entity_id), kind.encode())
+                .map_err(|_| error())?;
+        }
+    }
+    Ok(())
+}
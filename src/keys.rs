@@ -0,0 +1,215 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers for reading and building the Statement permutation keys described in the README.
+
+use crate::encoding::{
+    decode_boolean, decode_float, decode_integer, decode_tagged_literal, decode_timestamp,
+    encode_boolean, encode_float, encode_integer, encode_tagged_literal, encode_timestamp,
+    BOOLEAN_VALUE_PREFIX, BYTES_LITERAL_ID_TO_VALUE_PREFIX, BYTES_LITERAL_VALUE_TO_ID_PREFIX,
+    BYTES_VALUE_PREFIX, LANGUAGE_VALUE_PREFIX, TAGGED_LITERAL_ID_TO_VALUE_PREFIX,
+    TAGGED_LITERAL_VALUE_TO_ID_PREFIX, TIMESTAMP_VALUE_PREFIX, TYPED_VALUE_PREFIX,
+};
+use crate::large_literal::{read_large_literal, string_literal_key};
+use crate::value::Value;
+use ligature::{Entity, LigatureError};
+use ligature_kv::{
+    decode_id, decode_string_literal, encode_id, prepend, StatementIDSet, AEVC_PREFIX, AVEC_PREFIX,
+    CEAV_PREFIX, EAVC_PREFIX, ENTITY_VALUE_PREFIX, EVAC_PREFIX, FLOAT_VALUE_PREFIX,
+    INTEGER_VALUE_PREFIX, STRING_LITERAL_ID_TO_VALUE_PREFIX, STRING_VALUE_PREFIX, VAEC_PREFIX,
+    VEAC_PREFIX,
+};
+use std::ops::Bound;
+
+/// The length of a full permutation key.
+/// A prefix, an Entity, an Attribute, a Value with its type prefix and a Context.
+pub const PERMUTATION_KEY_LENGTH: usize = 1 + 8 + 8 + 9 + 8;
+
+/// Decodes any of the seven Statement permutations into a StatementIDSet.
+pub fn decode_permutation(key: &[u8]) -> Result<StatementIDSet, LigatureError> {
+    if key.len() != PERMUTATION_KEY_LENGTH {
+        return Err(LigatureError(format!(
+            "Invalid Statement permutation length {}",
+            key.len()
+        )));
+    }
+    let body = &key[1..];
+    // offsets of the entity, attribute, value and context in the key body
+    let (e, a, v, c) = match key[0] {
+        EAVC_PREFIX => (0, 8, 16, 25),
+        EVAC_PREFIX => (0, 17, 8, 25),
+        AEVC_PREFIX => (8, 0, 16, 25),
+        AVEC_PREFIX => (17, 0, 8, 25),
+        VEAC_PREFIX => (9, 17, 0, 25),
+        VAEC_PREFIX => (17, 9, 0, 25),
+        CEAV_PREFIX => (8, 16, 24, 0),
+        prefix => {
+            return Err(LigatureError(format!(
+                "Unknown Statement permutation prefix {}",
+                prefix
+            )))
+        }
+    };
+    Ok(StatementIDSet {
+        entity_id: read_u64(body, e),
+        attribute_id: read_u64(body, a),
+        value_prefix: body[v],
+        value_body: body[v + 1..v + 9].to_vec(),
+        context_id: read_u64(body, c),
+    })
+}
+
+/// Returns the key prefix of the permutation best suited to find Statements matching the given ids.
+/// The value is passed as its type prefix and body.
+pub fn match_prefix(
+    entity_id: Option<u64>,
+    attribute_id: Option<u64>,
+    value: Option<&(u8, Vec<u8>)>,
+) -> Vec<u8> {
+    let entity = entity_id.map(encode_id);
+    let attribute = attribute_id.map(encode_id);
+    let value = value.map(|(prefix, body)| {
+        let mut encoded = vec![*prefix];
+        encoded.extend_from_slice(body);
+        encoded
+    });
+    let (prefix, parts) = match (entity, attribute, value) {
+        (Some(e), Some(a), Some(v)) => (EAVC_PREFIX, vec![e, a, v]),
+        (Some(e), Some(a), None) => (EAVC_PREFIX, vec![e, a]),
+        (Some(e), None, Some(v)) => (EVAC_PREFIX, vec![e, v]),
+        (Some(e), None, None) => (EAVC_PREFIX, vec![e]),
+        (None, Some(a), Some(v)) => (AVEC_PREFIX, vec![a, v]),
+        (None, Some(a), None) => (AEVC_PREFIX, vec![a]),
+        (None, None, Some(v)) => (VEAC_PREFIX, vec![v]),
+        (None, None, None) => (EAVC_PREFIX, vec![]),
+    };
+    let mut key = vec![prefix];
+    for part in parts {
+        key.extend(part);
+    }
+    key
+}
+
+/// A range of permutation keys, scans are resumed by replacing one of its bounds with the last key read.
+pub type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Returns the range of keys starting with the given prefix.
+pub fn prefix_range(prefix: Vec<u8>) -> KeyRange {
+    let mut end = prefix.clone();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return (Bound::Included(prefix), Bound::Excluded(end));
+        }
+    }
+    (Bound::Included(prefix), Bound::Unbounded)
+}
+
+/// Returns the key prefix of the permutation that puts the Value right after the given ids,
+/// so a range of Values is a single range of keys.
+pub fn range_prefix(entity_id: Option<u64>, attribute_id: Option<u64>) -> Vec<u8> {
+    let (prefix, parts) = match (entity_id, attribute_id) {
+        (Some(e), Some(a)) => (EAVC_PREFIX, vec![e, a]),
+        (Some(e), None) => (EVAC_PREFIX, vec![e]),
+        (None, Some(a)) => (AVEC_PREFIX, vec![a]),
+        (None, None) => (VEAC_PREFIX, vec![]),
+    };
+    let mut key = vec![prefix];
+    for part in parts {
+        key.extend(encode_id(part));
+    }
+    key
+}
+
+/// Returns the type prefix and body of a Value without creating it, reading dictionaries with the given function.
+/// Returns None for String, Bytes and tagged Literals that have never been stored.
+pub fn lookup_value<G>(value: &Value, get: G) -> Result<Option<(u8, Vec<u8>)>, LigatureError>
+where
+    G: Fn(Vec<u8>) -> Result<Option<Vec<u8>>, LigatureError>,
+{
+    let dictionary =
+        |value_prefix: u8, key: Vec<u8>| -> Result<Option<(u8, Vec<u8>)>, LigatureError> {
+            Ok(get(key)?.map(|id| (value_prefix, id)))
+        };
+    match value {
+        Value::Entity(entity) => Ok(Some((ENTITY_VALUE_PREFIX, encode_id(entity.0)))),
+        Value::StringLiteral(value) => dictionary(STRING_VALUE_PREFIX, string_literal_key(value)),
+        Value::IntegerLiteral(value) => Ok(Some((INTEGER_VALUE_PREFIX, encode_integer(*value)))),
+        Value::FloatLiteral(value) => Ok(Some((FLOAT_VALUE_PREFIX, encode_float(*value)))),
+        Value::BooleanLiteral(value) => Ok(Some((BOOLEAN_VALUE_PREFIX, encode_boolean(*value)))),
+        Value::BytesLiteral(value) => dictionary(
+            BYTES_VALUE_PREFIX,
+            prepend(BYTES_LITERAL_VALUE_TO_ID_PREFIX, value.clone()),
+        ),
+        Value::TimestampLiteral(value) => {
+            Ok(Some((TIMESTAMP_VALUE_PREFIX, encode_timestamp(*value))))
+        }
+        Value::LanguageLiteral(_, _) => dictionary(
+            LANGUAGE_VALUE_PREFIX,
+            prepend(
+                TAGGED_LITERAL_VALUE_TO_ID_PREFIX,
+                encode_tagged_literal(value)?,
+            ),
+        ),
+        Value::TypedLiteral(_, _) => dictionary(
+            TYPED_VALUE_PREFIX,
+            prepend(
+                TAGGED_LITERAL_VALUE_TO_ID_PREFIX,
+                encode_tagged_literal(value)?,
+            ),
+        ),
+    }
+}
+
+/// Loads a Value from its type prefix and body, reading dictionaries with the given function.
+pub fn load_value<G>(value_prefix: u8, value_body: Vec<u8>, get: G) -> Result<Value, LigatureError>
+where
+    G: Fn(Vec<u8>) -> Result<Option<Vec<u8>>, LigatureError>,
+{
+    let dictionary = |id_to_value_prefix: u8,
+                      name: &str,
+                      value_body: Vec<u8>|
+     -> Result<Vec<u8>, LigatureError> {
+        let id = decode_id(value_body)?;
+        get(prepend(id_to_value_prefix, encode_id(id)))?
+            .ok_or_else(|| LigatureError(format!("Could not find {} with id = {}", name, id)))
+    };
+    match value_prefix {
+        ENTITY_VALUE_PREFIX => Ok(Value::Entity(Entity(decode_id(value_body)?))),
+        STRING_VALUE_PREFIX => {
+            let id = decode_id(value_body)?;
+            let encoded = match get(prepend(STRING_LITERAL_ID_TO_VALUE_PREFIX, encode_id(id)))? {
+                Some(encoded) => encoded,
+                None => read_large_literal(id, &get)?.ok_or_else(|| {
+                    LigatureError(format!("Could not find String Literal with id = {}", id))
+                })?,
+            };
+            Ok(Value::StringLiteral(decode_string_literal(encoded)?))
+        }
+        INTEGER_VALUE_PREFIX => Ok(Value::IntegerLiteral(decode_integer(value_body)?)),
+        FLOAT_VALUE_PREFIX => Ok(Value::FloatLiteral(decode_float(value_body)?)),
+        BOOLEAN_VALUE_PREFIX => Ok(Value::BooleanLiteral(decode_boolean(value_body)?)),
+        BYTES_VALUE_PREFIX => Ok(Value::BytesLiteral(dictionary(
+            BYTES_LITERAL_ID_TO_VALUE_PREFIX,
+            "Bytes Literal",
+            value_body,
+        )?)),
+        TIMESTAMP_VALUE_PREFIX => Ok(Value::TimestampLiteral(decode_timestamp(value_body)?)),
+        LANGUAGE_VALUE_PREFIX | TYPED_VALUE_PREFIX => decode_tagged_literal(dictionary(
+            TAGGED_LITERAL_ID_TO_VALUE_PREFIX,
+            "Literal",
+            value_body,
+        )?),
+        _ => Err(LigatureError(format!(
+            "Unknown value type {}",
+            value_prefix
+        ))),
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_be_bytes(id)
+}
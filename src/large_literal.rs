@@ -0,0 +1,380 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! String Literals longer than LARGE_LITERAL_THRESHOLD bytes are stored out of line.
+//! Their dictionary key is a SHA-256 hash of the whole string, and the string itself is stored
+//! in chunks that can be read without loading all of them.
+//! The chunks are removed with the last Statement that uses the String Literal.
+
+use crate::encoding::{
+    LARGE_LITERAL_CHUNK_PREFIX, LARGE_LITERAL_HASH_TO_ID_PREFIX, LARGE_LITERAL_ID_TO_HASH_PREFIX,
+};
+use crate::keys::decode_permutation;
+use crate::query_tx::LigatureSledQueryTx;
+use crate::write_tx::LigatureSledWriteTx;
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::{
+    decode_id, encode_id, encode_string_literal, prepend, CEAV_PREFIX,
+    STRING_LITERAL_ID_TO_VALUE_PREFIX, STRING_LITERAL_VALUE_TO_ID_PREFIX, STRING_VALUE_PREFIX,
+    VEAC_PREFIX,
+};
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read};
+
+/// String Literals with an encoding longer than this many bytes are stored out of line.
+pub const LARGE_LITERAL_THRESHOLD: usize = 1024;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const HASH_LENGTH: usize = 32;
+
+/// The key of a String Literal in the String Literal to ID dictionary,
+/// or in the Large Literal Hash to ID dictionary if it is over the threshold.
+/// Large String Literals aren't in the sorted dictionary so they're never part of a StringLiteralRange.
+pub fn string_literal_key(string_literal: &str) -> Vec<u8> {
+    let encoded = encode_string_literal(string_literal);
+    if encoded.len() <= LARGE_LITERAL_THRESHOLD {
+        prepend(STRING_LITERAL_VALUE_TO_ID_PREFIX, encoded)
+    } else {
+        prepend(
+            LARGE_LITERAL_HASH_TO_ID_PREFIX,
+            Sha256::digest(&encoded).to_vec(),
+        )
+    }
+}
+
+/// The prefix of every VEAC key of a Statement with the given String Literal as its Value.
+fn string_literal_statements_prefix(string_literal_id: u64) -> Vec<u8> {
+    let mut prefix = vec![VEAC_PREFIX, STRING_VALUE_PREFIX];
+    prefix.extend(encode_id(string_literal_id));
+    prefix
+}
+
+/// Every key a large String Literal is stored under, given its ID to Large Literal entry.
+fn large_literal_keys(
+    string_literal_id: u64,
+    reference: &[u8],
+) -> Result<Vec<Vec<u8>>, LigatureError> {
+    let (hash, len) = decode_reference(string_literal_id, reference)?;
+    let mut keys: Vec<Vec<u8>> = (0..chunk_count(len))
+        .map(|index| chunk_key(&hash, index))
+        .collect();
+    keys.push(prepend(
+        LARGE_LITERAL_ID_TO_HASH_PREFIX,
+        encode_id(string_literal_id),
+    ));
+    keys.push(prepend(LARGE_LITERAL_HASH_TO_ID_PREFIX, hash));
+    Ok(keys)
+}
+
+/// Version 13 keys large String Literals by their hash alone instead of the start of the string and the hash,
+/// and removes the large String Literals left behind by Statements removed before then.
+/// Each String Literal is moved in one batch so rerunning this only moves the ones that are left.
+pub fn key_by_hash(tree: &sled::Tree) -> Result<(), LigatureError> {
+    let error = || LigatureError("Error moving large String Literals.".to_string());
+    for entry in tree.scan_prefix(vec![STRING_LITERAL_VALUE_TO_ID_PREFIX]) {
+        let (key, id) = entry.map_err(|_| error())?;
+        if key.len() <= 1 + LARGE_LITERAL_THRESHOLD {
+            continue;
+        }
+        let mut batch = sled::Batch::default();
+        batch.remove(key.clone());
+        batch.insert(
+            prepend(
+                LARGE_LITERAL_HASH_TO_ID_PREFIX,
+                key[key.len() - HASH_LENGTH..].to_vec(),
+            ),
+            id,
+        );
+        tree.apply_batch(batch).map_err(|_| error())?;
+    }
+    for entry in tree.scan_prefix(vec![LARGE_LITERAL_ID_TO_HASH_PREFIX]) {
+        let (key, reference) = entry.map_err(|_| error())?;
+        let string_literal_id = decode_id(key[1..].to_vec())?;
+        if let Some(entry) = tree
+            .scan_prefix(string_literal_statements_prefix(string_literal_id))
+            .next()
+        {
+            entry.map_err(|_| error())?;
+            continue;
+        }
+        let mut batch = sled::Batch::default();
+        for key in large_literal_keys(string_literal_id, &reference)? {
+            batch.remove(key);
+        }
+        tree.apply_batch(batch).map_err(|_| error())?;
+    }
+    Ok(())
+}
+
+fn chunk_key(hash: &[u8], index: u32) -> Vec<u8> {
+    let mut key = prepend(LARGE_LITERAL_CHUNK_PREFIX, hash.to_vec());
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+impl LigatureSledWriteTx {
+    /// Stores the body of a String Literal, out of line if it is over the threshold.
+    pub(crate) fn store_string_literal_body(
+        &self,
+        string_literal_id: u64,
+        string_literal: &str,
+    ) -> Result<(), LigatureError> {
+        let encoded = encode_string_literal(string_literal);
+        let error = || LigatureError(format!("Error saving String Literal {}", string_literal_id));
+        if encoded.len() <= LARGE_LITERAL_THRESHOLD {
+            self.store
+                .insert(
+                    prepend(
+                        STRING_LITERAL_ID_TO_VALUE_PREFIX,
+                        encode_id(string_literal_id),
+                    ),
+                    encoded,
+                )
+                .map_err(|_| error())?;
+            return Ok(());
+        }
+        let hash = Sha256::digest(&encoded).to_vec();
+        for (index, chunk) in encoded.chunks(CHUNK_SIZE).enumerate() {
+            self.store
+                .insert(chunk_key(&hash, index as u32), chunk.to_vec())
+                .map_err(|_| error())?;
+        }
+        let mut reference = hash;
+        reference.extend(encode_id(encoded.len() as u64));
+        self.store
+            .insert(
+                prepend(
+                    LARGE_LITERAL_ID_TO_HASH_PREFIX,
+                    encode_id(string_literal_id),
+                ),
+                reference,
+            )
+            .map_err(|_| error())?;
+        Ok(())
+    }
+
+    /// Removes a large String Literal once no Statement has it as its Value.
+    /// Small String Literals are kept in the dictionary.
+    pub(crate) fn remove_unused_large_literal(
+        &self,
+        string_literal_id: u64,
+    ) -> Result<(), LigatureError> {
+        let error = || {
+            LigatureError(format!(
+                "Error removing String Literal {}",
+                string_literal_id
+            ))
+        };
+        let reference = self
+            .store
+            .get(prepend(
+                LARGE_LITERAL_ID_TO_HASH_PREFIX,
+                encode_id(string_literal_id),
+            ))
+            .map_err(|_| error())?;
+        let reference = match reference {
+            Some(reference) => reference,
+            None => return Ok(()),
+        };
+        if let Some(entry) = self
+            .store
+            .scan_prefix(string_literal_statements_prefix(string_literal_id))
+            .next()
+        {
+            entry?;
+            return Ok(());
+        }
+        for key in large_literal_keys(string_literal_id, &reference)? {
+            self.store.remove(key).map_err(|_| error())?;
+        }
+        Ok(())
+    }
+}
+
+impl LigatureSledQueryTx {
+    /// Opens a reader over each String Literal Value of the Statements with the given Entity, Attribute and context.
+    /// Large String Literals are read a chunk at a time instead of being loaded all at once.
+    /// Contexts can be shared, so there is one reader for every String Literal the Entity has
+    /// for the Attribute in the context, in dictionary id order.
+    /// Returns no readers if there is no such Statement with a String Literal Value.
+    pub fn string_literal_readers(
+        &self,
+        entity: &Entity,
+        attribute: &Attribute,
+        context: &Entity,
+    ) -> Result<Vec<LiteralReader>, LigatureError> {
+        let attribute_id = match self.lookup_attribute(attribute)? {
+            Some(attribute_id) => attribute_id,
+            None => return Ok(vec![]),
+        };
+        let mut prefix = prepend(CEAV_PREFIX, encode_id(context.0));
+        prefix.extend(encode_id(entity.0));
+        prefix.extend(encode_id(attribute_id));
+        prefix.push(STRING_VALUE_PREFIX);
+        let mut readers = vec![];
+        for key in self.store.scan_prefix(prefix).keys() {
+            let key = key.map_err(|_| LigatureError("Error looking up Statement.".to_string()))?;
+            let statement_id_set = decode_permutation(&key)?;
+            readers.push(self.literal_reader(decode_id(statement_id_set.value_body)?)?);
+        }
+        Ok(readers)
+    }
+
+    /// Opens a reader over the String Literal with this id.
+    fn literal_reader(&self, string_literal_id: u64) -> Result<LiteralReader, LigatureError> {
+        if let Some(reader) = self.large_literal_reader(string_literal_id)? {
+            return Ok(reader);
+        }
+        let body = self
+            .store
+            .get(prepend(
+                STRING_LITERAL_ID_TO_VALUE_PREFIX,
+                encode_id(string_literal_id),
+            ))
+            .map_err(|_| LigatureError("Error reading String Literal.".to_string()))?
+            .ok_or_else(|| {
+                LigatureError(format!(
+                    "Could not find String Literal with id = {}",
+                    string_literal_id
+                ))
+            })?;
+        Ok(LiteralReader {
+            store: self.store.clone(),
+            hash: vec![],
+            len: body.len() as u64,
+            chunk_count: 0,
+            next_chunk: 0,
+            chunk: Cursor::new(body.to_vec()),
+        })
+    }
+
+    /// Returns a reader if the String Literal with this id is stored out of line.
+    fn large_literal_reader(
+        &self,
+        string_literal_id: u64,
+    ) -> Result<Option<LiteralReader>, LigatureError> {
+        let reference = self
+            .store
+            .get(prepend(
+                LARGE_LITERAL_ID_TO_HASH_PREFIX,
+                encode_id(string_literal_id),
+            ))
+            .map_err(|_| LigatureError("Error reading String Literal.".to_string()))?;
+        match reference {
+            Some(reference) => {
+                let (hash, len) = decode_reference(string_literal_id, &reference)?;
+                Ok(Some(LiteralReader {
+                    store: self.store.clone(),
+                    hash: hash,
+                    len: len,
+                    chunk_count: chunk_count(len),
+                    next_chunk: 0,
+                    chunk: Cursor::new(vec![]),
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reads all of a String Literal if it is stored out of line, using the given function to read entries.
+pub fn read_large_literal<G>(
+    string_literal_id: u64,
+    get: &G,
+) -> Result<Option<Vec<u8>>, LigatureError>
+where
+    G: Fn(Vec<u8>) -> Result<Option<Vec<u8>>, LigatureError>,
+{
+    let reference = match get(prepend(
+        LARGE_LITERAL_ID_TO_HASH_PREFIX,
+        encode_id(string_literal_id),
+    ))? {
+        Some(reference) => reference,
+        None => return Ok(None),
+    };
+    let (hash, len) = decode_reference(string_literal_id, &reference)?;
+    let mut encoded = Vec::with_capacity(len as usize);
+    for index in 0..chunk_count(len) {
+        let chunk = get(chunk_key(&hash, index))?.ok_or_else(|| {
+            LigatureError(format!(
+                "Missing chunk {} of String Literal with id = {}",
+                index, string_literal_id
+            ))
+        })?;
+        encoded.extend(chunk);
+    }
+    Ok(Some(encoded))
+}
+
+/// Reads the hash and length of a large String Literal.
+fn decode_reference(
+    string_literal_id: u64,
+    reference: &[u8],
+) -> Result<(Vec<u8>, u64), LigatureError> {
+    if reference.len() != HASH_LENGTH + 8 {
+        return Err(LigatureError(format!(
+            "Invalid reference for String Literal with id = {}",
+            string_literal_id
+        )));
+    }
+    Ok((
+        reference[..HASH_LENGTH].to_vec(),
+        decode_id(reference[HASH_LENGTH..].to_vec())?,
+    ))
+}
+
+fn chunk_count(len: u64) -> u32 {
+    ((len + CHUNK_SIZE as u64 - 1) / CHUNK_SIZE as u64) as u32
+}
+
+/// Reads the encoded bytes of a String Literal.
+pub struct LiteralReader {
+    store: sled::Tree,
+    hash: Vec<u8>,
+    len: u64,
+    chunk_count: u32,
+    next_chunk: u32,
+    chunk: Cursor<Vec<u8>>,
+}
+
+impl LiteralReader {
+    /// The total length of the String Literal in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn load_next_chunk(&mut self) -> std::io::Result<bool> {
+        if self.next_chunk >= self.chunk_count {
+            return Ok(false);
+        }
+        let chunk = self
+            .store
+            .get(chunk_key(&self.hash, self.next_chunk))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Missing String Literal chunk {}", self.next_chunk),
+                )
+            })?;
+        self.chunk = Cursor::new(chunk.to_vec());
+        self.next_chunk += 1;
+        Ok(true)
+    }
+}
+
+impl Read for LiteralReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.chunk.read(buf)?;
+            if read > 0 || buf.is_empty() || !self.load_next_chunk()? {
+                return Ok(read);
+            }
+        }
+    }
+}
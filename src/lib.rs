@@ -4,34 +4,87 @@
 
 //#![deny(missing_docs)]
 
+mod aggregate;
+mod bgp;
+mod context_metadata;
+mod dataset_tree;
+mod datalog;
+mod datalog_syntax;
+mod delete;
+mod encoding;
+mod entity_names;
+mod entity_registry;
+mod keys;
+mod large_literal;
+mod migrations;
+mod pagination;
 mod query_tx;
+mod resolve;
+mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+mod sparql;
+mod sparql_syntax;
+mod stats;
+mod tagged_literal;
+mod traversal;
+mod tx_tree;
+mod value;
 mod write_tx;
 
-use ligature::{
-    Attribute, Dataset, Ligature, LigatureError, PersistedStatement, QueryFn, QueryTx, Range,
-    Statement, WriteFn, WriteTx,
-};
+use ligature::{Dataset, Ligature, LigatureError, QueryFn, WriteFn};
 use ligature_kv::{
-    chomp_assert, decode_dataset, encode_dataset, encode_dataset_match, prepend,
+    chomp_assert, decode_dataset, encode_dataset_match, encode_id, prepend,
     ATTRIBUTE_ID_COUNTER_KEY, DATASET_PREFIX, ENTITY_ID_COUNTER_KEY, STRING_LITERAL_ID_COUNTER_KEY,
 };
-use query_tx::LigatureSledQueryTx;
+pub use aggregate::{Aggregate, AggregateResult, GroupBy};
+pub use bgp::{Binding, Bindings, StatementPattern, Term};
+pub use datalog::{Atom, EvaluationLimits, Literal, Program, Rule};
+pub use delete::ReferencePolicy;
+pub use large_literal::{LiteralReader, LARGE_LITERAL_THRESHOLD};
+pub use migrations::{FORMAT_VERSION, FORMAT_VERSION_KEY};
+pub use pagination::{Cursor, Page, PageRequest};
+pub use query_tx::LigatureSledQueryTx;
+pub use schema::{AttributeSchema, Cardinality, SchemaViolation, ValueType};
+pub use sparql::{SparqlQuery, SparqlResults};
+pub use stats::DatasetStats;
+pub use traversal::{Direction, Edge, Traversal};
 use std::sync::RwLock;
-use write_tx::LigatureSledWriteTx;
+pub use value::{PersistedStatement, Range, Statement, Value};
+pub use write_tx::{LigatureSledWriteTx, WriteError};
+
+/// The message of the LigatureError returned when trying to modify a read only instance.
+pub const READ_ONLY_ERROR: &str = "This instance of LigatureSled is read only.";
 
 pub struct LigatureSled {
     //TODO eventually I won't need this but for now to support ReadTx range searches I need this lock
     //TODO an improvement on this would be pre-tree locks
     store_lock: RwLock<sled::Db>,
+    read_only: bool,
 }
 
 impl LigatureSled {
     /// Create/Open an instance of LigatureSled at the given path.
     pub fn new(path: String) -> Result<Self, sled::Error> {
         let instance = sled::open(path)?;
-        Ok(Self {
-            store_lock: RwLock::new(instance),
-        })
+        LigatureSled::from_db(instance, false)
+    }
+
+    /// Open an existing instance of LigatureSled at the given path that can't be modified through its API.
+    /// Creating or deleting Datasets and writes return a LigatureError with the READ_ONLY_ERROR message.
+    /// This is only read only at the API level, sled still takes its exclusive lock on the path
+    /// and can write to it while opening, recovering and flushing the store.
+    /// No other process can open the store while it is open, not even another reader,
+    /// use read_only_handle to get more read only handles to the same store.
+    pub fn open_read_only(path: String) -> Result<Self, sled::Error> {
+        if !std::path::Path::new(&path).exists() {
+            return Err(sled::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No store found at {}", path),
+            )));
+        }
+        let instance = sled::Config::default().path(path).open()?;
+        LigatureSled::from_db(instance, true)
     }
 
     /// Create a temporary instance of LigatureSled that is deleted on close.
@@ -40,15 +93,11 @@ impl LigatureSled {
         match path {
             None => {
                 let instance = sled::Config::default().temporary(true).open()?;
-                Ok(Self {
-                    store_lock: RwLock::new(instance),
-                })
+                LigatureSled::from_db(instance, false)
             }
             Some(p) => {
                 let instance = sled::Config::default().temporary(true).path(p).open()?;
-                Ok(Self {
-                    store_lock: RwLock::new(instance),
-                })
+                LigatureSled::from_db(instance, false)
             }
         }
     }
@@ -57,11 +106,178 @@ impl LigatureSled {
     /// Most people won't need this since the defaults are very good.
     pub fn from_config(config: sled::Config) -> Result<Self, sled::Error> {
         let instance = config.open()?;
+        LigatureSled::from_db(instance, false)
+    }
+
+    fn from_db(instance: sled::Db, read_only: bool) -> Result<Self, sled::Error> {
+        migrations::migrate(&instance, read_only).map_err(|e| sled::Error::Unsupported(e.0))?;
+        // legacy Dataset trees can have names that look orphaned, so this has to run after migrating
+        if !read_only {
+            dataset_tree::remove_orphaned_trees(&instance)
+                .map_err(|e| sled::Error::Unsupported(e.0))?;
+        }
         Ok(Self {
             store_lock: RwLock::new(instance),
+            read_only: read_only,
         })
     }
 
+    /// Returns a new read only handle that shares this instance's store.
+    pub fn read_only_handle(&self) -> Result<Self, LigatureError> {
+        let store = self
+            .store_lock
+            .read()
+            .map_err(|_| LigatureError("Error creating read only handle.".to_string()))?;
+        LigatureSled::from_db(store.clone(), true)
+            .map_err(|_| LigatureError("Error creating read only handle.".to_string()))
+    }
+
+    /// Returns true if this instance can't be modified.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self) -> Result<(), LigatureError> {
+        if self.read_only {
+            Err(LigatureError(READ_ONLY_ERROR.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns statement, entity, attribute and string literal counts, sizes and counters for a Dataset.
+    pub fn dataset_stats(&self, dataset: &Dataset) -> Result<DatasetStats, LigatureError> {
+        self.query_sled(dataset, |tx| tx.stats())
+    }
+
+    /// Runs a query against the given Dataset with access to the sled specific query functionality.
+    pub fn query_sled<T, F>(&self, dataset: &Dataset, f: F) -> Result<T, LigatureError>
+    where
+        F: FnOnce(&LigatureSledQueryTx) -> Result<T, LigatureError>,
+    {
+        let store = self
+            .store_lock
+            .read()
+            .map_err(|_| LigatureError("Error starting query transaction.".to_string()))?;
+        match dataset_tree::open_dataset_tree(&store, dataset)? {
+            Some(tree) => f(&LigatureSledQueryTx::new(tree)),
+            None => Err(LigatureError(
+                "Error starting query transaction.".to_string(),
+            )),
+        }
+    }
+
+    /// Runs a write against the given Dataset with access to the sled specific write functionality.
+    /// The passed function can be called more than once if sled needs to retry the transaction.
+    pub fn write_sled<T, F>(&self, dataset: &Dataset, f: F) -> Result<T, LigatureError>
+    where
+        F: Fn(&LigatureSledWriteTx) -> Result<T, LigatureError>,
+    {
+        self.try_write_sled(dataset, f)
+    }
+
+    /// Like write_sled, but the passed function can fail with its own type of error.
+    /// That error is returned as it is and errors from the store are converted into it.
+    pub fn try_write_sled<T, E, F>(&self, dataset: &Dataset, f: F) -> Result<T, E>
+    where
+        E: From<LigatureError>,
+        F: Fn(&LigatureSledWriteTx) -> Result<T, E>,
+    {
+        self.check_writable()?;
+        let store = self
+            .store_lock
+            .write()
+            .map_err(|_| LigatureError("Error starting write transaction.".to_string()))?;
+        match dataset_tree::open_dataset_tree(&store, dataset)? {
+            Some(tree) => {
+                let res = tree.transaction(|transaction_tree| {
+                    let write_tx = LigatureSledWriteTx::new(transaction_tree.clone(), tree.clone());
+                    let res = f(&write_tx);
+                    if write_tx.active.get() {
+                        match res {
+                            Ok(value) => match write_tx.store.write_counters(encoding::DATASET_SIZE_KEY) {
+                                Ok(()) => Ok(value),
+                                Err(err) => sled::transaction::abort(E::from(err)),
+                            },
+                            Err(err) => sled::transaction::abort(err),
+                        }
+                    } else {
+                        sled::transaction::abort(E::from(LigatureError(
+                            "Aborting transaction.".to_string(),
+                        )))
+                    }
+                });
+                res.map_err(|e| match e {
+                    sled::transaction::TransactionError::Abort(e) => e,
+                    sled::transaction::TransactionError::Storage(e) => {
+                        LigatureError(format!("Error with writetx - {:?}.", e)).into()
+                    }
+                })
+            }
+            None => Err(LigatureError(
+                "Error starting write transaction.".to_string(),
+            ).into()),
+        }
+    }
+
+    /// Copies all Statements, dictionaries and counters of a Dataset into a new Dataset.
+    /// Fails if the target Dataset already exists.
+    pub fn copy_dataset(&self, from: &Dataset, to: &Dataset) -> Result<(), LigatureError> {
+        self.check_writable()?;
+        let store = self.store_lock.write().map_err(|_| {
+            LigatureError(format!(
+                "Error starting write transaction when copying dataset {:?}.",
+                from
+            ))
+        })?;
+        let from_tree = LigatureSled::check_move(&store, from, to)?;
+        let contents = dataset_tree::tree_contents(&from_tree)?;
+        let dataset_id = dataset_tree::allocate_dataset_id(&store)?;
+        let to_tree = store
+            .open_tree(dataset_tree::tree_name(dataset_id))
+            .map_err(|_| LigatureError(format!("Error creating dataset tree for {:?}.", to)))?;
+        dataset_tree::insert_dataset(&store, to, dataset_id, &to_tree, &contents)
+    }
+
+    /// Moves a Dataset to a new name.
+    /// Fails if the target Dataset already exists.
+    pub fn rename_dataset(&self, from: &Dataset, to: &Dataset) -> Result<(), LigatureError> {
+        self.check_writable()?;
+        let store = self.store_lock.write().map_err(|_| {
+            LigatureError(format!(
+                "Error starting write transaction when renaming dataset {:?}.",
+                from
+            ))
+        })?;
+        LigatureSled::check_move(&store, from, to)?;
+        let encoded_from = dataset_tree::dataset_key(from);
+        let encoded_to = dataset_tree::dataset_key(to);
+        // the tree is named after the Dataset's id so only the entry in the root tree has to move
+        store
+            .transaction(|root| {
+                let dataset_id = root.remove(encoded_from.clone())?.unwrap_or_default();
+                root.insert(encoded_to.clone(), dataset_id)?;
+                Ok(())
+            })
+            .map_err(|_: sled::transaction::TransactionError<()>| {
+                LigatureError(format!("Error renaming dataset {:?} to {:?}.", from, to))
+            })?;
+        Ok(())
+    }
+
+    /// Checks that a Dataset can be copied or renamed and returns the tree of the source Dataset.
+    fn check_move(
+        store: &sled::Db,
+        from: &Dataset,
+        to: &Dataset,
+    ) -> Result<sled::Tree, LigatureError> {
+        if LigatureSled::internal_dataset_exists(store, &dataset_tree::dataset_key(to))? {
+            return Err(LigatureError(format!("Dataset {:?} already exists.", to)));
+        }
+        dataset_tree::open_dataset_tree(store, from)?
+            .ok_or_else(|| LigatureError(format!("Dataset {:?} doesn't exist.", from)))
+    }
+
     fn internal_dataset_exists(
         store: &sled::Db,
         encoded_dataset: &Vec<u8>,
@@ -84,8 +300,7 @@ impl Ligature for LigatureSled {
 
     fn dataset_exists(&self, dataset: &Dataset) -> Result<bool, LigatureError> {
         let store = self.store_lock.read().unwrap(); //to use map_err
-        let encoded_dataset = prepend(DATASET_PREFIX, encode_dataset(&dataset));
-        LigatureSled::internal_dataset_exists(&store, &encoded_dataset)
+        LigatureSled::internal_dataset_exists(&store, &dataset_tree::dataset_key(dataset))
     }
 
     fn match_datasets_prefix(
@@ -103,7 +318,7 @@ impl Ligature for LigatureSled {
                 let res = store.scan_prefix(encoded_prefix);
                 Box::new(res.map(|value_res| match value_res {
                     Ok(value) => decode_dataset(chomp_assert(DATASET_PREFIX, value.0.to_vec())?),
-                    Err(e) => Err(LigatureError(
+                    Err(_) => Err(LigatureError(
                         "Error presfix matching Datasets.".to_string(),
                     )),
                 }))
@@ -129,7 +344,7 @@ impl Ligature for LigatureSled {
                 let res = store.range(encoded_from..encoded_to);
                 Box::new(res.map(|value_res| match value_res {
                     Ok(value) => decode_dataset(chomp_assert(DATASET_PREFIX, value.0.to_vec())?),
-                    Err(e) => Err(LigatureError(
+                    Err(_) => Err(LigatureError(
                         "Error presfix matching Datasets.".to_string(),
                     )),
                 }))
@@ -139,116 +354,62 @@ impl Ligature for LigatureSled {
     }
 
     fn create_dataset(&self, dataset: &Dataset) -> Result<(), LigatureError> {
+        self.check_writable()?;
         let store = self.store_lock.write().map_err(|_| {
             LigatureError(format!(
                 "Error starting write transaction when adding dataset {:?}.",
                 dataset
             ))
         })?;
-        let encoded_dataset = prepend(DATASET_PREFIX, encode_dataset(dataset));
+        let encoded_dataset = dataset_tree::dataset_key(dataset);
         if !LigatureSled::internal_dataset_exists(&store, &encoded_dataset)? {
-            store
-                .insert(encoded_dataset, vec![])
-                .map_err(|_| LigatureError(format!("Error inserting dataset {:?}.", dataset)))?;
-            let dataset_tree = store.open_tree(dataset.name()).map_err(|_| {
-                LigatureError(format!("Error creating dataset tree for {:?}.", dataset))
-            })?;
-            let id_start: u64 = 0;
-            dataset_tree
-                .insert(vec![ENTITY_ID_COUNTER_KEY], id_start.to_be_bytes().to_vec())
-                .map_err(|_| {
-                    LigatureError(format!(
-                        "Error creating dataset entity id counter for {:?}.",
-                        dataset
-                    ))
-                })?;
-            dataset_tree
-                .insert(
-                    vec![ATTRIBUTE_ID_COUNTER_KEY],
-                    id_start.to_be_bytes().to_vec(),
-                )
-                .map_err(|_| {
-                    LigatureError(format!(
-                        "Error creating dataset attribute id counter for {:?}.",
-                        dataset
-                    ))
-                })?;
-            dataset_tree
-                .insert(
-                    vec![STRING_LITERAL_ID_COUNTER_KEY],
-                    id_start.to_be_bytes().to_vec(),
-                )
+            let dataset_id = dataset_tree::allocate_dataset_id(&store)?;
+            let tree = store
+                .open_tree(dataset_tree::tree_name(dataset_id))
                 .map_err(|_| {
-                    LigatureError(format!(
-                        "Error creating dataset string literal id counter for {:?}.",
-                        dataset
-                    ))
+                    LigatureError(format!("Error creating dataset tree for {:?}.", dataset))
                 })?;
+            let id_start: u64 = 0;
+            let mut contents = sled::Batch::default();
+            let mut size = 0;
+            for counter_key in vec![
+                ENTITY_ID_COUNTER_KEY,
+                ATTRIBUTE_ID_COUNTER_KEY,
+                STRING_LITERAL_ID_COUNTER_KEY,
+                encoding::BYTES_LITERAL_ID_COUNTER_KEY,
+                encoding::TAGGED_LITERAL_ID_COUNTER_KEY,
+            ] {
+                let value = id_start.to_be_bytes().to_vec();
+                size += stats::entry_size(&[counter_key], &value);
+                contents.insert(vec![counter_key], value);
+            }
+            contents.insert(vec![encoding::DATASET_SIZE_KEY], encode_id(size));
+            dataset_tree::insert_dataset(&store, dataset, dataset_id, &tree, &contents)?;
         }
         Ok(())
     }
 
     fn delete_dataset(&self, dataset: &Dataset) -> Result<(), LigatureError> {
+        self.check_writable()?;
         let store = self.store_lock.write().map_err(|_| {
             LigatureError("Error starting write transaction when deleting dataset.".to_string())
         })?;
-        let encoded_dataset = prepend(DATASET_PREFIX, encode_dataset(dataset));
-        if LigatureSled::internal_dataset_exists(&store, &encoded_dataset)? {
+        if let Some(tree) = dataset_tree::open_dataset_tree(&store, dataset)? {
             store
-                .remove(&encoded_dataset)
+                .remove(dataset_tree::dataset_key(dataset))
                 .map_err(|_| LigatureError("Error removing dataset.".to_string()))?;
             store
-                .drop_tree(dataset.name())
+                .drop_tree(tree.name())
                 .map_err(|_| LigatureError("Error dropping dataset tree.".to_string()))?;
         }
         Ok(())
     }
 
     fn query<T>(&self, dataset: &Dataset, f: QueryFn<T>) -> Result<T, LigatureError> {
-        let store = self
-            .store_lock
-            .read()
-            .map_err(|_| LigatureError("Error starting query transaction.".to_string()))?;
-        let encoded_dataset = prepend(DATASET_PREFIX, encode_dataset(dataset));
-        if LigatureSled::internal_dataset_exists(&store, &encoded_dataset)? {
-            let tree = store
-                .open_tree(dataset.name())
-                .map_err(|_| LigatureError("Error starting query transaction.".to_string()))?;
-            f(Box::new(&LigatureSledQueryTx::new(tree)))
-        } else {
-            Err(LigatureError(
-                "Error starting query transaction.".to_string(),
-            ))
-        }
+        self.query_sled(dataset, |tx| f(Box::new(tx)))
     }
 
     fn write<T>(&self, dataset: &Dataset, f: WriteFn<T>) -> Result<T, LigatureError> {
-        let store = self
-            .store_lock
-            .write()
-            .map_err(|_| LigatureError("Error starting write transaction.".to_string()))?;
-        let encoded_dataset = prepend(DATASET_PREFIX, encode_dataset(dataset));
-        if LigatureSled::internal_dataset_exists(&store, &encoded_dataset)? {
-            let tree = store
-                .open_tree(dataset.name())
-                .map_err(|_| LigatureError("Error starting write transaction.".to_string()))?;
-            let res = tree.transaction(|transaction_tree| {
-                let write_tx = LigatureSledWriteTx::new(transaction_tree.clone());
-                let res = f(Box::new(&write_tx));
-                if write_tx.active.get() {
-                    match res {
-                        Ok(value) => Ok(value),
-                        Err(err) => sled::transaction::abort(err),
-                    }
-                } else {
-                    sled::transaction::abort(LigatureError("Aborting transaction.".to_string()))
-                }
-            });
-            res.map_err(|e| LigatureError(format!("Error with writetx - {:?}.", e)))
-        } else {
-            Err(LigatureError(
-                "Error starting write transaction.".to_string(),
-            ))
-        }
+        self.write_sled(dataset, |tx| f(Box::new(tx)))
     }
 }
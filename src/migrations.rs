@@ -0,0 +1,310 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The on-disk format is versioned by a key in the root tree.
+//! Opening a store runs every migration between the store's version and FORMAT_VERSION in order,
+//! recording the new version after each one so an interrupted upgrade picks up where it stopped.
+
+use crate::dataset_tree;
+use crate::encoding::{
+    encode_float, encode_integer, BYTES_LITERAL_ID_COUNTER_KEY, TAGGED_LITERAL_ID_COUNTER_KEY,
+};
+use crate::entity_registry;
+use crate::keys::decode_permutation;
+use crate::large_literal;
+use crate::stats;
+use ligature::LigatureError;
+use ligature_kv::{
+    decode_id, encode_id, encode_statement_permutations, prepend, DATASET_PREFIX, EAVC_PREFIX,
+    FLOAT_VALUE_PREFIX, INTEGER_VALUE_PREFIX,
+};
+use sled::Transactional;
+
+/// Key in the root tree of the store's format version.
+pub const FORMAT_VERSION_KEY: u8 = 2;
+
+/// The format version written by this version of ligature-sled.
+pub const FORMAT_VERSION: u64 = 13;
+
+/// Stores created before the format was versioned don't have a version key.
+const UNVERSIONED_FORMAT: u64 = 1;
+
+/// Key prefix in the root tree of the ids of Datasets whose numbers were re-encoded by the
+/// version 10 migration, only used between the version 10 and 11 migrations.
+const ORDERED_NUMBERS_PREFIX: u8 = 3;
+
+/// Upgrades a store from one format version to the next.
+/// Migrations have to be safe to rerun since a store can be closed part of the way through one.
+struct Migration {
+    from: u64,
+    description: &'static str,
+    run: fn(&sled::Db) -> Result<(), LigatureError>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "Name Dataset trees after allocated ids",
+        run: dataset_tree::migrate_legacy_trees,
+    },
+    Migration {
+        from: 2,
+        description: "Add the Bytes Literal id counter",
+        run: add_bytes_literal_counters,
+    },
+    Migration {
+        from: 3,
+        description: "Add the Tagged Literal id counter",
+        run: add_tagged_literal_counters,
+    },
+    Migration {
+        from: 4,
+        description: "Store large String Literals out of line",
+        run: no_changes,
+    },
+    Migration {
+        from: 5,
+        description: "Add Attribute schemas",
+        run: no_changes,
+    },
+    Migration {
+        from: 6,
+        description: "Register allocated Entities",
+        run: entity_registry::register_entities,
+    },
+    Migration {
+        from: 7,
+        description: "Add Entity names",
+        run: no_changes,
+    },
+    Migration {
+        from: 8,
+        description: "Add the Dataset size counter",
+        run: add_dataset_sizes,
+    },
+    Migration {
+        from: 9,
+        description: "Encode Integers and Floats in order",
+        run: order_numbers,
+    },
+    Migration {
+        from: 10,
+        description: "Remove the ordered number markers",
+        run: remove_ordered_number_markers,
+    },
+    Migration {
+        from: 11,
+        description: "Add the Statement and Entity counts",
+        run: add_dataset_counts,
+    },
+    Migration {
+        from: 12,
+        description: "Key large String Literals by hash and remove unused ones",
+        run: key_large_literals_by_hash,
+    },
+];
+
+/// Reads the format version of a store.
+pub fn format_version(store: &sled::Db) -> Result<u64, LigatureError> {
+    let version = store
+        .get(vec![FORMAT_VERSION_KEY])
+        .map_err(|_| LigatureError("Error reading format version.".to_string()))?;
+    match version {
+        Some(version) => decode_id(version.to_vec()),
+        None => {
+            let has_datasets = store
+                .scan_prefix(vec![DATASET_PREFIX])
+                .next()
+                .is_some();
+            if has_datasets {
+                Ok(UNVERSIONED_FORMAT)
+            } else {
+                // there is nothing to migrate in an empty store
+                Ok(FORMAT_VERSION)
+            }
+        }
+    }
+}
+
+/// Checks that a store can be opened and migrates it to the current format if it is writable.
+pub fn migrate(store: &sled::Db, read_only: bool) -> Result<(), LigatureError> {
+    let mut version = format_version(store)?;
+    if version > FORMAT_VERSION {
+        return Err(LigatureError(format!(
+            "Store format version {} is newer than the supported version {}.",
+            version, FORMAT_VERSION
+        )));
+    }
+    if read_only {
+        return if version < FORMAT_VERSION {
+            Err(LigatureError(format!(
+                "Store format version {} has to be migrated to version {}, open it without read only first.",
+                version, FORMAT_VERSION
+            )))
+        } else {
+            Ok(())
+        };
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        (migration.run)(store).map_err(|e| {
+            LigatureError(format!(
+                "Error migrating from format version {} ({}) - {}",
+                migration.from, migration.description, e.0
+            ))
+        })?;
+        version = migration.from + 1;
+        write_format_version(store, version)?;
+    }
+    write_format_version(store, FORMAT_VERSION)
+}
+
+/// Version 3 added Bytes Literals, which need an id counter in every Dataset tree.
+fn add_bytes_literal_counters(store: &sled::Db) -> Result<(), LigatureError> {
+    add_dataset_counter(store, BYTES_LITERAL_ID_COUNTER_KEY)
+}
+
+/// Version 4 added Language and Typed Literals, which need an id counter in every Dataset tree.
+fn add_tagged_literal_counters(store: &sled::Db) -> Result<(), LigatureError> {
+    add_dataset_counter(store, TAGGED_LITERAL_ID_COUNTER_KEY)
+}
+
+/// For versions that only add new kinds of entries, existing stores are already valid.
+/// The version still changes so older versions of ligature-sled refuse stores they can't read.
+fn no_changes(_store: &sled::Db) -> Result<(), LigatureError> {
+    Ok(())
+}
+
+/// Version 9 stores the size of every Dataset tree instead of measuring it for each stats call.
+/// Measuring again when rerun gives the same size.
+fn add_dataset_sizes(store: &sled::Db) -> Result<(), LigatureError> {
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        let tree = store
+            .open_tree(dataset_tree::tree_name(decode_id(id.to_vec())?))
+            .map_err(|_| LigatureError("Error opening dataset tree.".to_string()))?;
+        stats::record_tree_size(&tree)?;
+    }
+    Ok(())
+}
+
+/// Version 10 encodes Integers with their sign bit flipped and Floats in IEEE 754 total order,
+/// so a range of numbers is a single range of permutation keys.
+/// Each Dataset is re-encoded in one transaction that also writes a marker in the root tree,
+/// so rerunning this skips the Datasets that were already re-encoded.
+fn order_numbers(store: &sled::Db) -> Result<(), LigatureError> {
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        let dataset_id = decode_id(id.to_vec())?;
+        let marker = prepend(ORDERED_NUMBERS_PREFIX, encode_id(dataset_id));
+        let done = store
+            .contains_key(&marker)
+            .map_err(|_| LigatureError("Error reading migration marker.".to_string()))?;
+        if done {
+            continue;
+        }
+        let tree = store
+            .open_tree(dataset_tree::tree_name(dataset_id))
+            .map_err(|_| LigatureError("Error opening dataset tree.".to_string()))?;
+        let mut removed = vec![];
+        let mut added = vec![];
+        for key in tree.scan_prefix(vec![EAVC_PREFIX]).keys() {
+            let key = key.map_err(|_| LigatureError("Error reading Statements.".to_string()))?;
+            let mut statement_id_set = decode_permutation(&key)?;
+            let mut body = [0u8; 8];
+            body.copy_from_slice(&statement_id_set.value_body);
+            let ordered = match statement_id_set.value_prefix {
+                INTEGER_VALUE_PREFIX => encode_integer(i64::from_be_bytes(body)),
+                FLOAT_VALUE_PREFIX => encode_float(f64::from_be_bytes(body)),
+                _ => continue,
+            };
+            removed.extend(encode_statement_permutations(&statement_id_set));
+            statement_id_set.value_body = ordered;
+            added.extend(encode_statement_permutations(&statement_id_set));
+        }
+        // every old key is removed before any new one is added since a new key can equal an old one
+        (&**store, &tree)
+            .transaction(|(root, tree)| {
+                for key in &removed {
+                    tree.remove(key.clone())?;
+                }
+                for key in &added {
+                    tree.insert(key.clone(), vec![])?;
+                }
+                root.insert(marker.clone(), vec![])?;
+                Ok(())
+            })
+            .map_err(|_: sled::transaction::TransactionError<()>| {
+                LigatureError("Error re-encoding numbers.".to_string())
+            })?;
+    }
+    Ok(())
+}
+
+/// Version 11 removes the markers left by version 10, which has been recorded as finished.
+fn remove_ordered_number_markers(store: &sled::Db) -> Result<(), LigatureError> {
+    for key in store.scan_prefix(vec![ORDERED_NUMBERS_PREFIX]).keys() {
+        let key = key.map_err(|_| LigatureError("Error reading migration marker.".to_string()))?;
+        store
+            .remove(key)
+            .map_err(|_| LigatureError("Error removing migration marker.".to_string()))?;
+    }
+    Ok(())
+}
+
+/// Version 12 stores the number of Statements, of Statements for each Attribute and of Entities
+/// instead of scanning for them on each stats call.
+/// Counting again when rerun gives the same counts.
+fn add_dataset_counts(store: &sled::Db) -> Result<(), LigatureError> {
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        let tree = store
+            .open_tree(dataset_tree::tree_name(decode_id(id.to_vec())?))
+            .map_err(|_| LigatureError("Error opening dataset tree.".to_string()))?;
+        stats::record_counts(&tree)?;
+    }
+    Ok(())
+}
+
+/// Version 13 keys large String Literals by hash alone and removes the ones no Statement uses,
+/// so the size of every Dataset tree is measured again afterwards.
+fn key_large_literals_by_hash(store: &sled::Db) -> Result<(), LigatureError> {
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        let tree = store
+            .open_tree(dataset_tree::tree_name(decode_id(id.to_vec())?))
+            .map_err(|_| LigatureError("Error opening dataset tree.".to_string()))?;
+        large_literal::key_by_hash(&tree)?;
+        stats::record_tree_size(&tree)?;
+    }
+    Ok(())
+}
+
+/// Starts a new id counter at 0 in every Dataset tree.
+fn add_dataset_counter(store: &sled::Db, counter_key: u8) -> Result<(), LigatureError> {
+    for entry in store.scan_prefix(vec![DATASET_PREFIX]).values() {
+        let id = entry.map_err(|_| LigatureError("Error iterating Datasets.".to_string()))?;
+        let tree = store
+            .open_tree(dataset_tree::tree_name(decode_id(id.to_vec())?))
+            .map_err(|_| LigatureError("Error opening dataset tree.".to_string()))?;
+        // existing counters are kept so rerunning this never resets one
+        let _ = tree
+            .compare_and_swap(
+                vec![counter_key],
+                None as Option<&[u8]>,
+                Some(encode_id(0)),
+            )
+            .map_err(|_| LigatureError(format!("Error adding id counter {}.", counter_key)))?;
+    }
+    Ok(())
+}
+
+fn write_format_version(store: &sled::Db, version: u64) -> Result<(), LigatureError> {
+    store
+        .insert(vec![FORMAT_VERSION_KEY], encode_id(version))
+        .map_err(|_| LigatureError("Error writing format version.".to_string()))?;
+    store
+        .flush()
+        .map_err(|_| LigatureError("Error writing format version.".to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,266 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Reading Statement queries a page at a time.
+//! A Cursor is the permutation key of the last Statement read, the next page is read from the key range after it,
+//! so Statements added or removed elsewhere in the Dataset don't shift pages the way an offset would.
+
+use crate::keys::{prefix_range, KeyRange};
+use crate::query_tx::LigatureSledQueryTx;
+use crate::value::{PersistedStatement, Range, Value};
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::EAVC_PREFIX;
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+/// The message of the error returned for a Cursor from a different query.
+pub(crate) const FOREIGN_CURSOR_ERROR: &str = "Cursor is not from this query.";
+
+/// An opaque position in the results of a query, written as a hex string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor(Vec<u8>);
+
+impl Cursor {
+    /// Reads a Cursor from the string written by its Display implementation.
+    pub fn parse(token: &str) -> Result<Cursor, LigatureError> {
+        let invalid = || LigatureError(format!("Invalid cursor {}.", token));
+        if token.is_empty() || token.len() % 2 != 0 || !token.is_ascii() {
+            return Err(invalid());
+        }
+        (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<Result<Vec<u8>, LigatureError>>()
+            .map(Cursor)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which part of a query's results to read, by default all of them in key order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PageRequest {
+    /// Continue after the last Statement of a previous page.
+    pub cursor: Option<Cursor>,
+    /// The number of Statements to skip, after the cursor if there is one.
+    pub offset: usize,
+    /// The most Statements to return, None for no limit.
+    pub limit: Option<usize>,
+    /// Read Statements in descending key order.
+    pub reverse: bool,
+}
+
+/// A page of Statements and the Cursor to read the next page with if there are more Statements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub statements: Vec<PersistedStatement>,
+    pub next: Option<Cursor>,
+}
+
+impl LigatureSledQueryTx {
+    /// Reads a page of all Statements in EAVC order.
+    pub fn all_statements_page(&self, request: &PageRequest) -> Result<Page, LigatureError> {
+        self.page_reader(vec![prefix_range(vec![EAVC_PREFIX])], request)?.collect_page()
+    }
+
+    /// Reads a page of the Statements matching a pattern, in the order of the permutation match_statements uses.
+    pub fn match_statements_page(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<Value>,
+        request: &PageRequest,
+    ) -> Result<Page, LigatureError> {
+        self.match_statements_page_reader(entity, attribute, value, request)?
+            .collect_page()
+    }
+
+    /// Reads a page of the Statements with a Value in a range, in the order match_statements_range returns them.
+    pub fn match_statements_range_page(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        range: Range,
+        request: &PageRequest,
+    ) -> Result<Page, LigatureError> {
+        let ranges = self.statement_ranges(entity, attribute, range)?;
+        self.page_reader(ranges, request)?.collect_page()
+    }
+
+    /// Like match_statements_page but reads the page lazily, so it can be written out as it is read.
+    pub(crate) fn match_statements_page_reader(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<Value>,
+        request: &PageRequest,
+    ) -> Result<PageReader, LigatureError> {
+        match self.statement_prefix(entity.as_ref(), attribute.as_ref(), value.as_ref())? {
+            Some(prefix) => self.page_reader(vec![prefix_range(prefix)], request),
+            None => self.page_reader(vec![], request),
+        }
+    }
+
+    /// Reads a page from key ranges that are scanned one after the other.
+    fn page_reader(
+        &self,
+        ranges: Vec<KeyRange>,
+        request: &PageRequest,
+    ) -> Result<PageReader, LigatureError> {
+        if request.limit == Some(0) {
+            return Err(LigatureError("Page limit must be at least 1.".to_string()));
+        }
+        let ranges = match &request.cursor {
+            // without ranges nothing can match, so there's nothing to resume and the page is empty
+            Some(_) if ranges.is_empty() => ranges,
+            Some(cursor) => resume(ranges, &cursor.0, request.reverse)?,
+            None if request.reverse => ranges.into_iter().rev().collect(),
+            None => ranges,
+        };
+        Ok(PageReader {
+            tx: self.clone(),
+            ranges: ranges.into_iter(),
+            scan: None,
+            reverse: request.reverse,
+            to_skip: request.offset,
+            remaining: request.limit,
+            last_key: None,
+            done: false,
+        })
+    }
+}
+
+/// An item of a page read by a PageReader.
+pub(crate) enum PageItem {
+    Statement(PersistedStatement),
+    /// Comes after the page's last Statement if there are more Statements.
+    Next(Cursor),
+}
+
+type Scan = Box<dyn Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>>;
+
+/// Reads a page one Statement at a time.
+pub(crate) struct PageReader {
+    tx: LigatureSledQueryTx,
+    ranges: std::vec::IntoIter<KeyRange>,
+    scan: Option<Scan>,
+    reverse: bool,
+    to_skip: usize,
+    /// The number of Statements left to read, None for no limit.
+    remaining: Option<usize>,
+    /// The key of the last Statement read, skipped ones included.
+    last_key: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl PageReader {
+    fn collect_page(self) -> Result<Page, LigatureError> {
+        let mut statements = vec![];
+        let mut next = None;
+        for item in self {
+            match item? {
+                PageItem::Statement(statement) => statements.push(statement),
+                PageItem::Next(cursor) => next = Some(cursor),
+            }
+        }
+        Ok(Page {
+            statements: statements,
+            next: next,
+        })
+    }
+
+    /// Returns the next key in the ranges, moving on to the next range when one runs out.
+    fn next_key(&mut self) -> Option<Result<Vec<u8>, LigatureError>> {
+        loop {
+            if let Some(scan) = self.scan.as_mut() {
+                if let Some(entry) = scan.next() {
+                    return Some(
+                        entry
+                            .map(|(key, _)| key.to_vec())
+                            .map_err(|_| LigatureError("Error reading Statements.".to_string())),
+                    );
+                }
+            }
+            let range = self.ranges.next()?;
+            let scan = self.tx.store.range(range);
+            self.scan = Some(if self.reverse {
+                Box::new(scan.rev())
+            } else {
+                Box::new(scan)
+            });
+        }
+    }
+}
+
+impl Iterator for PageReader {
+    type Item = Result<PageItem, LigatureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let key = match self.next_key() {
+                Some(Ok(key)) => key,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            };
+            let statement = match self.tx.load_statement(key.clone()) {
+                Ok(statement) => statement,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+            if self.remaining == Some(0) {
+                self.done = true;
+                return self
+                    .last_key
+                    .take()
+                    .map(|key| Ok(PageItem::Next(Cursor(key))));
+            }
+            self.last_key = Some(key);
+            if self.to_skip > 0 {
+                self.to_skip -= 1;
+                continue;
+            }
+            self.remaining = self.remaining.map(|remaining| remaining - 1);
+            return Some(Ok(PageItem::Statement(statement)));
+        }
+        None
+    }
+}
+
+/// Returns the part of the ranges left to scan after the given key, in the order to scan them.
+fn resume(
+    mut ranges: Vec<KeyRange>,
+    key: &[u8],
+    reverse: bool,
+) -> Result<Vec<KeyRange>, LigatureError> {
+    let key = key.to_vec();
+    let index = ranges
+        .iter()
+        .position(|range| range.contains(&key))
+        .ok_or_else(|| LigatureError(FOREIGN_CURSOR_ERROR.to_string()))?;
+    if reverse {
+        ranges.truncate(index + 1);
+        ranges[index].1 = Bound::Excluded(key);
+        ranges.reverse();
+        Ok(ranges)
+    } else {
+        let mut ranges = ranges.split_off(index);
+        ranges[0].0 = Bound::Excluded(key);
+        Ok(ranges)
+    }
+}
@@ -2,18 +2,25 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use ligature::{
-    Attribute, Dataset, Entity, Ligature, LigatureError, PersistedStatement, QueryTx, Range,
-    Statement, Value, WriteTx,
+use crate::encoding::{
+    encode_float, encode_integer, encode_timestamp, BYTES_LITERAL_VALUE_TO_ID_PREFIX,
+    BYTES_VALUE_PREFIX, TIMESTAMP_VALUE_PREFIX,
 };
+use crate::keys::{self, decode_permutation, match_prefix, prefix_range, range_prefix, KeyRange};
+use crate::value::{PersistedStatement, Range, Statement, Value};
+use ligature::{Attribute, Entity, LigatureError, QueryTx};
 use ligature_kv::{
-    decode_attribute, decode_dataset, decode_float, decode_id, decode_integer,
-    decode_statement_permutation, encode_dataset, encode_dataset_match, prepend,
-    ATTRIBUTE_ID_TO_NAME_PREFIX, EAVC_PREFIX,
+    chomp_assert, decode_attribute, decode_id, encode_attribute, encode_id, encode_string_literal,
+    prepend, ATTRIBUTE_ID_TO_NAME_PREFIX, ATTRIBUTE_NAME_TO_ID_PREFIX, CEAV_PREFIX, EAVC_PREFIX,
+    FLOAT_VALUE_PREFIX, INTEGER_VALUE_PREFIX, STRING_LITERAL_VALUE_TO_ID_PREFIX,
+    STRING_VALUE_PREFIX,
 };
+use std::convert::TryFrom;
+use std::ops::Bound;
 
+#[derive(Clone)]
 pub struct LigatureSledQueryTx {
-    store: sled::Tree,
+    pub(crate) store: sled::Tree,
 }
 
 impl LigatureSledQueryTx {
@@ -21,11 +28,40 @@ impl LigatureSledQueryTx {
         Self { store: store }
     }
 
-    fn load_statement(
+    /// Returns all Attributes in this Dataset whose name starts with the given prefix.
+    pub fn match_attributes_prefix(
+        &self,
+        prefix: &str,
+    ) -> Box<dyn Iterator<Item = Result<Attribute, LigatureError>>> {
+        let encoded_prefix = prepend(ATTRIBUTE_NAME_TO_ID_PREFIX, prefix.as_bytes().to_vec());
+        let iter = self.store.scan_prefix(encoded_prefix);
+        Box::new(iter.map(|attribute_res| match attribute_res {
+            Ok(attribute) => decode_attribute(chomp_assert(
+                ATTRIBUTE_NAME_TO_ID_PREFIX,
+                attribute.0.to_vec(),
+            )?),
+            Err(_) => Err(LigatureError("Error iterating Attributes.".to_string())),
+        }))
+    }
+
+    /// Returns all Statements in a context using the CEAV permutation.
+    pub fn statements_for_context(
+        &self,
+        context: &Entity,
+    ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
+        let tx = self.clone();
+        let iter = self.store.scan_prefix(prepend(CEAV_PREFIX, encode_id(context.0)));
+        Box::new(iter.map(move |entry| match entry {
+            Ok((key, _)) => tx.load_statement(key.to_vec()),
+            Err(_) => Err(LigatureError("Error reading Statements in context.".to_string())),
+        }))
+    }
+
+    pub(crate) fn load_statement(
         &self,
         encoded_statement: Vec<u8>,
     ) -> Result<PersistedStatement, LigatureError> {
-        let statement_id_set = decode_statement_permutation(encoded_statement)?;
+        let statement_id_set = decode_permutation(&encoded_statement)?;
         let entity = Entity(statement_id_set.entity_id);
         let attribute = self.load_attribute(statement_id_set.attribute_id)?;
         let value = self.load_value(statement_id_set.value_prefix, statement_id_set.value_body)?;
@@ -40,7 +76,10 @@ impl LigatureSledQueryTx {
         })
     }
 
-    fn load_attribute(&self, attribute_id: u64) -> Result<Attribute, LigatureError> {
+    pub(crate) fn load_attribute(
+        &self,
+        attribute_id: u64,
+    ) -> Result<Attribute, LigatureError> {
         let encoded_attribute_opt = self
             .store
             .get(prepend(
@@ -62,25 +101,216 @@ impl LigatureSledQueryTx {
         }
     }
 
-    fn load_value(&self, value_type: u8, value_body: Vec<u8>) -> Result<Value, LigatureError> {
-        match value_type {
-            ENTITY_VALUE_PREFIX => Ok(Value::Entity(Entity(decode_id(value_body)?))),
-            STRING_VALUE_PREFIX => Ok(Value::StringLiteral(
-                self.load_string_literal(decode_id(value_body)?)?,
+    pub(crate) fn load_value(
+        &self,
+        value_type: u8,
+        value_body: Vec<u8>,
+    ) -> Result<Value, LigatureError> {
+        keys::load_value(value_type, value_body, |key| {
+            self.store
+                .get(key)
+                .map(|body| body.map(|body| body.to_vec()))
+                .map_err(|_| LigatureError(format!("Error loading Value of type {}", value_type)))
+        })
+    }
+
+    /// Looks up the id of an Attribute without creating it.
+    pub(crate) fn lookup_attribute(
+        &self,
+        attribute: &Attribute,
+    ) -> Result<Option<u64>, LigatureError> {
+        let attribute_opt = self
+            .store
+            .get(prepend(ATTRIBUTE_NAME_TO_ID_PREFIX, encode_attribute(attribute)))
+            .map_err(|_| LigatureError(format!("Could not fetch Attribute {:?}", attribute)))?;
+        match attribute_opt {
+            Some(id) => Ok(Some(decode_id(id.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up the type prefix and body of a Value without creating it.
+    /// Returns None for String, Bytes and tagged Literals that have never been stored.
+    pub(crate) fn lookup_value(
+        &self,
+        value: &Value,
+    ) -> Result<Option<(u8, Vec<u8>)>, LigatureError> {
+        keys::lookup_value(value, |key| {
+            self.store
+                .get(key)
+                .map(|id| id.map(|id| id.to_vec()))
+                .map_err(|_| LigatureError(format!("Could not fetch Value {:?}", value)))
+        })
+    }
+
+    /// Returns the key prefix of the permutation best suited for the given pattern.
+    /// Returns None if the Attribute or Value has never been stored, so nothing can match.
+    pub(crate) fn statement_prefix(
+        &self,
+        entity: Option<&Entity>,
+        attribute: Option<&Attribute>,
+        value: Option<&Value>,
+    ) -> Result<Option<Vec<u8>>, LigatureError> {
+        let attribute_id = match attribute {
+            Some(attribute) => match self.lookup_attribute(attribute)? {
+                Some(id) => Some(id),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        let value = match value {
+            Some(value) => match self.lookup_value(value)? {
+                Some(encoded) => Some(encoded),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        Ok(Some(match_prefix(
+            entity.map(|e| e.0),
+            attribute_id,
+            value.as_ref(),
+        )))
+    }
+
+    /// Scans the permutation best suited for the given pattern and loads every matching Statement.
+    fn scan_statements(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<Value>,
+    ) -> Result<Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>>, LigatureError>
+    {
+        let prefix =
+            match self.statement_prefix(entity.as_ref(), attribute.as_ref(), value.as_ref())? {
+                Some(prefix) => prefix,
+                None => return Ok(Box::new(std::iter::empty())),
+            };
+        let tx = self.clone();
+        let iter = self.store.scan_prefix(prefix);
+        Ok(Box::new(iter.map(move |key_res| match key_res {
+            Ok(key) => tx.load_statement(key.0.to_vec()),
+            Err(_) => Err(LigatureError("Error matching Statements.".to_string())),
+        })))
+    }
+
+    /// Returns the ranges of the permutation that orders Statements by Value after the bound Entity and Attribute
+    /// that hold the given range of Values, in the order they are scanned.
+    /// Integers, Floats and Timestamps are encoded in order so they are scanned directly,
+    /// String and Bytes Literals are found in their dictionaries first.
+    pub(crate) fn statement_ranges(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        range: Range,
+    ) -> Result<Vec<KeyRange>, LigatureError> {
+        let attribute_id = match attribute {
+            Some(attribute) => match self.lookup_attribute(&attribute)? {
+                Some(id) => Some(id),
+                None => return Ok(vec![]),
+            },
+            None => None,
+        };
+        let prefix = range_prefix(entity.map(|e| e.0), attribute_id);
+        let value_key = |value_prefix: u8, body: Vec<u8>| {
+            let mut key = prefix.clone();
+            key.push(value_prefix);
+            key.extend(body);
+            key
+        };
+        let mut scans: Vec<KeyRange> = vec![];
+        let ordered_range = |value_prefix: u8, start: Vec<u8>, end: Vec<u8>| -> KeyRange {
+            (
+                Bound::Included(value_key(value_prefix, start)),
+                Bound::Excluded(value_key(value_prefix, end)),
+            )
+        };
+        match range {
+            Range::StringLiteralRange { start, end } => {
+                for id in self.dictionary_range(
+                    STRING_LITERAL_VALUE_TO_ID_PREFIX,
+                    encode_string_literal(&start),
+                    encode_string_literal(&end),
+                )? {
+                    scans.push(prefix_range(value_key(STRING_VALUE_PREFIX, id)));
+                }
+            }
+            Range::BytesLiteralRange { start, end } => {
+                for id in self.dictionary_range(BYTES_LITERAL_VALUE_TO_ID_PREFIX, start, end)? {
+                    scans.push(prefix_range(value_key(BYTES_VALUE_PREFIX, id)));
+                }
+            }
+            Range::IntegerLiteralRange { start, end } if start < end => scans.push(ordered_range(
+                INTEGER_VALUE_PREFIX,
+                encode_integer(start),
+                encode_integer(end),
+            )),
+            Range::IntegerLiteralRange { .. } => (),
+            // -0.0 sorts before 0.0 but compares equal to it, so zero bounds use -0.0
+            Range::FloatLiteralRange { start, end } if start < end => scans.push(ordered_range(
+                FLOAT_VALUE_PREFIX,
+                encode_float(if start == 0.0 { -0.0 } else { start }),
+                encode_float(if end == 0.0 { -0.0 } else { end }),
             )),
-            INTEGER_VALUE_PREFIX => Ok(Value::IntegerLiteral(decode_integer(value_body)?)),
-            FLOAT_VALUE_PREFIX => Ok(Value::FloatLiteral(decode_float(value_body)?)),
-            _ => Err(LigatureError(format!("Unknown value type {}", value_type))),
+            Range::FloatLiteralRange { .. } => (),
+            Range::TimestampLiteralRange { start, end } if start < end => {
+                scans.push(ordered_range(
+                    TIMESTAMP_VALUE_PREFIX,
+                    encode_timestamp(start),
+                    encode_timestamp(end),
+                ))
+            }
+            Range::TimestampLiteralRange { .. } => (),
         }
+        Ok(scans)
     }
 
-    fn load_string_literal(&self, string_literal_id: u64) -> Result<String, LigatureError> {
-        todo!()
+    /// Scans the permutation that orders Statements by Value after the bound Entity and Attribute.
+    fn scan_statements_range(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        range: Range,
+    ) -> Result<Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>>, LigatureError>
+    {
+        let scans: Vec<sled::Iter> = self
+            .statement_ranges(entity, attribute, range)?
+            .into_iter()
+            .map(|range| self.store.range(range))
+            .collect();
+        let tx = self.clone();
+        Ok(Box::new(scans.into_iter().flatten().map(
+            move |key_res| match key_res {
+                Ok(key) => tx.load_statement(key.0.to_vec()),
+                Err(_) => Err(LigatureError("Error matching Statements.".to_string())),
+            },
+        )))
+    }
+
+    /// Returns the ids of a dictionary's entries from start (inclusive) to end (exclusive) in value order.
+    /// Dictionary ids are given out in insertion order, so every id in the range is read before any Statement.
+    fn dictionary_range(
+        &self,
+        dictionary_prefix: u8,
+        start: Vec<u8>,
+        end: Vec<u8>,
+    ) -> Result<Vec<Vec<u8>>, LigatureError> {
+        if start >= end {
+            return Ok(vec![]);
+        }
+        self.store
+            .range(prepend(dictionary_prefix, start)..prepend(dictionary_prefix, end))
+            .values()
+            .map(|id| {
+                id.map(|id| id.to_vec())
+                    .map_err(|_| LigatureError("Error reading dictionary.".to_string()))
+            })
+            .collect()
     }
 }
 
-impl QueryTx for LigatureSledQueryTx {
-    fn all_statements(
+impl LigatureSledQueryTx {
+    /// Returns every Statement in this Dataset.
+    pub fn all_statements(
         &self,
     ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>> + '_> {
         let itr = self.store.scan_prefix(vec![EAVC_PREFIX]);
@@ -90,28 +320,99 @@ impl QueryTx for LigatureSledQueryTx {
         }))
     }
 
-    fn match_statements(
+    /// Returns the Statements matching an Entity, Attribute and Value pattern.
+    pub fn match_statements(
         &self,
         entity: Option<Entity>,
         attribute: Option<Attribute>,
         value: Option<Value>,
     ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
-        todo!()
+        match self.scan_statements(entity, attribute, value) {
+            Ok(iter) => iter,
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
     }
 
-    fn match_statements_range(
+    /// Returns the Statements with a Value in the given Range.
+    pub fn match_statements_range(
         &self,
         entity: Option<Entity>,
         attribute: Option<Attribute>,
         value: Range,
     ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
-        todo!()
+        match self.scan_statements_range(entity, attribute, value) {
+            Ok(iter) => iter,
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
     }
 
-    fn statement_for_context(
+    /// Returns the only Statement in a context, like every context made by add_statement has.
+    /// Fails if the context is shared and holds more than one Statement, use statements_for_context for those.
+    pub fn statement_for_context(
         &self,
         context: &Entity,
     ) -> Result<Option<PersistedStatement>, LigatureError> {
-        todo!()
+        let mut statements = self.statements_for_context(context);
+        let statement = statements.next().transpose()?;
+        if statements.next().is_some() {
+            return Err(LigatureError(format!(
+                "Context {} holds more than one Statement.",
+                context.0
+            )));
+        }
+        Ok(statement)
+    }
+}
+
+/// Converts Statements for the ligature traits,
+/// Statements with Values ligature doesn't have are returned as errors.
+fn to_ligature(
+    iter: Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>> + '_>,
+) -> Box<dyn Iterator<Item = Result<ligature::PersistedStatement, LigatureError>> + '_> {
+    Box::new(iter.map(|statement| ligature::PersistedStatement::try_from(statement?)))
+}
+
+impl QueryTx for LigatureSledQueryTx {
+    fn all_statements(
+        &self,
+    ) -> Box<dyn Iterator<Item = Result<ligature::PersistedStatement, LigatureError>> + '_> {
+        to_ligature(LigatureSledQueryTx::all_statements(self))
+    }
+
+    fn match_statements(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<ligature::Value>,
+    ) -> Box<dyn Iterator<Item = Result<ligature::PersistedStatement, LigatureError>>> {
+        to_ligature(LigatureSledQueryTx::match_statements(
+            self,
+            entity,
+            attribute,
+            value.map(Value::from),
+        ))
+    }
+
+    fn match_statements_range(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: ligature::Range,
+    ) -> Box<dyn Iterator<Item = Result<ligature::PersistedStatement, LigatureError>>> {
+        to_ligature(LigatureSledQueryTx::match_statements_range(
+            self,
+            entity,
+            attribute,
+            value.into(),
+        ))
+    }
+
+    fn statement_for_context(
+        &self,
+        context: &Entity,
+    ) -> Result<Option<ligature::PersistedStatement>, LigatureError> {
+        LigatureSledQueryTx::statement_for_context(self, context)?
+            .map(ligature::PersistedStatement::try_from)
+            .transpose()
     }
 }
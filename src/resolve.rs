@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Finding Entities by the Value of a unique Attribute, like an email address or a SKU.
+
+use crate::query_tx::LigatureSledQueryTx;
+use crate::write_tx::LigatureSledWriteTx;
+use crate::value::{Statement, Value};
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::{decode_id, encode_id, prepend, AVEC_PREFIX};
+use sled::IVec;
+
+/// The prefix of the AVEC keys of Statements with the given Attribute and Value.
+fn attribute_value_prefix(attribute_id: u64, value: (u8, Vec<u8>)) -> Vec<u8> {
+    let mut prefix = prepend(AVEC_PREFIX, encode_id(attribute_id));
+    prefix.push(value.0);
+    prefix.extend(value.1);
+    prefix
+}
+
+/// Reads the Entity of an AVEC key, it comes after the prefix, Attribute and Value.
+fn avec_entity(key: &IVec) -> Result<Entity, LigatureError> {
+    Ok(Entity(decode_id(key[18..26].to_vec())?))
+}
+
+impl LigatureSledQueryTx {
+    /// Returns an Entity that has the given Value for an Attribute.
+    /// For unique Attributes this is the only Entity with that Value.
+    pub fn resolve_entity(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Result<Option<Entity>, LigatureError> {
+        let attribute_id = match self.lookup_attribute(attribute)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let value = match self.lookup_value(value)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match self
+            .store
+            .scan_prefix(attribute_value_prefix(attribute_id, value))
+            .keys()
+            .next()
+        {
+            Some(key) => {
+                let key = key.map_err(|_| LigatureError("Error resolving Entity.".to_string()))?;
+                Ok(Some(avec_entity(&key)?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl LigatureSledWriteTx {
+    /// Returns an Entity that has the given Value for an Attribute, including Statements added in this transaction.
+    pub fn resolve_entity(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Result<Option<Entity>, LigatureError> {
+        let attribute_id = match self.lookup_attribute_id(attribute)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let value = match self.lookup_value(value)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match self
+            .store
+            .scan_prefix(attribute_value_prefix(attribute_id, value))
+            .next()
+        {
+            Some(entry) => Ok(Some(avec_entity(&entry?.0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the Entity with the given Value for a unique Attribute,
+    /// or creates a new Entity with a Statement giving it that Value if there isn't one.
+    /// Fails if the Attribute doesn't have a unique schema, since more than one Entity could match.
+    pub fn resolve_or_create_entity(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Result<Entity, LigatureError> {
+        match self.attribute_schema(attribute)? {
+            Some(schema) if schema.unique => (),
+            _ => {
+                return Err(LigatureError(format!(
+                    "Attribute {} has to be unique to resolve Entities by it.",
+                    attribute.name()
+                )))
+            }
+        }
+        if let Some(entity) = self.resolve_entity(attribute, value)? {
+            return Ok(entity);
+        }
+        let entity = self.new_entity()?;
+        self.add_statement(&Statement {
+            entity: entity.clone(),
+            attribute: attribute.clone(),
+            value: value.clone(),
+        })?;
+        Ok(entity)
+    }
+}
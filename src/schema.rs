@@ -0,0 +1,410 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Attributes can have a schema that constrains the Statements using them.
+//! Schemas are stored per Dataset by Attribute id and are checked by add_statement,
+//! setting a schema checks it against the Statements already in the Dataset.
+
+use crate::encoding::{
+    ATTRIBUTE_SCHEMA_PREFIX, BOOLEAN_VALUE_PREFIX, BYTES_VALUE_PREFIX, LANGUAGE_VALUE_PREFIX,
+    TIMESTAMP_VALUE_PREFIX, TYPED_VALUE_PREFIX,
+};
+use crate::keys::decode_permutation;
+use crate::query_tx::LigatureSledQueryTx;
+use crate::write_tx::LigatureSledWriteTx;
+use crate::value::{Statement, Value};
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::{
+    decode_id, encode_id, prepend, AEVC_PREFIX, AVEC_PREFIX, EAVC_PREFIX, ENTITY_VALUE_PREFIX,
+    FLOAT_VALUE_PREFIX, INTEGER_VALUE_PREFIX, STRING_VALUE_PREFIX,
+};
+
+/// The type of Value an Attribute's Statements have to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Entity,
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Bytes,
+    Timestamp,
+    Language,
+    Typed,
+}
+
+impl ValueType {
+    /// Returns the type of a Value.
+    pub fn of(value: &Value) -> ValueType {
+        match value {
+            Value::Entity(_) => ValueType::Entity,
+            Value::StringLiteral(_) => ValueType::String,
+            Value::IntegerLiteral(_) => ValueType::Integer,
+            Value::FloatLiteral(_) => ValueType::Float,
+            Value::BooleanLiteral(_) => ValueType::Boolean,
+            Value::BytesLiteral(_) => ValueType::Bytes,
+            Value::TimestampLiteral(_) => ValueType::Timestamp,
+            Value::LanguageLiteral(_, _) => ValueType::Language,
+            Value::TypedLiteral(_, _) => ValueType::Typed,
+        }
+    }
+
+    fn value_prefix(&self) -> u8 {
+        match self {
+            ValueType::Entity => ENTITY_VALUE_PREFIX,
+            ValueType::String => STRING_VALUE_PREFIX,
+            ValueType::Integer => INTEGER_VALUE_PREFIX,
+            ValueType::Float => FLOAT_VALUE_PREFIX,
+            ValueType::Boolean => BOOLEAN_VALUE_PREFIX,
+            ValueType::Bytes => BYTES_VALUE_PREFIX,
+            ValueType::Timestamp => TIMESTAMP_VALUE_PREFIX,
+            ValueType::Language => LANGUAGE_VALUE_PREFIX,
+            ValueType::Typed => TYPED_VALUE_PREFIX,
+        }
+    }
+
+    fn from_value_prefix(value_prefix: u8) -> Result<ValueType, LigatureError> {
+        match value_prefix {
+            ENTITY_VALUE_PREFIX => Ok(ValueType::Entity),
+            STRING_VALUE_PREFIX => Ok(ValueType::String),
+            INTEGER_VALUE_PREFIX => Ok(ValueType::Integer),
+            FLOAT_VALUE_PREFIX => Ok(ValueType::Float),
+            BOOLEAN_VALUE_PREFIX => Ok(ValueType::Boolean),
+            BYTES_VALUE_PREFIX => Ok(ValueType::Bytes),
+            TIMESTAMP_VALUE_PREFIX => Ok(ValueType::Timestamp),
+            LANGUAGE_VALUE_PREFIX => Ok(ValueType::Language),
+            TYPED_VALUE_PREFIX => Ok(ValueType::Typed),
+            _ => Err(LigatureError(format!(
+                "Unknown value type {}",
+                value_prefix
+            ))),
+        }
+    }
+}
+
+/// How many distinct Values an Entity can have for an Attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cardinality {
+    One,
+    Many,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttributeSchema {
+    pub value_type: ValueType,
+    pub cardinality: Cardinality,
+    /// When true no two Entities can have the same Value for the Attribute.
+    pub unique: bool,
+}
+
+impl AttributeSchema {
+    /// A schema that only constrains the type of Value, with many Values per Entity and no uniqueness.
+    pub fn new(value_type: ValueType) -> Self {
+        Self {
+            value_type: value_type,
+            cardinality: Cardinality::Many,
+            unique: false,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let cardinality = match self.cardinality {
+            Cardinality::One => 0,
+            Cardinality::Many => 1,
+        };
+        vec![
+            self.value_type.value_prefix(),
+            cardinality,
+            self.unique as u8,
+        ]
+    }
+
+    fn decode(encoded: &[u8]) -> Result<Self, LigatureError> {
+        if encoded.len() != 3 {
+            return Err(LigatureError("Invalid Attribute schema".to_string()));
+        }
+        let cardinality = match encoded[1] {
+            0 => Cardinality::One,
+            1 => Cardinality::Many,
+            _ => return Err(LigatureError("Invalid Attribute cardinality".to_string())),
+        };
+        Ok(Self {
+            value_type: ValueType::from_value_prefix(encoded[0])?,
+            cardinality: cardinality,
+            unique: encoded[2] == 1,
+        })
+    }
+}
+
+/// A way a Statement breaks its Attribute's schema.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaViolation {
+    /// The Statement's Value isn't of the Attribute's type.
+    WrongValueType {
+        attribute: Attribute,
+        expected: ValueType,
+        found: ValueType,
+    },
+    /// The Entity already has a different Value for an Attribute with a cardinality of one.
+    CardinalityOne {
+        attribute: Attribute,
+        entity: Entity,
+    },
+    /// Another Entity already has this Value for a unique Attribute.
+    NotUnique { attribute: Attribute, value: Value },
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaViolation::WrongValueType {
+                attribute,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Attribute {} requires {:?} Values but found {:?}.",
+                attribute.name(),
+                expected,
+                found
+            ),
+            SchemaViolation::CardinalityOne { attribute, entity } => write!(
+                f,
+                "Attribute {} allows one Value per Entity but Entity {} has more.",
+                attribute.name(),
+                entity.0
+            ),
+            SchemaViolation::NotUnique { attribute, value } => write!(
+                f,
+                "Attribute {} is unique but {:?} is used by more than one Entity.",
+                attribute.name(),
+                value
+            ),
+        }
+    }
+}
+
+impl From<SchemaViolation> for LigatureError {
+    fn from(violation: SchemaViolation) -> Self {
+        LigatureError(violation.to_string())
+    }
+}
+
+fn schema_key(attribute_id: u64) -> Vec<u8> {
+    prepend(ATTRIBUTE_SCHEMA_PREFIX, encode_id(attribute_id))
+}
+
+impl LigatureSledQueryTx {
+    /// Returns the schema of an Attribute, None if it doesn't have one.
+    pub fn attribute_schema(
+        &self,
+        attribute: &Attribute,
+    ) -> Result<Option<AttributeSchema>, LigatureError> {
+        let attribute_id = match self.lookup_attribute(attribute)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let schema = self
+            .store
+            .get(schema_key(attribute_id))
+            .map_err(|_| LigatureError(format!("Error reading schema of {:?}", attribute)))?;
+        schema
+            .map(|schema| AttributeSchema::decode(&schema))
+            .transpose()
+    }
+
+    /// Returns every Attribute with a schema, ordered by Attribute id.
+    pub fn attribute_schemas(
+        &self,
+    ) -> Box<dyn Iterator<Item = Result<(Attribute, AttributeSchema), LigatureError>>> {
+        let tx = self.clone();
+        let iter = self.store.scan_prefix(vec![ATTRIBUTE_SCHEMA_PREFIX]);
+        Box::new(iter.map(move |entry| match entry {
+            Ok((key, schema)) => Ok((
+                tx.load_attribute(decode_id(key[1..].to_vec())?)?,
+                AttributeSchema::decode(&schema)?,
+            )),
+            Err(_) => Err(LigatureError("Error iterating schemas.".to_string())),
+        }))
+    }
+}
+
+impl LigatureSledWriteTx {
+    /// Sets the schema of an Attribute, creating the Attribute if it doesn't exist.
+    /// Fails with the first SchemaViolation if Statements already in the Dataset don't follow the schema.
+    pub fn set_attribute_schema(
+        &self,
+        attribute: &Attribute,
+        schema: AttributeSchema,
+    ) -> Result<(), LigatureError> {
+        let attribute_id = self.check_or_create_attribute(attribute)?;
+        if let Some(violation) = self.existing_violations(attribute, attribute_id, &schema)? {
+            return Err(violation.into());
+        }
+        self.store
+            .insert(schema_key(attribute_id), schema.encode())
+            .map_err(|_| LigatureError(format!("Error saving schema of {:?}", attribute)))?;
+        Ok(())
+    }
+
+    /// Returns the schema of an Attribute, None if it doesn't have one.
+    pub fn attribute_schema(
+        &self,
+        attribute: &Attribute,
+    ) -> Result<Option<AttributeSchema>, LigatureError> {
+        let attribute_id = match self.lookup_attribute_id(attribute)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let schema = self
+            .store
+            .get(schema_key(attribute_id))
+            .map_err(|_| LigatureError(format!("Error reading schema of {:?}", attribute)))?;
+        schema
+            .map(|schema| AttributeSchema::decode(&schema))
+            .transpose()
+    }
+
+    /// Removes the schema of an Attribute so it is unconstrained again.
+    pub fn remove_attribute_schema(&self, attribute: &Attribute) -> Result<(), LigatureError> {
+        if let Some(attribute_id) = self.lookup_attribute_id(attribute)? {
+            self.store
+                .remove(schema_key(attribute_id))
+                .map_err(|_| LigatureError(format!("Error removing schema of {:?}", attribute)))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the ways adding a Statement would break its Attribute's schema, without adding it.
+    pub fn validate_statement(
+        &self,
+        statement: &Statement,
+    ) -> Result<Vec<SchemaViolation>, LigatureError> {
+        let attribute = &statement.attribute;
+        let attribute_id = match self.lookup_attribute_id(attribute)? {
+            Some(id) => id,
+            None => return Ok(vec![]),
+        };
+        let schema = self
+            .store
+            .get(schema_key(attribute_id))
+            .map_err(|_| LigatureError(format!("Error reading schema of {:?}", attribute)))?;
+        let schema = match schema {
+            Some(schema) => AttributeSchema::decode(&schema)?,
+            None => return Ok(vec![]),
+        };
+        let mut violations = vec![];
+        let found = ValueType::of(&statement.value);
+        if found != schema.value_type {
+            violations.push(SchemaViolation::WrongValueType {
+                attribute: attribute.clone(),
+                expected: schema.value_type,
+                found: found,
+            });
+        }
+        // None if the Value has never been stored, so no existing Statement can use it
+        let value = self.lookup_value(&statement.value)?.map(|(prefix, body)| {
+            let mut encoded = vec![prefix];
+            encoded.extend(body);
+            encoded
+        });
+        let entity_id = encode_id(statement.entity.0);
+        if schema.cardinality == Cardinality::One {
+            let mut prefix = prepend(EAVC_PREFIX, entity_id.clone());
+            prefix.extend(encode_id(attribute_id));
+            for entry in self.store.scan_prefix(prefix) {
+                let (key, _) = entry?;
+                // EAVC keys have the Value after the prefix, Entity and Attribute
+                if Some(&key[17..26]) != value.as_deref() {
+                    violations.push(SchemaViolation::CardinalityOne {
+                        attribute: attribute.clone(),
+                        entity: statement.entity.clone(),
+                    });
+                    break;
+                }
+            }
+        }
+        if let (true, Some(value)) = (schema.unique, &value) {
+            let mut prefix = prepend(AVEC_PREFIX, encode_id(attribute_id));
+            prefix.extend(value);
+            for entry in self.store.scan_prefix(prefix) {
+                let (key, _) = entry?;
+                // AVEC keys have the Entity after the prefix, Attribute and Value
+                if key[18..26] != entity_id[..] {
+                    violations.push(SchemaViolation::NotUnique {
+                        attribute: attribute.clone(),
+                        value: statement.value.clone(),
+                    });
+                    break;
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Finds the first Statement already using an Attribute that doesn't follow a schema.
+    fn existing_violations(
+        &self,
+        attribute: &Attribute,
+        attribute_id: u64,
+        schema: &AttributeSchema,
+    ) -> Result<Option<SchemaViolation>, LigatureError> {
+        // AEVC keys are sorted by Entity then Value so an Entity's Values are next to each other
+        let mut previous: Option<(u64, u8, Vec<u8>)> = None;
+        for entry in self
+            .store
+            .scan_prefix(prepend(AEVC_PREFIX, encode_id(attribute_id)))
+        {
+            let (key, _) = entry?;
+            let ids = decode_permutation(&key)?;
+            let found = ValueType::from_value_prefix(ids.value_prefix)?;
+            if found != schema.value_type {
+                return Ok(Some(SchemaViolation::WrongValueType {
+                    attribute: attribute.clone(),
+                    expected: schema.value_type,
+                    found: found,
+                }));
+            }
+            if schema.cardinality == Cardinality::One {
+                if let Some((entity_id, value_prefix, value_body)) = &previous {
+                    if *entity_id == ids.entity_id
+                        && (*value_prefix != ids.value_prefix || *value_body != ids.value_body)
+                    {
+                        return Ok(Some(SchemaViolation::CardinalityOne {
+                            attribute: attribute.clone(),
+                            entity: Entity(ids.entity_id),
+                        }));
+                    }
+                }
+            }
+            previous = Some((ids.entity_id, ids.value_prefix, ids.value_body));
+        }
+        if !schema.unique {
+            return Ok(None);
+        }
+        // AVEC keys are sorted by Value so Entities sharing a Value are next to each other
+        let mut previous: Option<(u64, u8, Vec<u8>)> = None;
+        for entry in self
+            .store
+            .scan_prefix(prepend(AVEC_PREFIX, encode_id(attribute_id)))
+        {
+            let (key, _) = entry?;
+            let ids = decode_permutation(&key)?;
+            if let Some((entity_id, value_prefix, value_body)) = &previous {
+                if *entity_id != ids.entity_id
+                    && *value_prefix == ids.value_prefix
+                    && *value_body == ids.value_body
+                {
+                    let value = self.load_value(ids.value_prefix, ids.value_body)?;
+                    return Ok(Some(SchemaViolation::NotUnique {
+                        attribute: attribute.clone(),
+                        value: value,
+                    }));
+                }
+            }
+            previous = Some((ids.entity_id, ids.value_prefix, ids.value_body));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,630 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! An HTTP/JSON server that exposes the Ligature API over a LigatureSled store.
+//!
+//! | Method | Path                         | Description                                         |
+//! | ------ | ---------------------------- | --------------------------------------------------- |
+//! | GET    | /datasets                    | all Datasets, or `?prefix=` or `?from=&to=` matches |
+//! | POST   | /datasets/{name}             | create a Dataset                                    |
+//! | DELETE | /datasets/{name}             | delete a Dataset                                    |
+//! | GET    | /datasets/{name}/statements  | match Statements with `?entity=&attribute=&value=`  |
+//! | POST   | /datasets/{name}/statements  | run a batch of write operations atomically          |
+//! | POST   | /datasets/{name}/sparql      | run the SPARQL SELECT query in the body             |
+//!
+//! Dataset names need to be percent encoded since they can contain `/`.
+//! Results are returned as newline delimited JSON, Datasets and Statements are written out as they are read
+//! so an error reading them ends the response with an `{"error": message}` line.
+//! Invalid requests get a 400, missing Datasets a 404, writes to a read only instance a 403
+//! and errors reading or writing the store a 500.
+//! Statements can be read a page at a time with `limit`, `offset`, `reverse=true` and `cursor`,
+//! pages that aren't the last end with a `{"next": cursor}` line.
+
+use crate::pagination::{PageItem, FOREIGN_CURSOR_ERROR};
+use crate::{Binding, LigatureSled, PageRequest, SparqlQuery, WriteError, READ_ONLY_ERROR};
+use crate::value::{PersistedStatement, Statement, Value};
+use ligature::{Attribute, Dataset, Entity, Ligature, LigatureError};
+use serde_json::{json, Value as Json};
+use std::io::{Cursor, Read};
+use std::net::SocketAddr;
+use tiny_http::{Header, Method, Request, Response, StatusCode};
+
+/// An error that is returned to the client with the given status code.
+struct HttpError(u16, String);
+
+/// Errors from the store are the server's fault unless it is read only,
+/// invalid requests are turned into a 400 where they are checked.
+impl From<LigatureError> for HttpError {
+    fn from(error: LigatureError) -> Self {
+        if error.0 == READ_ONLY_ERROR {
+            HttpError(403, error.0)
+        } else {
+            HttpError(500, error.0)
+        }
+    }
+}
+
+/// Statements that can't be added are the client's fault.
+impl From<WriteError> for HttpError {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::InvalidEntity(error) => HttpError(400, error.0),
+            WriteError::SchemaViolation(violation) => {
+                HttpError(400, LigatureError::from(violation).0)
+            }
+            WriteError::Store(error) => error.into(),
+        }
+    }
+}
+
+type HttpResult = Result<Response<Box<dyn Read>>, HttpError>;
+
+type Lines = Box<dyn Iterator<Item = Result<Json, LigatureError>>>;
+
+/// An Entity in a write operation, either an existing Entity or the nth Entity created by the batch.
+enum EntityRef {
+    Entity(Entity),
+    New(usize),
+}
+
+enum ValueRef {
+    Entity(EntityRef),
+    Value(Value),
+}
+
+/// A single operation in a batched write.
+enum Operation {
+    NewEntity,
+    AddStatement(EntityRef, Attribute, ValueRef),
+    RemoveStatement(EntityRef, Attribute, ValueRef, Entity),
+}
+
+pub struct LigatureServer {
+    instance: LigatureSled,
+    server: tiny_http::Server,
+}
+
+impl LigatureServer {
+    /// Creates a server for the given instance listening on the given address, ex. "127.0.0.1:4200".
+    /// Use port 0 to have one assigned.
+    pub fn new(instance: LigatureSled, address: &str) -> Result<Self, LigatureError> {
+        let server = tiny_http::Server::http(address)
+            .map_err(|e| LigatureError(format!("Could not start server - {:?}", e)))?;
+        Ok(Self { instance, server })
+    }
+
+    /// The address the server is listening on.
+    pub fn address(&self) -> SocketAddr {
+        self.server.server_addr()
+    }
+
+    /// Handles requests until the server is unblocked.
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            self.handle(request);
+        }
+    }
+
+    /// Stops a running server after it finishes the request it is handling.
+    pub fn unblock(&self) {
+        self.server.unblock();
+    }
+
+    fn handle(&self, mut request: Request) {
+        let response = match self.route(&mut request) {
+            Ok(response) => response,
+            Err(HttpError(status, message)) => ndjson(status, vec![json!({ "error": message })]),
+        };
+        // there isn't anyone left to report a failed response to
+        let _ = request.respond(response);
+    }
+
+    fn route(&self, request: &mut Request) -> HttpResult {
+        let (path, query) = split_url(request.url())?;
+        let segments: Vec<&str> = path.iter().map(|s| s.as_str()).collect();
+        let method = request.method().clone();
+        match (&method, segments.as_slice()) {
+            (Method::Get, ["datasets"]) => self.datasets(&query),
+            (Method::Post, ["datasets", name]) => {
+                self.instance.create_dataset(&parse_dataset(name)?)?;
+                Ok(empty(201))
+            }
+            (Method::Delete, ["datasets", name]) => {
+                let dataset = self.existing_dataset(name)?;
+                self.instance.delete_dataset(&dataset)?;
+                Ok(empty(204))
+            }
+            (Method::Get, ["datasets", name, "statements"]) => {
+                let dataset = self.existing_dataset(name)?;
+                self.match_statements(&dataset, &query)
+            }
+            (Method::Post, ["datasets", name, "statements"]) => {
+                let dataset = self.existing_dataset(name)?;
+                let mut body = String::new();
+                request
+                    .as_reader()
+                    .read_to_string(&mut body)
+                    .map_err(|_| HttpError(400, "Could not read request body.".to_string()))?;
+                self.write(&dataset, &body)
+            }
+            (Method::Post, ["datasets", name, "sparql"]) => {
+                let dataset = self.existing_dataset(name)?;
+                let mut body = String::new();
+                request
+                    .as_reader()
+                    .read_to_string(&mut body)
+                    .map_err(|_| HttpError(400, "Could not read request body.".to_string()))?;
+                self.sparql(&dataset, &body)
+            }
+            (_, ["datasets"])
+            | (_, ["datasets", _])
+            | (_, ["datasets", _, "statements"])
+            | (_, ["datasets", _, "sparql"]) => {
+                Err(HttpError(405, format!("Method {} not allowed.", method)))
+            }
+            _ => Err(HttpError(404, format!("Unknown path {}.", request.url()))),
+        }
+    }
+
+    fn existing_dataset(&self, name: &str) -> Result<Dataset, HttpError> {
+        let dataset = parse_dataset(name)?;
+        if self.instance.dataset_exists(&dataset)? {
+            Ok(dataset)
+        } else {
+            Err(HttpError(404, format!("Dataset {} not found.", name)))
+        }
+    }
+
+    fn datasets(&self, query: &[(String, String)]) -> HttpResult {
+        let datasets = match (param(query, "prefix"), param(query, "from"), param(query, "to")) {
+            (Some(prefix), None, None) => self.instance.match_datasets_prefix(prefix),
+            (None, Some(from), Some(to)) => self.instance.match_datasets_range(from, to),
+            (None, None, None) => self.instance.all_datasets(),
+            _ => {
+                return Err(HttpError(
+                    400,
+                    "Datasets can be matched by prefix or by from and to.".to_string(),
+                ))
+            }
+        };
+        let lines = datasets.map(|dataset| Ok(json!({ "dataset": dataset?.name() })));
+        Ok(ndjson_stream(Box::new(lines)))
+    }
+
+    fn match_statements(&self, dataset: &Dataset, query: &[(String, String)]) -> HttpResult {
+        let entity = match param(query, "entity") {
+            Some(entity) => Some(Entity(entity.parse().map_err(|_| {
+                HttpError(400, format!("Invalid Entity {}.", entity))
+            })?)),
+            None => None,
+        };
+        let attribute = match param(query, "attribute") {
+            Some(attribute) => Some(parse_attribute(attribute)?),
+            None => None,
+        };
+        let value = match param(query, "value") {
+            Some(value) => {
+                let json: Json = serde_json::from_str(value)
+                    .map_err(|_| HttpError(400, format!("Invalid Value {}.", value)))?;
+                match value_from_json(&json)? {
+                    ValueRef::Value(value) => Some(value),
+                    ValueRef::Entity(EntityRef::Entity(entity)) => Some(Value::Entity(entity)),
+                    ValueRef::Entity(EntityRef::New(_)) => {
+                        return Err(HttpError(
+                            400,
+                            "New Entities can't be used in queries.".to_string(),
+                        ))
+                    }
+                }
+            }
+            None => None,
+        };
+        let request = page_request(query)?;
+        let reader = self
+            .instance
+            .query_sled(dataset, |tx| {
+                tx.match_statements_page_reader(entity, attribute, value, &request)
+            })
+            .map_err(|e| {
+                if e.0 == FOREIGN_CURSOR_ERROR {
+                    HttpError(400, e.0)
+                } else {
+                    e.into()
+                }
+            })?;
+        let lines = reader.map(|item| {
+            Ok(match item? {
+                PageItem::Statement(statement) => statement_to_json(&statement),
+                PageItem::Next(next) => json!({ "next": next.to_string() }),
+            })
+        });
+        Ok(ndjson_stream(Box::new(lines)))
+    }
+
+    /// Returns each solution as an object from variable names to their bindings.
+    fn sparql(&self, dataset: &Dataset, body: &str) -> HttpResult {
+        let query = SparqlQuery::parse(body).map_err(|e| HttpError(400, e.0))?;
+        // a parsed query is valid, so evaluating it only fails if the store does
+        let results = self
+            .instance
+            .query_sled(dataset, |tx| tx.evaluate_sparql(&query))?;
+        let lines = results
+            .solutions
+            .iter()
+            .map(|solution| {
+                let object = solution
+                    .iter()
+                    .map(|(name, binding)| {
+                        let json = match binding {
+                            Binding::Attribute(attribute) => {
+                                json!({ "attribute": attribute.name() })
+                            }
+                            Binding::Value(value) => value_to_json(value),
+                        };
+                        (name.clone(), json)
+                    })
+                    .collect::<serde_json::Map<String, Json>>();
+                Json::Object(object)
+            })
+            .collect();
+        Ok(ndjson(200, lines))
+    }
+
+    /// Runs all operations in the body inside of a single write so they either all succeed or all fail.
+    fn write(&self, dataset: &Dataset, body: &str) -> HttpResult {
+        let json: Json = serde_json::from_str(body)
+            .map_err(|e| HttpError(400, format!("Invalid JSON - {}.", e)))?;
+        let operations = json
+            .as_array()
+            .ok_or_else(|| HttpError(400, "Expected an array of operations.".to_string()))?
+            .iter()
+            .map(operation_from_json)
+            .collect::<Result<Vec<Operation>, HttpError>>()?;
+        let results = self
+            .instance
+            .try_write_sled::<_, HttpError, _>(dataset, |tx| {
+                let mut new_entities: Vec<Entity> = vec![];
+                let mut results = vec![];
+                for operation in operations.iter() {
+                    let result = match operation {
+                        Operation::NewEntity => {
+                            let entity = tx.new_entity()?;
+                            new_entities.push(entity.clone());
+                            json!({ "entity": entity.0 })
+                        }
+                        Operation::AddStatement(entity, attribute, value) => {
+                            let statement = Statement {
+                                entity: resolve_entity(&new_entities, entity)?,
+                                attribute: attribute.clone(),
+                                value: resolve_value(&new_entities, value)?,
+                            };
+                            statement_to_json(&tx.try_add_statement(&statement)?)
+                        }
+                        Operation::RemoveStatement(entity, attribute, value, context) => {
+                            let persisted_statement = PersistedStatement {
+                                statement: Statement {
+                                    entity: resolve_entity(&new_entities, entity)?,
+                                    attribute: attribute.clone(),
+                                    value: resolve_value(&new_entities, value)?,
+                                },
+                                context: context.clone(),
+                            };
+                            json!({ "removed": tx.remove_statement(&persisted_statement)? })
+                        }
+                    };
+                    results.push(result);
+                }
+                Ok(results)
+            })?;
+        Ok(ndjson(200, results))
+    }
+}
+
+fn resolve_entity(new_entities: &[Entity], entity: &EntityRef) -> Result<Entity, HttpError> {
+    match entity {
+        EntityRef::Entity(entity) => Ok(entity.clone()),
+        EntityRef::New(i) => new_entities
+            .get(*i)
+            .cloned()
+            .ok_or_else(|| HttpError(400, format!("Unknown new Entity {}.", i))),
+    }
+}
+
+fn resolve_value(new_entities: &[Entity], value: &ValueRef) -> Result<Value, HttpError> {
+    match value {
+        ValueRef::Entity(entity) => Ok(Value::Entity(resolve_entity(new_entities, entity)?)),
+        ValueRef::Value(value) => Ok(value.clone()),
+    }
+}
+
+fn empty(status: u16) -> Response<Box<dyn Read>> {
+    Response::new(
+        StatusCode(status),
+        vec![],
+        Box::new(std::io::empty()),
+        Some(0),
+        None,
+    )
+}
+
+/// Creates a response with each value as a line of JSON.
+fn ndjson(status: u16, lines: Vec<Json>) -> Response<Box<dyn Read>> {
+    ndjson_response(status, Box::new(lines.into_iter().map(Ok)))
+}
+
+/// Creates a successful response that reads each line as it is written out.
+fn ndjson_stream(lines: Lines) -> Response<Box<dyn Read>> {
+    ndjson_response(200, lines)
+}
+
+fn ndjson_response(status: u16, lines: Lines) -> Response<Box<dyn Read>> {
+    let reader = NdjsonReader {
+        lines: lines,
+        current: Cursor::new(vec![]),
+        done: false,
+    };
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/x-ndjson"[..])
+        .expect("Static header is valid.");
+    Response::new(
+        StatusCode(status),
+        vec![content_type],
+        Box::new(reader),
+        None,
+        None,
+    )
+}
+
+/// Serializes one line at a time as the response is written out.
+/// The status has already been sent by the time a line fails, so the error is written as the last line.
+struct NdjsonReader {
+    lines: Lines,
+    current: Cursor<Vec<u8>>,
+    done: bool,
+}
+
+impl Read for NdjsonReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            if self.done {
+                return Ok(0);
+            }
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(error)) => {
+                    self.done = true;
+                    json!({ "error": error.0 })
+                }
+                None => return Ok(0),
+            };
+            self.current = Cursor::new(format!("{}\n", line).into_bytes());
+        }
+    }
+}
+
+fn statement_to_json(persisted_statement: &PersistedStatement) -> Json {
+    json!({
+        "entity": persisted_statement.statement.entity.0,
+        "attribute": persisted_statement.statement.attribute.name(),
+        "value": value_to_json(&persisted_statement.statement.value),
+        "context": persisted_statement.context.0,
+    })
+}
+
+fn value_to_json(value: &Value) -> Json {
+    match value {
+        Value::Entity(entity) => json!({ "entity": entity.0 }),
+        Value::StringLiteral(value) => json!({ "string": value }),
+        Value::IntegerLiteral(value) => json!({ "integer": value }),
+        Value::FloatLiteral(value) => json!({ "float": value }),
+        Value::BooleanLiteral(value) => json!({ "boolean": value }),
+        Value::BytesLiteral(value) => {
+            let hex: String = value.iter().map(|b| format!("{:02x}", b)).collect();
+            json!({ "bytes": hex })
+        }
+        Value::TimestampLiteral(value) => json!({ "timestamp": value }),
+        Value::LanguageLiteral(value, language) => {
+            json!({ "language": { "value": value, "tag": language } })
+        }
+        Value::TypedLiteral(value, datatype) => {
+            json!({ "typed": { "value": value, "datatype": datatype } })
+        }
+    }
+}
+
+fn operation_from_json(json: &Json) -> Result<Operation, HttpError> {
+    let field = |name: &str| {
+        json.get(name)
+            .ok_or_else(|| HttpError(400, format!("Operation is missing {}.", name)))
+    };
+    let attribute = || {
+        field("attribute")?
+            .as_str()
+            .ok_or_else(|| HttpError(400, "Attributes must be strings.".to_string()))
+            .and_then(parse_attribute)
+    };
+    match json.get("op").and_then(|op| op.as_str()) {
+        Some("new_entity") => Ok(Operation::NewEntity),
+        Some("add_statement") => Ok(Operation::AddStatement(
+            entity_from_json(field("entity")?)?,
+            attribute()?,
+            value_from_json(field("value")?)?,
+        )),
+        Some("remove_statement") => Ok(Operation::RemoveStatement(
+            entity_from_json(field("entity")?)?,
+            attribute()?,
+            value_from_json(field("value")?)?,
+            Entity(field("context")?.as_u64().ok_or_else(|| {
+                HttpError(400, "Contexts must be Entity ids.".to_string())
+            })?),
+        )),
+        _ => Err(HttpError(
+            400,
+            format!("Unknown operation {}.", json.get("op").unwrap_or(&Json::Null)),
+        )),
+    }
+}
+
+/// Entities are written as their id, or as {"new": n} for the nth Entity created by the batch.
+fn entity_from_json(json: &Json) -> Result<EntityRef, HttpError> {
+    if let Some(id) = json.as_u64() {
+        Ok(EntityRef::Entity(Entity(id)))
+    } else if let Some(index) = json.get("new").and_then(|i| i.as_u64()) {
+        Ok(EntityRef::New(index as usize))
+    } else {
+        Err(HttpError(400, format!("Invalid Entity {}.", json)))
+    }
+}
+
+/// Values are written as {"entity": id}, {"string": ".."}, {"integer": 1} or {"float": 1.0}.
+fn value_from_json(json: &Json) -> Result<ValueRef, HttpError> {
+    let invalid = || HttpError(400, format!("Invalid Value {}.", json));
+    let object = json.as_object().ok_or_else(invalid)?;
+    if object.len() != 1 {
+        return Err(invalid());
+    }
+    match object.iter().next() {
+        Some((key, value)) => match key.as_str() {
+            "entity" => Ok(ValueRef::Entity(entity_from_json(value)?)),
+            "string" => Ok(ValueRef::Value(Value::StringLiteral(
+                value.as_str().ok_or_else(invalid)?.to_string(),
+            ))),
+            "integer" => Ok(ValueRef::Value(Value::IntegerLiteral(
+                value.as_i64().ok_or_else(invalid)?,
+            ))),
+            "float" => Ok(ValueRef::Value(Value::FloatLiteral(
+                value.as_f64().ok_or_else(invalid)?,
+            ))),
+            "boolean" => Ok(ValueRef::Value(Value::BooleanLiteral(
+                value.as_bool().ok_or_else(invalid)?,
+            ))),
+            "bytes" => {
+                let hex = value.as_str().ok_or_else(invalid)?;
+                if hex.len() % 2 != 0 || !hex.is_ascii() {
+                    return Err(invalid());
+                }
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+                    .collect::<Result<Vec<u8>, HttpError>>()?;
+                Ok(ValueRef::Value(Value::BytesLiteral(bytes)))
+            }
+            "timestamp" => Ok(ValueRef::Value(Value::TimestampLiteral(
+                value.as_i64().ok_or_else(invalid)?,
+            ))),
+            "language" => {
+                let (value, language) = tagged_from_json(value, "tag").ok_or_else(invalid)?;
+                Ok(ValueRef::Value(Value::LanguageLiteral(value, language)))
+            }
+            "typed" => {
+                let (value, datatype) = tagged_from_json(value, "datatype").ok_or_else(invalid)?;
+                Ok(ValueRef::Value(Value::TypedLiteral(value, datatype)))
+            }
+            _ => Err(invalid()),
+        },
+        None => Err(invalid()),
+    }
+}
+
+/// Reads a tagged Literal written as `{"value": .., "<tag field>": ..}`.
+fn tagged_from_json(json: &Json, tag_field: &str) -> Option<(String, String)> {
+    let value = json.get("value")?.as_str()?;
+    let tag = json.get(tag_field)?.as_str()?;
+    Some((value.to_string(), tag.to_string()))
+}
+
+fn parse_dataset(name: &str) -> Result<Dataset, HttpError> {
+    Dataset::new(name).map_err(|e| HttpError(400, e.0))
+}
+
+fn parse_attribute(name: &str) -> Result<Attribute, HttpError> {
+    Attribute::new(name).map_err(|e| HttpError(400, e.0))
+}
+
+/// Reads the `cursor`, `offset`, `limit` and `reverse` query parameters.
+fn page_request(query: &[(String, String)]) -> Result<PageRequest, HttpError> {
+    let number = |name: &str| match param(query, name) {
+        Some(value) => value
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|_| HttpError(400, format!("Invalid {} {}.", name, value))),
+        None => Ok(None),
+    };
+    let cursor = match param(query, "cursor") {
+        Some(cursor) => Some(crate::Cursor::parse(cursor).map_err(|e| HttpError(400, e.0))?),
+        None => None,
+    };
+    let limit = number("limit")?;
+    if limit == Some(0) {
+        return Err(HttpError(400, "Page limit must be at least 1.".to_string()));
+    }
+    let reverse = match param(query, "reverse") {
+        Some("true") => true,
+        Some("false") | None => false,
+        Some(other) => return Err(HttpError(400, format!("Invalid reverse {}.", other))),
+    };
+    Ok(PageRequest {
+        cursor: cursor,
+        offset: number("offset")?.unwrap_or(0),
+        limit: limit,
+        reverse: reverse,
+    })
+}
+
+fn param<'a>(query: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    query
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Splits a request url into its decoded path segments and query parameters.
+fn split_url(url: &str) -> Result<(Vec<String>, Vec<(String, String)>), HttpError> {
+    let (path, query) = match url.find('?') {
+        Some(i) => (&url[..i], &url[i + 1..]),
+        None => (url, ""),
+    };
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect::<Result<Vec<String>, HttpError>>()?;
+    let mut params = vec![];
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = match pair.find('=') {
+            Some(i) => (&pair[..i], &pair[i + 1..]),
+            None => (pair, ""),
+        };
+        params.push((percent_decode(key)?, percent_decode(value)?));
+    }
+    Ok((segments, params))
+}
+
+fn percent_decode(value: &str) -> Result<String, HttpError> {
+    let invalid = || HttpError(400, format!("Invalid percent encoding in {}.", value));
+    let bytes = value.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value.get(i + 1..i + 3).ok_or_else(invalid)?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
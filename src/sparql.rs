@@ -0,0 +1,450 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A subset of SPARQL SELECT queries evaluated against a Dataset.
+//! IRIs in the predicate position are Attribute names and everywhere else they are the names of named Entities.
+//! Basic graph patterns are evaluated with match_patterns so every triple uses the best permutation.
+
+use crate::aggregate::compare_values;
+use crate::bgp::{Binding, Bindings, StatementPattern, Term};
+use crate::datalog::{push_bytes, tuple_key};
+use crate::query_tx::LigatureSledQueryTx;
+use crate::value::Value;
+use ligature::{Attribute, Entity, LigatureError};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SparqlTerm {
+    Variable(String),
+    Iri(String),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TriplePattern {
+    pub subject: SparqlTerm,
+    pub predicate: SparqlTerm,
+    pub object: SparqlTerm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expression {
+    Term(SparqlTerm),
+    Bound(String),
+    Compare(Comparison, Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+}
+
+/// Triples that all have to match, filters on them and optional groups that extend their matches.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct GroupPattern {
+    pub triples: Vec<TriplePattern>,
+    pub filters: Vec<Expression>,
+    pub optionals: Vec<GroupPattern>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Projection {
+    All,
+    Variables(Vec<String>),
+    /// Counts solutions, or the ones binding a variable, into a single solution.
+    Count {
+        variable: Option<String>,
+        distinct: bool,
+        alias: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OrderCondition {
+    pub variable: String,
+    pub descending: bool,
+}
+
+/// A parsed SELECT query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparqlQuery {
+    pub(crate) distinct: bool,
+    pub(crate) projection: Projection,
+    pub(crate) pattern: GroupPattern,
+    pub(crate) order_by: Vec<OrderCondition>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) offset: usize,
+}
+
+/// The solutions of a query, variables that an OPTIONAL didn't bind are left out of a solution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparqlResults {
+    pub variables: Vec<String>,
+    pub solutions: Vec<Bindings>,
+}
+
+/// A Value or IRI that a filter works with.
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Iri(String),
+    Value(Value),
+}
+
+impl GroupPattern {
+    /// Every variable in the group in the order they first appear.
+    fn variables(&self, variables: &mut Vec<String>) {
+        for triple in self.triples.iter() {
+            for term in [&triple.subject, &triple.predicate, &triple.object].iter() {
+                if let SparqlTerm::Variable(name) = term {
+                    if !variables.contains(name) {
+                        variables.push(name.clone());
+                    }
+                }
+            }
+        }
+        for optional in self.optionals.iter() {
+            optional.variables(variables);
+        }
+    }
+}
+
+impl LigatureSledQueryTx {
+    /// Evaluates a SELECT query, see SparqlQuery::parse for the supported syntax.
+    pub fn evaluate_sparql(&self, query: &SparqlQuery) -> Result<SparqlResults, LigatureError> {
+        let mut solutions = self.evaluate_group(&query.pattern, &Bindings::new())?;
+        if !query.order_by.is_empty() {
+            solutions.sort_by(|a, b| {
+                for condition in query.order_by.iter() {
+                    let order =
+                        compare_for_order(a.get(&condition.variable), b.get(&condition.variable));
+                    let order = if condition.descending {
+                        order.reverse()
+                    } else {
+                        order
+                    };
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                }
+                Ordering::Equal
+            });
+        }
+        let (variables, mut solutions) = match &query.projection {
+            Projection::All => {
+                let mut variables = vec![];
+                query.pattern.variables(&mut variables);
+                (variables, solutions)
+            }
+            Projection::Variables(variables) => {
+                let solutions = solutions
+                    .into_iter()
+                    .map(|solution| {
+                        solution
+                            .into_iter()
+                            .filter(|(name, _)| variables.contains(name))
+                            .collect()
+                    })
+                    .collect();
+                (variables.clone(), solutions)
+            }
+            Projection::Count {
+                variable,
+                distinct,
+                alias,
+            } => {
+                let counted = solutions.iter().filter_map(|solution| match variable {
+                    Some(variable) => solution.get(variable).map(binding_key),
+                    None => Some(bindings_key(solution)),
+                });
+                let count = if *distinct {
+                    counted.collect::<HashSet<Vec<u8>>>().len()
+                } else {
+                    counted.count()
+                };
+                let mut solution = Bindings::new();
+                solution.insert(
+                    alias.clone(),
+                    Binding::Value(Value::IntegerLiteral(count as i64)),
+                );
+                (vec![alias.clone()], vec![solution])
+            }
+        };
+        if query.distinct {
+            let mut seen = HashSet::new();
+            solutions.retain(|solution| seen.insert(bindings_key(solution)));
+        }
+        let solutions = solutions
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok(SparqlResults {
+            variables: variables,
+            solutions: solutions,
+        })
+    }
+
+    /// Matches a group's triples with the given bindings filled in, then extends each match with its optional groups
+    /// and keeps the ones that pass its filters.
+    fn evaluate_group(
+        &self,
+        group: &GroupPattern,
+        input: &Bindings,
+    ) -> Result<Vec<Bindings>, LigatureError> {
+        let mut solutions = match self.group_patterns(group, input)? {
+            Some(patterns) if patterns.is_empty() => vec![input.clone()],
+            Some(patterns) => {
+                let mut solutions = vec![];
+                for bindings in self.match_patterns(patterns) {
+                    let mut solution = input.clone();
+                    solution.extend(bindings?);
+                    solutions.push(solution);
+                }
+                solutions
+            }
+            None => vec![],
+        };
+        for optional in group.optionals.iter() {
+            let mut extended = vec![];
+            for solution in solutions {
+                let matches = self.evaluate_group(optional, &solution)?;
+                if matches.is_empty() {
+                    extended.push(solution);
+                } else {
+                    extended.extend(matches);
+                }
+            }
+            solutions = extended;
+        }
+        let mut filtered = vec![];
+        for solution in solutions {
+            let mut passes = true;
+            for filter in group.filters.iter() {
+                if self.evaluate_expression(filter, &solution)?
+                    != Some(Operand::Value(Value::BooleanLiteral(true)))
+                {
+                    passes = false;
+                    break;
+                }
+            }
+            if passes {
+                filtered.push(solution);
+            }
+        }
+        Ok(filtered)
+    }
+
+    /// Turns a group's triples into Statement patterns with bound variables filled in.
+    /// Returns None if an IRI doesn't name an Entity or a variable is bound to something that can't match,
+    /// since then the group has no solutions.
+    fn group_patterns(
+        &self,
+        group: &GroupPattern,
+        bindings: &Bindings,
+    ) -> Result<Option<Vec<StatementPattern>>, LigatureError> {
+        let mut patterns = vec![];
+        for triple in group.triples.iter() {
+            let entity = match &triple.subject {
+                SparqlTerm::Variable(name) => match bindings.get(name) {
+                    Some(Binding::Value(Value::Entity(entity))) => Term::Bound(entity.clone()),
+                    Some(_) => return Ok(None),
+                    None => Term::Variable(name.clone()),
+                },
+                SparqlTerm::Iri(iri) => match self.lookup_named_entity(iri)? {
+                    Some(entity) => Term::Bound(entity),
+                    None => return Ok(None),
+                },
+                SparqlTerm::Literal(_) => return Ok(None),
+            };
+            let attribute = match &triple.predicate {
+                SparqlTerm::Variable(name) => match bindings.get(name) {
+                    Some(Binding::Attribute(attribute)) => Term::Bound(attribute.clone()),
+                    Some(_) => return Ok(None),
+                    None => Term::Variable(name.clone()),
+                },
+                SparqlTerm::Iri(iri) => Term::Bound(Attribute::new(iri)?),
+                SparqlTerm::Literal(_) => return Ok(None),
+            };
+            let value = match &triple.object {
+                SparqlTerm::Variable(name) => match bindings.get(name) {
+                    Some(Binding::Value(value)) => Term::Bound(value.clone()),
+                    Some(_) => return Ok(None),
+                    None => Term::Variable(name.clone()),
+                },
+                SparqlTerm::Iri(iri) => match self.lookup_named_entity(iri)? {
+                    Some(entity) => Term::Bound(Value::Entity(entity)),
+                    None => return Ok(None),
+                },
+                SparqlTerm::Literal(value) => Term::Bound(value.clone()),
+            };
+            patterns.push(StatementPattern {
+                entity: entity,
+                attribute: attribute,
+                value: value,
+            });
+        }
+        Ok(Some(patterns))
+    }
+
+    /// Evaluates a filter expression, returning None for unbound variables and type errors.
+    fn evaluate_expression(
+        &self,
+        expression: &Expression,
+        bindings: &Bindings,
+    ) -> Result<Option<Operand>, LigatureError> {
+        let boolean = |value: bool| Some(Operand::Value(Value::BooleanLiteral(value)));
+        match expression {
+            Expression::Term(SparqlTerm::Variable(name)) => match bindings.get(name) {
+                Some(Binding::Attribute(attribute)) => {
+                    Ok(Some(Operand::Iri(attribute.name().to_string())))
+                }
+                Some(Binding::Value(Value::Entity(entity))) => self.entity_operand(entity),
+                Some(Binding::Value(value)) => Ok(Some(Operand::Value(value.clone()))),
+                None => Ok(None),
+            },
+            Expression::Term(SparqlTerm::Iri(iri)) => Ok(Some(Operand::Iri(iri.clone()))),
+            Expression::Term(SparqlTerm::Literal(value)) => Ok(Some(Operand::Value(value.clone()))),
+            Expression::Bound(name) => Ok(boolean(bindings.contains_key(name))),
+            Expression::Compare(comparison, left, right) => {
+                let left = self.evaluate_expression(left, bindings)?;
+                let right = self.evaluate_expression(right, bindings)?;
+                let (left, right) = match (left, right) {
+                    (Some(left), Some(right)) => (left, right),
+                    _ => return Ok(None),
+                };
+                let order = compare_operands(&left, &right);
+                let result = match comparison {
+                    Comparison::Equal => order == Some(Ordering::Equal),
+                    Comparison::NotEqual => order != Some(Ordering::Equal),
+                    Comparison::Less => order == Some(Ordering::Less),
+                    Comparison::LessOrEqual => {
+                        order == Some(Ordering::Less) || order == Some(Ordering::Equal)
+                    }
+                    Comparison::Greater => order == Some(Ordering::Greater),
+                    Comparison::GreaterOrEqual => {
+                        order == Some(Ordering::Greater) || order == Some(Ordering::Equal)
+                    }
+                };
+                Ok(boolean(result))
+            }
+            Expression::And(left, right) => {
+                let left = effective_boolean(self.evaluate_expression(left, bindings)?);
+                let right = effective_boolean(self.evaluate_expression(right, bindings)?);
+                Ok(match (left, right) {
+                    (Some(false), _) | (_, Some(false)) => boolean(false),
+                    (Some(true), Some(true)) => boolean(true),
+                    _ => None,
+                })
+            }
+            Expression::Or(left, right) => {
+                let left = effective_boolean(self.evaluate_expression(left, bindings)?);
+                let right = effective_boolean(self.evaluate_expression(right, bindings)?);
+                Ok(match (left, right) {
+                    (Some(true), _) | (_, Some(true)) => boolean(true),
+                    (Some(false), Some(false)) => boolean(false),
+                    _ => None,
+                })
+            }
+            Expression::Not(inner) => Ok(effective_boolean(
+                self.evaluate_expression(inner, bindings)?,
+            )
+            .and_then(|b| boolean(!b))),
+        }
+    }
+
+    /// Named Entities are compared by their IRI, other Entities by their id.
+    fn entity_operand(&self, entity: &Entity) -> Result<Option<Operand>, LigatureError> {
+        match self.entity_name(entity)? {
+            Some(name) => Ok(Some(Operand::Iri(name))),
+            None => Ok(Some(Operand::Value(Value::Entity(entity.clone())))),
+        }
+    }
+}
+
+fn effective_boolean(operand: Option<Operand>) -> Option<bool> {
+    match operand {
+        Some(Operand::Value(Value::BooleanLiteral(value))) => Some(value),
+        _ => None,
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::IntegerLiteral(value) => Some(*value as f64),
+        Value::FloatLiteral(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Orders two operands if they can be compared, numbers are compared with each other whatever their type.
+fn compare_operands(left: &Operand, right: &Operand) -> Option<Ordering> {
+    match (left, right) {
+        (Operand::Iri(left), Operand::Iri(right)) => Some(left.cmp(right)),
+        (Operand::Value(left), Operand::Value(right)) => match (left, right) {
+            (Value::IntegerLiteral(left), Value::IntegerLiteral(right)) => Some(left.cmp(right)),
+            (Value::StringLiteral(left), Value::StringLiteral(right)) => Some(left.cmp(right)),
+            (Value::BooleanLiteral(left), Value::BooleanLiteral(right)) => Some(left.cmp(right)),
+            (Value::TimestampLiteral(left), Value::TimestampLiteral(right)) => {
+                Some(left.cmp(right))
+            }
+            (Value::Entity(left), Value::Entity(right)) if left == right => Some(Ordering::Equal),
+            _ => match (number(left), number(right)) {
+                (Some(left), Some(right)) => left.partial_cmp(&right),
+                _ if left == right => Some(Ordering::Equal),
+                _ => None,
+            },
+        },
+        _ => None,
+    }
+}
+
+/// Orders bindings for ORDER BY, unbound first, then Values in the order aggregate uses and then Attributes by name.
+fn compare_for_order(left: Option<&Binding>, right: Option<&Binding>) -> Ordering {
+    match (left, right) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(Binding::Value(left)), Some(Binding::Value(right))) => compare_values(left, right),
+        (Some(Binding::Value(_)), Some(Binding::Attribute(_))) => Ordering::Less,
+        (Some(Binding::Attribute(_)), Some(Binding::Value(_))) => Ordering::Greater,
+        (Some(Binding::Attribute(left)), Some(Binding::Attribute(right))) => {
+            left.name().cmp(right.name())
+        }
+    }
+}
+
+/// Encodes a binding so equal bindings, and only equal bindings, have the same key.
+fn binding_key(binding: &Binding) -> Vec<u8> {
+    match binding {
+        Binding::Value(value) => {
+            let mut key = vec![0];
+            key.extend(tuple_key(std::slice::from_ref(value)));
+            key
+        }
+        Binding::Attribute(attribute) => {
+            let mut key = vec![1];
+            push_bytes(&mut key, attribute.name().as_bytes());
+            key
+        }
+    }
+}
+
+/// Encodes a solution so equal solutions, and only equal solutions, have the same key.
+fn bindings_key(bindings: &Bindings) -> Vec<u8> {
+    let mut key = vec![];
+    for (variable, binding) in bindings.iter() {
+        push_bytes(&mut key, variable.as_bytes());
+        push_bytes(&mut key, &binding_key(binding));
+    }
+    key
+}
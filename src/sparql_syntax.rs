@@ -0,0 +1,516 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Reads SPARQL SELECT queries from text.
+//!
+//! ```text
+//! PREFIX ex: <https://example.com/>
+//! SELECT DISTINCT ?name ?age WHERE {
+//!   ?person a ex:Person ; ex:name ?name .
+//!   OPTIONAL { ?person ex:age ?age FILTER(?age >= 18) }
+//!   FILTER(?name != "Bob" && !BOUND(?age) || ?age < 65)
+//! } ORDER BY DESC(?age) ?name LIMIT 10 OFFSET 20
+//! ```
+//!
+//! Supported are PREFIX, SELECT with variables, `*` or `(COUNT([DISTINCT] *|?var) AS ?var)`, triples using `a`, `;` and `,`,
+//! FILTER with comparisons, `&&`, `||`, `!` and BOUND, nested OPTIONAL groups, ORDER BY, LIMIT and OFFSET.
+//! Literals are strings with an optional language tag or datatype, integers, decimals, true and false.
+//! A query either projects variables or a single COUNT, there is no GROUP BY.
+
+use crate::sparql::{
+    Comparison, Expression, GroupPattern, OrderCondition, Projection, SparqlQuery, SparqlTerm,
+    TriplePattern,
+};
+use crate::value::Value;
+use ligature::{Attribute, LigatureError};
+use std::collections::HashMap;
+
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+impl SparqlQuery {
+    /// Parses a SELECT query.
+    pub fn parse(text: &str) -> Result<SparqlQuery, LigatureError> {
+        let mut parser = Parser::new(text);
+        let query = parser.query()?;
+        parser.skip_whitespace();
+        if !parser.at_end() {
+            return Err(parser.error("end of query"));
+        }
+        Ok(query)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    prefixes: HashMap<String, String>,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            prefixes: HashMap::new(),
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn error(&self, expected: &str) -> LigatureError {
+        LigatureError(format!(
+            "Expected {} at character {} of SPARQL.",
+            expected, self.position
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.position += 1;
+                }
+            } else if c.is_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Skips whitespace and consumes the given text if it is next.
+    fn consume(&mut self, text: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + text.chars().count();
+        if end <= self.chars.len()
+            && self.chars[self.position..end]
+                .iter()
+                .copied()
+                .eq(text.chars())
+        {
+            self.position = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), LigatureError> {
+        if self.consume(text) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", text)))
+        }
+    }
+
+    /// Consumes a keyword in any case if it is next and isn't the start of a longer name.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + keyword.len();
+        if end <= self.chars.len()
+            && self.chars[self.position..end]
+                .iter()
+                .zip(keyword.chars())
+                .all(|(a, b)| a.eq_ignore_ascii_case(&b))
+            && !matches!(self.chars.get(end), Some(c) if is_name_char(*c) || *c == ':')
+        {
+            self.position = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if f(c)) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn query(&mut self) -> Result<SparqlQuery, LigatureError> {
+        while self.keyword("PREFIX") {
+            self.skip_whitespace();
+            let prefix = self.take_while(is_name_char);
+            self.expect(":")?;
+            let iri = self.iri_ref()?;
+            self.prefixes.insert(prefix, iri);
+        }
+        if !self.keyword("SELECT") {
+            return Err(self.error("SELECT"));
+        }
+        let distinct = self.keyword("DISTINCT");
+        let projection = self.projection()?;
+        self.keyword("WHERE");
+        let pattern = self.group()?;
+        let mut order_by = vec![];
+        if self.keyword("ORDER") {
+            if !self.keyword("BY") {
+                return Err(self.error("BY"));
+            }
+            loop {
+                let descending = if self.keyword("ASC") {
+                    false
+                } else if self.keyword("DESC") {
+                    true
+                } else if self.consume_variable_start() {
+                    order_by.push(OrderCondition {
+                        variable: self.variable_name()?,
+                        descending: false,
+                    });
+                    continue;
+                } else {
+                    break;
+                };
+                self.expect("(")?;
+                let variable = self.variable()?;
+                self.expect(")")?;
+                order_by.push(OrderCondition {
+                    variable: variable,
+                    descending: descending,
+                });
+            }
+            if order_by.is_empty() {
+                return Err(self.error("an order condition"));
+            }
+        }
+        let mut limit = None;
+        let mut offset = 0;
+        loop {
+            if self.keyword("LIMIT") {
+                limit = Some(self.count()?);
+            } else if self.keyword("OFFSET") {
+                offset = self.count()?;
+            } else {
+                break;
+            }
+        }
+        Ok(SparqlQuery {
+            distinct: distinct,
+            projection: projection,
+            pattern: pattern,
+            order_by: order_by,
+            limit: limit,
+            offset: offset,
+        })
+    }
+
+    fn count(&mut self) -> Result<usize, LigatureError> {
+        self.skip_whitespace();
+        self.take_while(|c| c.is_ascii_digit())
+            .parse::<usize>()
+            .map_err(|_| self.error("a number"))
+    }
+
+    fn projection(&mut self) -> Result<Projection, LigatureError> {
+        if self.consume("*") {
+            return Ok(Projection::All);
+        }
+        if self.consume("(") {
+            if !self.keyword("COUNT") {
+                return Err(self.error("COUNT"));
+            }
+            self.expect("(")?;
+            let distinct = self.keyword("DISTINCT");
+            let variable = if self.consume("*") {
+                None
+            } else {
+                Some(self.variable()?)
+            };
+            self.expect(")")?;
+            if !self.keyword("AS") {
+                return Err(self.error("AS"));
+            }
+            let alias = self.variable()?;
+            self.expect(")")?;
+            return Ok(Projection::Count {
+                variable: variable,
+                distinct: distinct,
+                alias: alias,
+            });
+        }
+        let mut variables = vec![];
+        while self.consume_variable_start() {
+            variables.push(self.variable_name()?);
+        }
+        if variables.is_empty() {
+            Err(self.error("'*', a COUNT or variables"))
+        } else {
+            Ok(Projection::Variables(variables))
+        }
+    }
+
+    fn group(&mut self) -> Result<GroupPattern, LigatureError> {
+        self.expect("{")?;
+        let mut group = GroupPattern::default();
+        loop {
+            if self.consume("}") {
+                return Ok(group);
+            } else if self.consume(".") {
+                continue;
+            } else if self.keyword("FILTER") {
+                group.filters.push(self.constraint()?);
+            } else if self.keyword("OPTIONAL") {
+                group.optionals.push(self.group()?);
+            } else {
+                self.triples(&mut group.triples)?;
+            }
+        }
+    }
+
+    /// Reads a subject followed by predicates and objects separated by `;` and `,`.
+    fn triples(&mut self, triples: &mut Vec<TriplePattern>) -> Result<(), LigatureError> {
+        let subject = self.term()?;
+        loop {
+            let predicate = if self.keyword("a") {
+                SparqlTerm::Iri(RDF_TYPE.to_string())
+            } else {
+                self.term()?
+            };
+            // predicates are Attribute names so invalid ones are caught before evaluation
+            if let SparqlTerm::Iri(iri) = &predicate {
+                Attribute::new(iri)?;
+            }
+            loop {
+                triples.push(TriplePattern {
+                    subject: subject.clone(),
+                    predicate: predicate.clone(),
+                    object: self.term()?,
+                });
+                if !self.consume(",") {
+                    break;
+                }
+            }
+            if !self.consume(";") {
+                return Ok(());
+            }
+            self.skip_whitespace();
+            if matches!(self.peek(), Some('.') | Some('}')) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn consume_variable_start(&mut self) -> bool {
+        self.consume("?") || self.consume("$")
+    }
+
+    fn variable_name(&mut self) -> Result<String, LigatureError> {
+        let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+        if name.is_empty() {
+            Err(self.error("a variable name"))
+        } else {
+            Ok(name)
+        }
+    }
+
+    fn variable(&mut self) -> Result<String, LigatureError> {
+        if self.consume_variable_start() {
+            self.variable_name()
+        } else {
+            Err(self.error("a variable"))
+        }
+    }
+
+    fn iri_ref(&mut self) -> Result<String, LigatureError> {
+        self.expect("<")?;
+        let iri = self.take_while(|c| c != '>' && !c.is_whitespace());
+        self.expect(">")?;
+        Ok(iri)
+    }
+
+    /// Reads an IRI written in angle brackets or as a prefixed name.
+    fn iri(&mut self) -> Result<String, LigatureError> {
+        self.skip_whitespace();
+        if self.peek() == Some('<') {
+            return self.iri_ref();
+        }
+        let prefix = self.take_while(is_name_char);
+        self.expect(":")?;
+        let mut local = self.take_while(|c| is_name_char(c) || c == '.');
+        while local.ends_with('.') {
+            local.pop();
+            self.position -= 1;
+        }
+        match self.prefixes.get(&prefix) {
+            Some(namespace) => Ok(format!("{}{}", namespace, local)),
+            None => Err(LigatureError(format!("Unknown SPARQL prefix {}:.", prefix))),
+        }
+    }
+
+    fn term(&mut self) -> Result<SparqlTerm, LigatureError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('?') | Some('$') => Ok(SparqlTerm::Variable(self.variable()?)),
+            Some('"') | Some('\'') => Ok(SparqlTerm::Literal(self.string_literal()?)),
+            Some(c)
+                if c.is_ascii_digit()
+                    || ((c == '-' || c == '+')
+                        && matches!(self.peek_at(1), Some(c) if c.is_ascii_digit())) =>
+            {
+                Ok(SparqlTerm::Literal(self.number()?))
+            }
+            Some(_) if self.keyword("true") => Ok(SparqlTerm::Literal(Value::BooleanLiteral(true))),
+            Some(_) if self.keyword("false") => {
+                Ok(SparqlTerm::Literal(Value::BooleanLiteral(false)))
+            }
+            Some(c) if c == '<' || c == ':' || is_name_char(c) => Ok(SparqlTerm::Iri(self.iri()?)),
+            _ => Err(self.error("a term")),
+        }
+    }
+
+    /// Reads an integer, or a decimal if it has a fractional part.
+    fn number(&mut self) -> Result<Value, LigatureError> {
+        let start = self.position;
+        if matches!(self.peek(), Some('-') | Some('+')) {
+            self.position += 1;
+        }
+        self.take_while(|c| c.is_ascii_digit());
+        let decimal =
+            self.peek() == Some('.') && matches!(self.peek_at(1), Some(c) if c.is_ascii_digit());
+        if decimal {
+            self.position += 1;
+            self.take_while(|c| c.is_ascii_digit());
+        }
+        let number: String = self.chars[start..self.position].iter().collect();
+        if decimal {
+            let float = number.parse::<f64>().map_err(|_| self.error("a number"))?;
+            Ok(Value::FloatLiteral(float))
+        } else {
+            let integer = number.parse::<i64>().map_err(|_| self.error("a number"))?;
+            Ok(Value::IntegerLiteral(integer))
+        }
+    }
+
+    /// Reads a quoted string with an optional language tag or datatype, backslash escapes the next character.
+    fn string_literal(&mut self) -> Result<Value, LigatureError> {
+        let quote = self.peek().unwrap_or('"');
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error(&format!("'{}'", quote))),
+                Some(c) if c == quote => {
+                    self.position += 1;
+                    break;
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some(c) => string.push(c),
+                        None => return Err(self.error("an escaped character")),
+                    }
+                    self.position += 1;
+                }
+                Some(c) => {
+                    string.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+        if self.peek() == Some('@') {
+            self.position += 1;
+            let language = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+            Ok(Value::LanguageLiteral(string, language))
+        } else if self.peek() == Some('^') && self.peek_at(1) == Some('^') {
+            self.position += 2;
+            let datatype = self.iri()?;
+            Ok(Value::TypedLiteral(string, datatype))
+        } else {
+            Ok(Value::StringLiteral(string))
+        }
+    }
+
+    /// Reads a parenthesized expression or a BOUND call after FILTER.
+    fn constraint(&mut self) -> Result<Expression, LigatureError> {
+        if self.keyword("BOUND") {
+            self.expect("(")?;
+            let variable = self.variable()?;
+            self.expect(")")?;
+            return Ok(Expression::Bound(variable));
+        }
+        self.expect("(")?;
+        let expression = self.or_expression()?;
+        self.expect(")")?;
+        Ok(expression)
+    }
+
+    fn or_expression(&mut self) -> Result<Expression, LigatureError> {
+        let mut expression = self.and_expression()?;
+        while self.consume("||") {
+            expression = Expression::Or(Box::new(expression), Box::new(self.and_expression()?));
+        }
+        Ok(expression)
+    }
+
+    fn and_expression(&mut self) -> Result<Expression, LigatureError> {
+        let mut expression = self.unary_expression()?;
+        while self.consume("&&") {
+            expression = Expression::And(Box::new(expression), Box::new(self.unary_expression()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary_expression(&mut self) -> Result<Expression, LigatureError> {
+        self.skip_whitespace();
+        if self.peek() == Some('!') && self.peek_at(1) != Some('=') {
+            self.position += 1;
+            return Ok(Expression::Not(Box::new(self.unary_expression()?)));
+        }
+        let left = self.primary_expression()?;
+        let comparison = if self.consume("!=") {
+            Comparison::NotEqual
+        } else if self.consume("<=") {
+            Comparison::LessOrEqual
+        } else if self.consume(">=") {
+            Comparison::GreaterOrEqual
+        } else if self.consume("=") {
+            Comparison::Equal
+        } else if self.consume("<") {
+            Comparison::Less
+        } else if self.consume(">") {
+            Comparison::Greater
+        } else {
+            return Ok(left);
+        };
+        let right = self.primary_expression()?;
+        Ok(Expression::Compare(
+            comparison,
+            Box::new(left),
+            Box::new(right),
+        ))
+    }
+
+    fn primary_expression(&mut self) -> Result<Expression, LigatureError> {
+        if self.consume("(") {
+            let expression = self.or_expression()?;
+            self.expect(")")?;
+            Ok(expression)
+        } else if self.keyword("BOUND") {
+            self.expect("(")?;
+            let variable = self.variable()?;
+            self.expect(")")?;
+            Ok(Expression::Bound(variable))
+        } else {
+            Ok(Expression::Term(self.term()?))
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::encoding::{
+    ATTRIBUTE_STATEMENT_COUNT_PREFIX, BYTES_LITERAL_ID_COUNTER_KEY,
+    BYTES_LITERAL_VALUE_TO_ID_PREFIX, DATASET_SIZE_KEY, ENTITY_COUNT_KEY, ENTITY_REGISTRY_PREFIX,
+    LARGE_LITERAL_HASH_TO_ID_PREFIX, STATEMENT_COUNT_KEY, TAGGED_LITERAL_ID_COUNTER_KEY,
+    TAGGED_LITERAL_VALUE_TO_ID_PREFIX,
+};
+use crate::entity_registry::EntityKind;
+use crate::query_tx::LigatureSledQueryTx;
+use ligature::{Attribute, LigatureError};
+use ligature_kv::{
+    decode_id, encode_id, prepend, ATTRIBUTE_ID_COUNTER_KEY, ATTRIBUTE_NAME_TO_ID_PREFIX,
+    EAVC_PREFIX, ENTITY_ID_COUNTER_KEY, STRING_LITERAL_ID_COUNTER_KEY,
+    STRING_LITERAL_VALUE_TO_ID_PREFIX,
+};
+use std::collections::BTreeMap;
+
+/// Counts and sizes for a single Dataset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetStats {
+    /// The number of Statements in the Dataset.
+    pub statement_count: u64,
+    /// The number of Entities made with new_entity that haven't been deleted, contexts aren't counted.
+    pub entity_count: u64,
+    /// The number of Attributes in the Attribute dictionary.
+    pub attribute_count: u64,
+    /// The number of Statements using each Attribute, ordered by Attribute id.
+    pub attribute_statement_counts: Vec<(Attribute, u64)>,
+    /// The number of String Literals in the String Literal dictionaries.
+    /// Large String Literals are removed with the last Statement that uses them, small ones are kept.
+    pub string_literal_count: u64,
+    /// The number of Bytes Literals in the Bytes Literal dictionary.
+    pub bytes_literal_count: u64,
+    /// The number of language and datatype tagged Literals in the tagged Literal dictionary.
+    pub tagged_literal_count: u64,
+    /// The size of all keys and values in the Dataset's tree, kept up to date by every write.
+    pub total_bytes: u64,
+    pub entity_id_counter: u64,
+    pub attribute_id_counter: u64,
+    pub string_literal_id_counter: u64,
+    pub bytes_literal_id_counter: u64,
+    pub tagged_literal_id_counter: u64,
+}
+
+impl LigatureSledQueryTx {
+    /// Computes statistics for this Dataset.
+    /// Statement, Entity and per Attribute counts and the size are read from counts kept up to date by every write,
+    /// the dictionary counts come from prefix scans of the dictionaries.
+    pub fn stats(&self) -> Result<DatasetStats, LigatureError> {
+        let mut attribute_statement_counts = vec![];
+        for entry in self
+            .store
+            .scan_prefix(vec![ATTRIBUTE_STATEMENT_COUNT_PREFIX])
+        {
+            let (key, count) =
+                entry.map_err(|_| LigatureError("Error reading Attribute counts.".to_string()))?;
+            let attribute_id = decode_id(key[1..].to_vec())?;
+            attribute_statement_counts.push((
+                self.load_attribute(attribute_id)?,
+                decode_id(count.to_vec())?,
+            ));
+        }
+        Ok(DatasetStats {
+            statement_count: self.read_count(STATEMENT_COUNT_KEY)?,
+            entity_count: self.read_count(ENTITY_COUNT_KEY)?,
+            attribute_count: self.count_prefix(ATTRIBUTE_NAME_TO_ID_PREFIX)?,
+            attribute_statement_counts: attribute_statement_counts,
+            string_literal_count: self.count_prefix(STRING_LITERAL_VALUE_TO_ID_PREFIX)?
+                + self.count_prefix(LARGE_LITERAL_HASH_TO_ID_PREFIX)?,
+            bytes_literal_count: self.count_prefix(BYTES_LITERAL_VALUE_TO_ID_PREFIX)?,
+            tagged_literal_count: self.count_prefix(TAGGED_LITERAL_VALUE_TO_ID_PREFIX)?,
+            total_bytes: self.read_counter(DATASET_SIZE_KEY)?,
+            entity_id_counter: self.read_counter(ENTITY_ID_COUNTER_KEY)?,
+            attribute_id_counter: self.read_counter(ATTRIBUTE_ID_COUNTER_KEY)?,
+            string_literal_id_counter: self.read_counter(STRING_LITERAL_ID_COUNTER_KEY)?,
+            bytes_literal_id_counter: self.read_counter(BYTES_LITERAL_ID_COUNTER_KEY)?,
+            tagged_literal_id_counter: self.read_counter(TAGGED_LITERAL_ID_COUNTER_KEY)?,
+        })
+    }
+
+    fn count_prefix(&self, prefix: u8) -> Result<u64, LigatureError> {
+        let mut count = 0;
+        for key in self.store.scan_prefix(vec![prefix]).keys() {
+            key.map_err(|_| LigatureError(format!("Error counting prefix {}.", prefix)))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Reads a count, counts that haven't been written yet are zero.
+    fn read_count(&self, key: u8) -> Result<u64, LigatureError> {
+        match self
+            .store
+            .get(vec![key])
+            .map_err(|_| LigatureError(format!("Could not read count {}", key)))?
+        {
+            Some(count) => decode_id(count.to_vec()),
+            None => Ok(0),
+        }
+    }
+
+    fn read_counter(&self, key: u8) -> Result<u64, LigatureError> {
+        match self
+            .store
+            .get(vec![key])
+            .map_err(|_| LigatureError(format!("Could not read ID Counter for {}", key)))?
+        {
+            Some(id) => decode_id(id.to_vec()),
+            None => Err(LigatureError(format!(
+                "Could not find ID Counter for {}",
+                key
+            ))),
+        }
+    }
+}
+
+/// Returns the keys of the counts an entry of a Dataset's tree is part of.
+/// Every Statement has an EAVC key, which counts towards the Statement count and its Attribute's count,
+/// and Entities are counted by their registry entries.
+pub(crate) fn count_keys(key: &[u8], value: &[u8]) -> Vec<Vec<u8>> {
+    match key.first() {
+        Some(&EAVC_PREFIX) => vec![
+            vec![STATEMENT_COUNT_KEY],
+            prepend(ATTRIBUTE_STATEMENT_COUNT_PREFIX, key[9..17].to_vec()),
+        ],
+        Some(&ENTITY_REGISTRY_PREFIX) if value == EntityKind::Entity.encode().as_slice() => {
+            vec![vec![ENTITY_COUNT_KEY]]
+        }
+        _ => vec![],
+    }
+}
+
+/// Checks if a key is the key of the size or one of the counts, which aren't part of the Dataset's size.
+fn is_counter_key(key: &[u8]) -> bool {
+    key == [DATASET_SIZE_KEY]
+        || key == [STATEMENT_COUNT_KEY]
+        || key == [ENTITY_COUNT_KEY]
+        || key.first() == Some(&ATTRIBUTE_STATEMENT_COUNT_PREFIX)
+}
+
+/// The number of bytes an entry adds to a Dataset's size, counters don't count.
+pub(crate) fn entry_size(key: &[u8], value: &[u8]) -> u64 {
+    if is_counter_key(key) {
+        0
+    } else {
+        (key.len() + value.len()) as u64
+    }
+}
+
+/// Measures every key and value in a Dataset's tree and stores the result as the Dataset's size.
+/// Only used when a Dataset is created or migrated, after that write transactions keep the size up to date.
+pub(crate) fn record_tree_size(tree: &sled::Tree) -> Result<(), LigatureError> {
+    let error = || LigatureError("Error recording Dataset size.".to_string());
+    let mut size = 0;
+    for entry in tree.iter() {
+        let (key, value) = entry.map_err(|_| error())?;
+        size += entry_size(&key, &value);
+    }
+    tree.insert(vec![DATASET_SIZE_KEY], encode_id(size))
+        .map_err(|_| error())?;
+    Ok(())
+}
+
+/// Counts the Statements, the Statements of each Attribute and the Entities of a Dataset's tree
+/// and replaces the stored counts with the result.
+/// Only used when a Dataset is migrated, after that write transactions keep the counts up to date.
+pub(crate) fn record_counts(tree: &sled::Tree) -> Result<(), LigatureError> {
+    let error = || LigatureError("Error recording Dataset counts.".to_string());
+    let mut counts: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for prefix in &[EAVC_PREFIX, ENTITY_REGISTRY_PREFIX] {
+        for entry in tree.scan_prefix(vec![*prefix]) {
+            let (key, value) = entry.map_err(|_| error())?;
+            for count_key in count_keys(&key, &value) {
+                *counts.entry(count_key).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut batch = sled::Batch::default();
+    for key in tree
+        .scan_prefix(vec![ATTRIBUTE_STATEMENT_COUNT_PREFIX])
+        .keys()
+    {
+        batch.remove(key.map_err(|_| error())?);
+    }
+    batch.insert(vec![STATEMENT_COUNT_KEY], encode_id(0));
+    batch.insert(vec![ENTITY_COUNT_KEY], encode_id(0));
+    for (key, count) in counts {
+        batch.insert(key, encode_id(count));
+    }
+    tree.apply_batch(batch).map_err(|_| error())
+}
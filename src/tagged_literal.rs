@@ -0,0 +1,105 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Matching Language and Typed Literals by their tag.
+
+use crate::encoding::{
+    encode_literal_tag, LANGUAGE_VALUE_PREFIX, TAGGED_LITERAL_VALUE_TO_ID_PREFIX,
+    TYPED_VALUE_PREFIX,
+};
+use crate::keys::range_prefix;
+use crate::query_tx::LigatureSledQueryTx;
+use crate::value::{PersistedStatement, Value};
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::prepend;
+
+impl LigatureSledQueryTx {
+    /// Returns Statements whose Value is a Language Literal with the given language tag.
+    /// Tags are matched exactly so `en` doesn't match `en-GB`.
+    pub fn match_language_literals(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        language: &str,
+    ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
+        self.match_tagged_literals(entity, attribute, LANGUAGE_VALUE_PREFIX, language)
+    }
+
+    /// Returns Statements whose Value is a Typed Literal with the given datatype.
+    pub fn match_typed_literals(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        datatype: &str,
+    ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
+        self.match_tagged_literals(entity, attribute, TYPED_VALUE_PREFIX, datatype)
+    }
+
+    fn match_tagged_literals(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value_prefix: u8,
+        tag: &str,
+    ) -> Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>> {
+        match self.scan_tagged_literals(entity, attribute, value_prefix, tag) {
+            Ok(iter) => iter,
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+
+    /// When an Entity or Attribute is given all of its Literals of this kind are scanned and filtered by tag,
+    /// otherwise the Literals with the tag are found in the dictionary first.
+    fn scan_tagged_literals(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value_prefix: u8,
+        tag: &str,
+    ) -> Result<Box<dyn Iterator<Item = Result<PersistedStatement, LigatureError>>>, LigatureError>
+    {
+        let encoded_tag = encode_literal_tag(value_prefix, tag)?;
+        let attribute_id = match attribute {
+            Some(attribute) => match self.lookup_attribute(&attribute)? {
+                Some(id) => Some(id),
+                None => return Ok(Box::new(std::iter::empty())),
+            },
+            None => None,
+        };
+        let mut prefix = range_prefix(entity.as_ref().map(|e| e.0), attribute_id);
+        prefix.push(value_prefix);
+        let tx = self.clone();
+        let load = move |key_res: Result<(sled::IVec, sled::IVec), sled::Error>| match key_res {
+            Ok(key) => tx.load_statement(key.0.to_vec()),
+            Err(_) => Err(LigatureError("Error matching Statements.".to_string())),
+        };
+        if entity.is_some() || attribute_id.is_some() {
+            let tag = tag.to_string();
+            let iter = self.store.scan_prefix(prefix).map(load);
+            Ok(Box::new(iter.filter(move |statement| match statement {
+                Ok(statement) => match &statement.statement.value {
+                    Value::LanguageLiteral(_, value_tag) | Value::TypedLiteral(_, value_tag) => {
+                        *value_tag == tag
+                    }
+                    _ => false,
+                },
+                Err(_) => true,
+            })))
+        } else {
+            let ids = self
+                .store
+                .scan_prefix(prepend(TAGGED_LITERAL_VALUE_TO_ID_PREFIX, encoded_tag))
+                .values()
+                .collect::<Result<Vec<sled::IVec>, sled::Error>>()
+                .map_err(|_| LigatureError("Error reading Literal dictionary.".to_string()))?;
+            let store = self.store.clone();
+            let iter = ids.into_iter().flat_map(move |id| {
+                let mut key = prefix.clone();
+                key.extend_from_slice(&id);
+                store.scan_prefix(key)
+            });
+            Ok(Box::new(iter.map(load)))
+        }
+    }
+}
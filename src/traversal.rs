@@ -0,0 +1,367 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Traversing the graph made by Statements whose Value is an Entity.
+//! Outgoing edges are read from EAVC and EVAC and incoming edges from VAEC and VEAC,
+//! so each step is a prefix scan that only visits Entity valued Statements.
+
+use crate::keys::decode_permutation;
+use crate::query_tx::LigatureSledQueryTx;
+use ligature::{Attribute, Entity, LigatureError};
+use ligature_kv::{
+    decode_id, encode_id, StatementIDSet, EAVC_PREFIX, ENTITY_VALUE_PREFIX, EVAC_PREFIX,
+    VAEC_PREFIX, VEAC_PREFIX,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Which Statements to follow from an Entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From a Statement's Entity to its Value.
+    Outgoing,
+    /// From a Statement's Value to its Entity.
+    Incoming,
+    /// Both ways.
+    Both,
+}
+
+/// A Statement whose Value is an Entity, read in the direction it was followed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: Entity,
+    pub attribute: Attribute,
+    pub to: Entity,
+    pub context: Entity,
+}
+
+/// How to walk the graph, by default outgoing edges with any Attribute to any depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Traversal {
+    pub direction: Direction,
+    /// Only follow Statements with this Attribute.
+    pub attribute: Option<Attribute>,
+    /// Don't follow edges from Entities this many steps from the start.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for Traversal {
+    fn default() -> Self {
+        Traversal {
+            direction: Direction::Outgoing,
+            attribute: None,
+            max_depth: None,
+        }
+    }
+}
+
+/// A Traversal with its Attribute looked up.
+#[derive(Clone)]
+struct Walk {
+    tx: LigatureSledQueryTx,
+    direction: Direction,
+    /// None when the Attribute has never been stored so there are no edges to follow.
+    attribute_id: Option<Option<u64>>,
+    max_depth: Option<usize>,
+}
+
+impl Walk {
+    fn new(tx: &LigatureSledQueryTx, traversal: &Traversal) -> Result<Self, LigatureError> {
+        let attribute_id = match &traversal.attribute {
+            Some(attribute) => tx.lookup_attribute(attribute)?.map(Some),
+            None => Some(None),
+        };
+        Ok(Walk {
+            tx: tx.clone(),
+            direction: traversal.direction,
+            attribute_id: attribute_id,
+            max_depth: traversal.max_depth,
+        })
+    }
+
+    fn can_expand(&self, depth: usize) -> bool {
+        match self.max_depth {
+            Some(max_depth) => depth < max_depth,
+            None => true,
+        }
+    }
+
+    /// The key prefixes to scan for edges from an Entity, and whether they are outgoing.
+    fn prefixes(&self, entity_id: u64) -> Vec<(Vec<u8>, bool)> {
+        let attribute_id = match self.attribute_id {
+            Some(attribute_id) => attribute_id,
+            None => return vec![],
+        };
+        let mut prefixes = vec![];
+        if self.direction != Direction::Incoming {
+            let prefix = match attribute_id {
+                Some(attribute_id) => {
+                    let mut prefix = vec![EAVC_PREFIX];
+                    prefix.extend(encode_id(entity_id));
+                    prefix.extend(encode_id(attribute_id));
+                    prefix.push(ENTITY_VALUE_PREFIX);
+                    prefix
+                }
+                None => {
+                    let mut prefix = vec![EVAC_PREFIX];
+                    prefix.extend(encode_id(entity_id));
+                    prefix.push(ENTITY_VALUE_PREFIX);
+                    prefix
+                }
+            };
+            prefixes.push((prefix, true));
+        }
+        if self.direction != Direction::Outgoing {
+            let mut prefix = vec![
+                if attribute_id.is_some() {
+                    VAEC_PREFIX
+                } else {
+                    VEAC_PREFIX
+                },
+                ENTITY_VALUE_PREFIX,
+            ];
+            prefix.extend(encode_id(entity_id));
+            if let Some(attribute_id) = attribute_id {
+                prefix.extend(encode_id(attribute_id));
+            }
+            prefixes.push((prefix, false));
+        }
+        prefixes
+    }
+
+    /// Returns the id of the Entity at the other end of each edge from an Entity with the Statement it came from.
+    fn edges(
+        &self,
+        entity_id: u64,
+    ) -> Box<dyn Iterator<Item = Result<(u64, StatementIDSet), LigatureError>>> {
+        let store = self.tx.store.clone();
+        Box::new(
+            self.prefixes(entity_id)
+                .into_iter()
+                .flat_map(move |(prefix, outgoing)| {
+                    store.scan_prefix(prefix).map(move |entry| {
+                        let (key, _) = entry.map_err(|_| {
+                            LigatureError("Error reading Entity edges.".to_string())
+                        })?;
+                        let statement_id_set = decode_permutation(&key)?;
+                        let other = if outgoing {
+                            decode_id(statement_id_set.value_body.clone())?
+                        } else {
+                            statement_id_set.entity_id
+                        };
+                        Ok((other, statement_id_set))
+                    })
+                }),
+        )
+    }
+
+    fn edge(
+        &self,
+        from: u64,
+        to: u64,
+        statement_id_set: &StatementIDSet,
+    ) -> Result<Edge, LigatureError> {
+        Ok(Edge {
+            from: Entity(from),
+            attribute: self.tx.load_attribute(statement_id_set.attribute_id)?,
+            to: Entity(to),
+            context: Entity(statement_id_set.context_id),
+        })
+    }
+}
+
+impl LigatureSledQueryTx {
+    /// Returns the edges from an Entity in the given direction, optionally only ones with the given Attribute.
+    pub fn neighbours(
+        &self,
+        entity: &Entity,
+        direction: Direction,
+        attribute: Option<Attribute>,
+    ) -> Box<dyn Iterator<Item = Result<Edge, LigatureError>>> {
+        let traversal = Traversal {
+            direction: direction,
+            attribute: attribute,
+            max_depth: None,
+        };
+        let walk = match Walk::new(self, &traversal) {
+            Ok(walk) => walk,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+        let from = entity.0;
+        Box::new(walk.edges(from).map(move |edge| {
+            let (to, statement_id_set) = edge?;
+            walk.edge(from, to, &statement_id_set)
+        }))
+    }
+
+    /// Visits Entities reachable from the start in breadth first order with their distance from the start,
+    /// starting with the start at depth 0. Each Entity is visited once.
+    pub fn breadth_first(
+        &self,
+        start: &Entity,
+        traversal: &Traversal,
+    ) -> Box<dyn Iterator<Item = Result<(Entity, usize), LigatureError>>> {
+        match Walk::new(self, traversal) {
+            Ok(walk) => {
+                let mut queue = VecDeque::new();
+                queue.push_back((start.0, 0));
+                let mut visited = HashSet::new();
+                visited.insert(start.0);
+                Box::new(BreadthFirst {
+                    walk: walk,
+                    queue: queue,
+                    visited: visited,
+                    failed: false,
+                })
+            }
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+
+    /// Visits Entities reachable from the start in depth first order with their depth in the search,
+    /// starting with the start at depth 0. Each Entity is visited once.
+    pub fn depth_first(
+        &self,
+        start: &Entity,
+        traversal: &Traversal,
+    ) -> Box<dyn Iterator<Item = Result<(Entity, usize), LigatureError>>> {
+        match Walk::new(self, traversal) {
+            Ok(walk) => Box::new(DepthFirst {
+                walk: walk,
+                stack: vec![(start.0, 0)],
+                visited: HashSet::new(),
+                failed: false,
+            }),
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+
+    /// Returns every Entity reachable from the start within the Traversal's depth, not including the start.
+    pub fn reachable(
+        &self,
+        start: &Entity,
+        traversal: &Traversal,
+    ) -> Box<dyn Iterator<Item = Result<Entity, LigatureError>>> {
+        Box::new(
+            self.breadth_first(start, traversal)
+                .skip(1)
+                .map(|visit| visit.map(|(entity, _)| entity)),
+        )
+    }
+
+    /// Returns the edges of a shortest path between two Entities, or None if there isn't one within the Traversal's depth.
+    /// The path from an Entity to itself is empty.
+    pub fn shortest_path(
+        &self,
+        from: &Entity,
+        to: &Entity,
+        traversal: &Traversal,
+    ) -> Result<Option<Vec<Edge>>, LigatureError> {
+        let walk = Walk::new(self, traversal)?;
+        // the Entity each visited Entity was reached from, with the Statement that was followed
+        let mut parents: HashMap<u64, (u64, StatementIDSet)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((from.0, 0));
+        let mut visited = HashSet::new();
+        visited.insert(from.0);
+        while let Some((entity_id, depth)) = queue.pop_front() {
+            if entity_id == to.0 {
+                let mut path = vec![];
+                let mut current = entity_id;
+                while let Some((parent, statement_id_set)) = parents.get(&current) {
+                    path.push(walk.edge(*parent, current, statement_id_set)?);
+                    current = *parent;
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+            if !walk.can_expand(depth) {
+                continue;
+            }
+            for edge in walk.edges(entity_id) {
+                let (other, statement_id_set) = edge?;
+                if visited.insert(other) {
+                    parents.insert(other, (entity_id, statement_id_set));
+                    queue.push_back((other, depth + 1));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+struct BreadthFirst {
+    walk: Walk,
+    queue: VecDeque<(u64, usize)>,
+    visited: HashSet<u64>,
+    failed: bool,
+}
+
+impl Iterator for BreadthFirst {
+    type Item = Result<(Entity, usize), LigatureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (entity_id, depth) = self.queue.pop_front()?;
+        if self.walk.can_expand(depth) {
+            for edge in self.walk.edges(entity_id) {
+                match edge {
+                    Ok((other, _)) => {
+                        if self.visited.insert(other) {
+                            self.queue.push_back((other, depth + 1));
+                        }
+                    }
+                    Err(e) => {
+                        self.failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        Some(Ok((Entity(entity_id), depth)))
+    }
+}
+
+struct DepthFirst {
+    walk: Walk,
+    stack: Vec<(u64, usize)>,
+    visited: HashSet<u64>,
+    failed: bool,
+}
+
+impl Iterator for DepthFirst {
+    type Item = Result<(Entity, usize), LigatureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let (entity_id, depth) = self.stack.pop()?;
+            if !self.visited.insert(entity_id) {
+                continue;
+            }
+            if self.walk.can_expand(depth) {
+                let mut next = vec![];
+                for edge in self.walk.edges(entity_id) {
+                    match edge {
+                        Ok((other, _)) => {
+                            if !self.visited.contains(&other) {
+                                next.push((other, depth + 1));
+                            }
+                        }
+                        Err(e) => {
+                            self.failed = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                // pushed in reverse so edges are followed in key order
+                self.stack.extend(next.into_iter().rev());
+            }
+            return Some(Ok((Entity(entity_id), depth)));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Sled's TransactionalTree can't scan, so write transactions go through a TxTree that keeps track of the
+//! transaction's pending writes and merges them into scans of the Dataset's tree.
+//! Scans aren't part of sled's conflict detection,
+//! that's fine since LigatureSled only runs one write transaction at a time while holding the store's write lock.
+//! It also keeps track of how many bytes the transaction adds to the tree and how it changes the Dataset's counts,
+//! so the stored size and counts are updated in the same transaction.
+
+use crate::stats;
+use ligature::LigatureError;
+use ligature_kv::{decode_id, encode_id};
+use sled::transaction::{TransactionalTree, UnabortableTransactionResult};
+use sled::IVec;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+
+pub struct TxTree {
+    tx: TransactionalTree,
+    tree: sled::Tree,
+    pending: RefCell<BTreeMap<Vec<u8>, Option<IVec>>>,
+    /// The change in the size of the keys and values in the tree made by this transaction.
+    size_delta: Cell<i64>,
+    /// The change in each count stored in the tree made by this transaction, by the count's key.
+    count_deltas: RefCell<BTreeMap<Vec<u8>, i64>>,
+}
+
+impl TxTree {
+    pub fn new(tx: TransactionalTree, tree: sled::Tree) -> Self {
+        Self {
+            tx: tx,
+            tree: tree,
+            pending: RefCell::new(BTreeMap::new()),
+            size_delta: Cell::new(0),
+            count_deltas: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> UnabortableTransactionResult<Option<IVec>> {
+        self.tx.get(key)
+    }
+
+    pub fn insert<K, V>(&self, key: K, value: V) -> UnabortableTransactionResult<Option<IVec>>
+    where
+        K: AsRef<[u8]> + Into<IVec>,
+        V: Into<IVec>,
+    {
+        let key: IVec = key.into();
+        let value: IVec = value.into();
+        self.pending
+            .borrow_mut()
+            .insert(key.to_vec(), Some(value.clone()));
+        let size = (key.len() + value.len()) as i64;
+        let previous = self.tx.insert(key.clone(), value.clone())?;
+        self.size_delta
+            .set(self.size_delta.get() + size - entry_size(&key, &previous));
+        if let Some(previous) = &previous {
+            self.count(&key, previous, -1);
+        }
+        self.count(&key, &value, 1);
+        Ok(previous)
+    }
+
+    pub fn remove<K>(&self, key: K) -> UnabortableTransactionResult<Option<IVec>>
+    where
+        K: AsRef<[u8]> + Into<IVec>,
+    {
+        let key: IVec = key.into();
+        self.pending.borrow_mut().insert(key.to_vec(), None);
+        let previous = self.tx.remove(key.clone())?;
+        self.size_delta
+            .set(self.size_delta.get() - entry_size(&key, &previous));
+        if let Some(previous) = &previous {
+            self.count(&key, previous, -1);
+        }
+        Ok(previous)
+    }
+
+    fn count(&self, key: &[u8], value: &[u8], delta: i64) {
+        let mut count_deltas = self.count_deltas.borrow_mut();
+        for count_key in stats::count_keys(key, value) {
+            *count_deltas.entry(count_key).or_insert(0) += delta;
+        }
+    }
+
+    /// Adds the changes made by this transaction to the size stored under the given key and to the stored counts.
+    /// The size and count entries themselves aren't counted, per Attribute counts that drop to zero are removed.
+    pub fn write_counters(&self, size_key: u8) -> Result<(), LigatureError> {
+        let size_delta = self.size_delta.replace(0);
+        if size_delta != 0 {
+            self.add_to_counter(vec![size_key], size_delta)?;
+        }
+        let count_deltas = std::mem::take(&mut *self.count_deltas.borrow_mut());
+        for (count_key, delta) in count_deltas {
+            if delta != 0 {
+                self.add_to_counter(count_key, delta)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_to_counter(&self, key: Vec<u8>, delta: i64) -> Result<(), LigatureError> {
+        let error = || LigatureError("Error updating Dataset counters.".to_string());
+        let current = match self.tx.get(key.clone()).map_err(|_| error())? {
+            Some(current) => decode_id(current.to_vec())? as i64,
+            None => 0,
+        };
+        let next = std::cmp::max(current + delta, 0) as u64;
+        if next == 0 && key.len() > 1 {
+            self.tx.remove(key).map_err(|_| error())?;
+        } else {
+            self.tx.insert(key, encode_id(next)).map_err(|_| error())?;
+        }
+        Ok(())
+    }
+
+    /// Scans all keys starting with the given prefix, including writes made earlier in this transaction.
+    pub fn scan_prefix(
+        &self,
+        prefix: Vec<u8>,
+    ) -> Box<dyn Iterator<Item = Result<(IVec, IVec), LigatureError>>> {
+        let pending: Vec<(Vec<u8>, Option<IVec>)> = self
+            .pending
+            .borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Box::new(MergedScan {
+            tree: self.tree.scan_prefix(prefix).peekable(),
+            pending: pending.into_iter().peekable(),
+        })
+    }
+}
+
+fn entry_size(key: &[u8], value: &Option<IVec>) -> i64 {
+    match value {
+        Some(value) => (key.len() + value.len()) as i64,
+        None => 0,
+    }
+}
+
+/// Merges two sorted scans, pending writes replace entries with the same key in the tree.
+struct MergedScan {
+    tree: Peekable<sled::Iter>,
+    pending: Peekable<std::vec::IntoIter<(Vec<u8>, Option<IVec>)>>,
+}
+
+impl Iterator for MergedScan {
+    type Item = Result<(IVec, IVec), LigatureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.tree.peek(), self.pending.peek()) {
+                (None, None) => return None,
+                (Some(Err(_)), _) => {
+                    self.tree.next();
+                    return Some(Err(LigatureError("Error scanning Dataset.".to_string())));
+                }
+                (Some(Ok((tree_key, _))), Some((pending_key, _))) => {
+                    tree_key.as_ref().cmp(pending_key.as_slice())
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+            };
+            if order == Ordering::Less {
+                return self.tree.next().map(|entry| {
+                    entry.map_err(|_| LigatureError("Error scanning Dataset.".to_string()))
+                });
+            }
+            if order == Ordering::Equal {
+                self.tree.next();
+            }
+            if let Some((key, Some(value))) = self.pending.next() {
+                return Some(Ok((IVec::from(key), value)));
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The Values, Statements and Ranges stored by ligature-sled.
+//! ligature's Value only has Entities, Strings, Integers and Floats,
+//! these types add the Value types ligature-sled stores on top of those.
+//! The ligature QueryTx and WriteTx traits keep working with ligature's types,
+//! Statements using the added Value types can only be read and written through
+//! LigatureSledQueryTx and LigatureSledWriteTx.
+
+use ligature::{Attribute, Entity, LigatureError};
+use std::convert::TryFrom;
+
+/// A Value of a Statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Entity(Entity),
+    StringLiteral(String),
+    IntegerLiteral(i64),
+    FloatLiteral(f64),
+    BooleanLiteral(bool),
+    BytesLiteral(Vec<u8>),
+    /// Nanoseconds since the Unix epoch in UTC.
+    TimestampLiteral(i64),
+    /// A string and its language tag.
+    LanguageLiteral(String, String),
+    /// A string and its datatype IRI.
+    TypedLiteral(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub entity: Entity,
+    pub attribute: Attribute,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedStatement {
+    pub statement: Statement,
+    pub context: Entity,
+}
+
+/// A range of Values from start (inclusive) to end (exclusive).
+/// String Literals longer than LARGE_LITERAL_THRESHOLD bytes are keyed by hash and never in a StringLiteralRange.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Range {
+    StringLiteralRange { start: String, end: String },
+    IntegerLiteralRange { start: i64, end: i64 },
+    FloatLiteralRange { start: f64, end: f64 },
+    BytesLiteralRange { start: Vec<u8>, end: Vec<u8> },
+    TimestampLiteralRange { start: i64, end: i64 },
+}
+
+impl From<ligature::Value> for Value {
+    fn from(value: ligature::Value) -> Self {
+        match value {
+            ligature::Value::Entity(entity) => Value::Entity(entity),
+            ligature::Value::StringLiteral(value) => Value::StringLiteral(value),
+            ligature::Value::IntegerLiteral(value) => Value::IntegerLiteral(value),
+            ligature::Value::FloatLiteral(value) => Value::FloatLiteral(value),
+        }
+    }
+}
+
+impl TryFrom<Value> for ligature::Value {
+    type Error = LigatureError;
+
+    /// Fails for the Value types ligature doesn't have.
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Entity(entity) => Ok(ligature::Value::Entity(entity)),
+            Value::StringLiteral(value) => Ok(ligature::Value::StringLiteral(value)),
+            Value::IntegerLiteral(value) => Ok(ligature::Value::IntegerLiteral(value)),
+            Value::FloatLiteral(value) => Ok(ligature::Value::FloatLiteral(value)),
+            value => Err(LigatureError(format!(
+                "{:?} can only be read through LigatureSledQueryTx.",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<ligature::Statement> for Statement {
+    fn from(statement: ligature::Statement) -> Self {
+        Statement {
+            entity: statement.entity,
+            attribute: statement.attribute,
+            value: statement.value.into(),
+        }
+    }
+}
+
+impl TryFrom<Statement> for ligature::Statement {
+    type Error = LigatureError;
+
+    fn try_from(statement: Statement) -> Result<Self, Self::Error> {
+        Ok(ligature::Statement {
+            entity: statement.entity,
+            attribute: statement.attribute,
+            value: ligature::Value::try_from(statement.value)?,
+        })
+    }
+}
+
+impl From<ligature::PersistedStatement> for PersistedStatement {
+    fn from(persisted_statement: ligature::PersistedStatement) -> Self {
+        PersistedStatement {
+            statement: persisted_statement.statement.into(),
+            context: persisted_statement.context,
+        }
+    }
+}
+
+impl TryFrom<PersistedStatement> for ligature::PersistedStatement {
+    type Error = LigatureError;
+
+    fn try_from(persisted_statement: PersistedStatement) -> Result<Self, Self::Error> {
+        Ok(ligature::PersistedStatement {
+            statement: ligature::Statement::try_from(persisted_statement.statement)?,
+            context: persisted_statement.context,
+        })
+    }
+}
+
+impl From<ligature::Range> for Range {
+    fn from(range: ligature::Range) -> Self {
+        match range {
+            ligature::Range::StringLiteralRange { start, end } => Range::StringLiteralRange {
+                start: start,
+                end: end,
+            },
+            ligature::Range::IntegerLiteralRange { start, end } => Range::IntegerLiteralRange {
+                start: start,
+                end: end,
+            },
+            ligature::Range::FloatLiteralRange { start, end } => Range::FloatLiteralRange {
+                start: start,
+                end: end,
+            },
+        }
+    }
+}
@@ -2,34 +2,72 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use ligature::{
-    Attribute, Dataset, Entity, Ligature, LigatureError, PersistedStatement, QueryTx, Range,
-    Statement, Value, WriteTx,
+use crate::encoding::{
+    encode_boolean, encode_float, encode_integer, encode_tagged_literal, encode_timestamp,
+    BOOLEAN_VALUE_PREFIX, BYTES_LITERAL_ID_COUNTER_KEY, BYTES_LITERAL_ID_TO_VALUE_PREFIX,
+    BYTES_LITERAL_VALUE_TO_ID_PREFIX, BYTES_VALUE_PREFIX, LANGUAGE_VALUE_PREFIX,
+    TAGGED_LITERAL_ID_COUNTER_KEY, TAGGED_LITERAL_ID_TO_VALUE_PREFIX,
+    TAGGED_LITERAL_VALUE_TO_ID_PREFIX, TIMESTAMP_VALUE_PREFIX, TYPED_VALUE_PREFIX,
 };
+use crate::entity_registry::EntityKind;
+use crate::keys::{self, decode_permutation};
+use crate::large_literal::string_literal_key;
+use crate::schema::SchemaViolation;
+use crate::tx_tree::TxTree;
+use crate::value::{PersistedStatement, Statement, Value};
+use ligature::{Attribute, Entity, LigatureError, WriteTx};
 use ligature_kv::{
-    decode_dataset, decode_id, encode_attribute, encode_dataset, encode_dataset_match, encode_id,
-    encode_statement_permutations, encode_string_literal, prepend, StatementIDSet, AEVC_PREFIX,
+    decode_id, encode_attribute, encode_id, encode_statement_permutations, prepend, StatementIDSet,
     ATTRIBUTE_ID_COUNTER_KEY, ATTRIBUTE_ID_TO_NAME_PREFIX, ATTRIBUTE_NAME_TO_ID_PREFIX,
-    CEAV_PREFIX, ENTITY_ID_COUNTER_KEY, ENTITY_VALUE_PREFIX, FLOAT_VALUE_PREFIX,
-    INTEGER_VALUE_PREFIX, STRING_LITERAL_ID_COUNTER_KEY, STRING_LITERAL_ID_TO_VALUE_PREFIX,
-    STRING_LITERAL_VALUE_TO_ID_PREFIX, STRING_VALUE_PREFIX, VEAC_PREFIX,
+    EAVC_PREFIX, ENTITY_VALUE_PREFIX, FLOAT_VALUE_PREFIX, INTEGER_VALUE_PREFIX,
+    STRING_LITERAL_ID_COUNTER_KEY, STRING_VALUE_PREFIX,
 };
 use std::cell::Cell;
+use std::convert::TryFrom;
+
+/// Why a Statement couldn't be added, errors caused by the Statement itself are kept apart
+/// from errors reading or writing the store.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WriteError {
+    /// The Statement's Entity or Value is an Entity that can't be used.
+    InvalidEntity(LigatureError),
+    /// The Statement breaks its Attribute's schema.
+    SchemaViolation(SchemaViolation),
+    /// Reading or writing the store failed.
+    Store(LigatureError),
+}
+
+impl From<LigatureError> for WriteError {
+    fn from(error: LigatureError) -> Self {
+        WriteError::Store(error)
+    }
+}
+
+impl From<WriteError> for LigatureError {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::InvalidEntity(error) => error,
+            WriteError::SchemaViolation(violation) => violation.into(),
+            WriteError::Store(error) => error,
+        }
+    }
+}
 
 pub struct LigatureSledWriteTx {
-    store: sled::transaction::TransactionalTree,
+    pub(crate) store: TxTree,
     pub active: Cell<bool>,
 }
 
 impl LigatureSledWriteTx {
-    pub fn new(store: sled::transaction::TransactionalTree) -> Self {
+    /// Creates a write transaction over a transaction on the given Dataset tree.
+    pub fn new(store: sled::transaction::TransactionalTree, tree: sled::Tree) -> Self {
         Self { 
-            store: store,
+            store: TxTree::new(store, tree),
             active: Cell::new(true), 
         }
     }
 
-    fn read_id(&self, id: u8) -> Result<u64, LigatureError> {
+    pub(crate) fn read_id(&self, id: u8) -> Result<u64, LigatureError> {
         let id_opt = self
             .store
             .get(vec![id])
@@ -46,20 +84,48 @@ impl LigatureSledWriteTx {
         }
     }
 
-    /// Checks if the passed Entity is valid and if so returns the id of the entity.
+    /// Checks if the passed Entity is registered as a plain Entity and if so returns the id of the entity.
     /// Otherwise a LigatureError is returned.
     fn check_entity(&self, entity: &Entity) -> Result<u64, LigatureError> {
-        let current_id = self.read_id(ENTITY_ID_COUNTER_KEY)?;
-        if entity.0 <= current_id {
-            Ok(entity.0)
-        } else {
-            Err(LigatureError(format!("Invalid Entity {:?}", entity)))
+        match self.invalid_entity(entity)? {
+            Some(error) => Err(error),
+            None => Ok(entity.0),
+        }
+    }
+
+    /// Returns the error check_entity would return for an Entity that isn't registered as a plain Entity,
+    /// kept apart from errors reading the registry.
+    pub(crate) fn invalid_entity(
+        &self,
+        entity: &Entity,
+    ) -> Result<Option<LigatureError>, LigatureError> {
+        match self.entity_kind(entity)? {
+            Some(EntityKind::Entity) => Ok(None),
+            Some(EntityKind::Context) => Ok(Some(LigatureError(format!(
+                "Entity {} is the context of a Statement, not an Entity.",
+                entity.0
+            )))),
+            None if entity.0 == 0 => {
+                Ok(Some(LigatureError("Entity 0 is never valid.".to_string())))
+            }
+            None => Ok(Some(LigatureError(format!(
+                "Entity {} doesn't exist in this Dataset.",
+                entity.0
+            )))),
+        }
+    }
+
+    /// Checks if the passed Entity is registered, as a plain Entity or a context, and returns its id.
+    fn check_subject(&self, entity: &Entity) -> Result<u64, LigatureError> {
+        match self.entity_kind(entity)? {
+            Some(_) => Ok(entity.0),
+            None => self.check_entity(entity),
         }
     }
 
     /// Checks if an Attribute exists and returns it's id if it does.
     /// Otherwise it creates a new Attribute and returns the new id.
-    fn check_or_create_attribute(&self, attribute: &Attribute) -> Result<u64, LigatureError> {
+    pub(crate) fn check_or_create_attribute(&self, attribute: &Attribute) -> Result<u64, LigatureError> {
         let encoded_attribute = prepend(ATTRIBUTE_NAME_TO_ID_PREFIX, encode_attribute(attribute));
         let attribute_opt = self
             .store
@@ -92,6 +158,47 @@ impl LigatureSledWriteTx {
         Ok(next_attribute_id)
     }
 
+    /// Looks up the id of an Attribute without creating it.
+    pub(crate) fn lookup_attribute_id(
+        &self,
+        attribute: &Attribute,
+    ) -> Result<Option<u64>, LigatureError> {
+        let attribute_opt = self
+            .store
+            .get(prepend(ATTRIBUTE_NAME_TO_ID_PREFIX, encode_attribute(attribute)))
+            .map_err(|_| LigatureError(format!("Could not fetch Attribute {:?}", attribute)))?;
+        match attribute_opt {
+            Some(id) => Ok(Some(decode_id(id.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up the type prefix and body of a Value without creating it.
+    pub(crate) fn lookup_value(
+        &self,
+        value: &Value,
+    ) -> Result<Option<(u8, Vec<u8>)>, LigatureError> {
+        keys::lookup_value(value, |key| {
+            self.store
+                .get(key)
+                .map(|id| id.map(|id| id.to_vec()))
+                .map_err(|_| LigatureError(format!("Could not fetch Value {:?}", value)))
+        })
+    }
+
+    pub(crate) fn load_value(
+        &self,
+        value_type: u8,
+        value_body: Vec<u8>,
+    ) -> Result<Value, LigatureError> {
+        keys::load_value(value_type, value_body, |key| {
+            self.store
+                .get(key)
+                .map(|body| body.map(|body| body.to_vec()))
+                .map_err(|_| LigatureError(format!("Error loading Value of type {}", value_type)))
+        })
+    }
+
     /// Checks if a value exists and if it does returns the Value's type prefix and the Value's id.
     /// Otherwise it create a new instance of the value and returns the same.
     fn check_or_create_value(&self, value: &Value) -> Result<(u8, Vec<u8>), LigatureError> {
@@ -101,10 +208,17 @@ impl LigatureSledWriteTx {
                 Ok((ENTITY_VALUE_PREFIX, res.to_be_bytes().to_vec()))
             }
             Value::StringLiteral(value) => self.check_or_create_string_literal(value),
-            Value::IntegerLiteral(value) => {
-                Ok((INTEGER_VALUE_PREFIX, value.to_be_bytes().to_vec()))
+            Value::IntegerLiteral(value) => Ok((INTEGER_VALUE_PREFIX, encode_integer(*value))),
+            Value::FloatLiteral(value) => Ok((FLOAT_VALUE_PREFIX, encode_float(*value))),
+            Value::BooleanLiteral(value) => Ok((BOOLEAN_VALUE_PREFIX, encode_boolean(*value))),
+            Value::BytesLiteral(value) => self.check_or_create_bytes_literal(value),
+            Value::TimestampLiteral(value) => {
+                Ok((TIMESTAMP_VALUE_PREFIX, encode_timestamp(*value)))
+            }
+            Value::LanguageLiteral(_, _) => {
+                self.check_or_create_tagged_literal(LANGUAGE_VALUE_PREFIX, value)
             }
-            Value::FloatLiteral(value) => Ok((FLOAT_VALUE_PREFIX, value.to_be_bytes().to_vec())),
+            Value::TypedLiteral(_, _) => self.check_or_create_tagged_literal(TYPED_VALUE_PREFIX, value),
         }
     }
 
@@ -114,10 +228,7 @@ impl LigatureSledWriteTx {
         &self,
         string_literal: &String,
     ) -> Result<(u8, Vec<u8>), LigatureError> {
-        let encoded_string = prepend(
-            STRING_LITERAL_VALUE_TO_ID_PREFIX,
-            encode_string_literal(string_literal),
-        );
+        let encoded_string = string_literal_key(string_literal);
         let string_opt = self
             .store
             .get(encoded_string)
@@ -147,27 +258,112 @@ impl LigatureSledWriteTx {
             })?;
         self.store
             .insert(
-                prepend(
-                    STRING_LITERAL_VALUE_TO_ID_PREFIX,
-                    encode_string_literal(string_literal),
-                ),
+                string_literal_key(string_literal),
                 encode_id(next_string_literal_id),
             )
             .map_err(|_| {
                 LigatureError(format!("Error saving String Literal {:?}", string_literal))
             })?;
+        self.store_string_literal_body(next_string_literal_id, string_literal)?;
+        Ok(next_string_literal_id)
+    }
+
+    /// Checks if a Bytes Literal already exists or creates a new one if it doesn't.
+    /// Returns a tuple of the BYTES_VALUE_PREFIX and the Bytes Literal's ID.
+    fn check_or_create_bytes_literal(
+        &self,
+        bytes_literal: &Vec<u8>,
+    ) -> Result<(u8, Vec<u8>), LigatureError> {
+        let encoded_bytes = prepend(BYTES_LITERAL_VALUE_TO_ID_PREFIX, bytes_literal.clone());
+        let bytes_opt = self
+            .store
+            .get(encoded_bytes)
+            .map_err(|_| LigatureError(format!("Could not fetch Bytes {:?}", bytes_literal)))?;
+        match bytes_opt {
+            Some(id) => Ok((BYTES_VALUE_PREFIX, id.to_vec())),
+            None => Ok((
+                BYTES_VALUE_PREFIX,
+                encode_id(self.create_bytes_literal(bytes_literal)?),
+            )),
+        }
+    }
+
+    /// Creates a new Bytes Literal (does not check if it already exists!).
+    /// Returns the Bytes Literal's new ID.
+    fn create_bytes_literal(&self, bytes_literal: &Vec<u8>) -> Result<u64, LigatureError> {
+        let next_bytes_literal_id = self.read_id(BYTES_LITERAL_ID_COUNTER_KEY)? + 1;
+        self.store
+            .insert(
+                vec![BYTES_LITERAL_ID_COUNTER_KEY],
+                encode_id(next_bytes_literal_id),
+            )
+            .map_err(|_| {
+                LigatureError("Could not increment Bytes Literal ID Counter".to_string())
+            })?;
+        self.store
+            .insert(
+                prepend(BYTES_LITERAL_VALUE_TO_ID_PREFIX, bytes_literal.clone()),
+                encode_id(next_bytes_literal_id),
+            )
+            .map_err(|_| {
+                LigatureError(format!("Error saving Bytes Literal {:?}", bytes_literal))
+            })?;
         self.store
             .insert(
                 prepend(
-                    STRING_LITERAL_ID_TO_VALUE_PREFIX,
-                    encode_id(next_string_literal_id),
+                    BYTES_LITERAL_ID_TO_VALUE_PREFIX,
+                    encode_id(next_bytes_literal_id),
                 ),
-                encode_string_literal(string_literal),
+                bytes_literal.clone(),
             )
             .map_err(|_| {
-                LigatureError(format!("Error saving String Literal {:?}", string_literal))
+                LigatureError(format!("Error saving Bytes Literal {:?}", bytes_literal))
             })?;
-        Ok(next_string_literal_id)
+        Ok(next_bytes_literal_id)
+    }
+
+    /// Checks if a Language or Typed Literal already exists or creates a new one if it doesn't.
+    /// Literals with different tags are separate entries even if their values are the same.
+    fn check_or_create_tagged_literal(
+        &self,
+        value_prefix: u8,
+        literal: &Value,
+    ) -> Result<(u8, Vec<u8>), LigatureError> {
+        let encoded_literal = encode_tagged_literal(literal)?;
+        let literal_opt = self
+            .store
+            .get(prepend(
+                TAGGED_LITERAL_VALUE_TO_ID_PREFIX,
+                encoded_literal.clone(),
+            ))
+            .map_err(|_| LigatureError(format!("Could not fetch Literal {:?}", literal)))?;
+        match literal_opt {
+            Some(id) => Ok((value_prefix, id.to_vec())),
+            None => {
+                let next_literal_id = self.read_id(TAGGED_LITERAL_ID_COUNTER_KEY)? + 1;
+                self.store
+                    .insert(
+                        vec![TAGGED_LITERAL_ID_COUNTER_KEY],
+                        encode_id(next_literal_id),
+                    )
+                    .map_err(|_| {
+                        LigatureError("Could not increment Tagged Literal ID Counter".to_string())
+                    })?;
+                self.store
+                    .insert(
+                        prepend(TAGGED_LITERAL_VALUE_TO_ID_PREFIX, encoded_literal.clone()),
+                        encode_id(next_literal_id),
+                    )
+                    .map_err(|_| LigatureError(format!("Error saving Literal {:?}", literal)))?;
+                self.store
+                    .insert(
+                        prepend(TAGGED_LITERAL_ID_TO_VALUE_PREFIX, encode_id(next_literal_id)),
+                        encoded_literal,
+                    )
+                    .map_err(|_| LigatureError(format!("Error saving Literal {:?}", literal)))?;
+                Ok((value_prefix, encode_id(next_literal_id)))
+            }
+        }
     }
 
     fn lookup_statement_id_set(
@@ -175,7 +371,7 @@ impl LigatureSledWriteTx {
         statement: &Statement,
         context: &Entity,
     ) -> Result<StatementIDSet, LigatureError> {
-        let entity_id = self.check_entity(&statement.entity)?;
+        let entity_id = self.check_subject(&statement.entity)?;
         let attribute_id = self.check_or_create_attribute(&statement.attribute)?;
         let (value_type_prefix, value_body) = self.check_or_create_value(&statement.value)?;
 
@@ -187,19 +383,26 @@ impl LigatureSledWriteTx {
             context_id: context.0,
         })
     }
-}
-
-impl WriteTx for LigatureSledWriteTx {
-    fn new_entity(&self) -> Result<Entity, LigatureError> {
-        let next_id_value = self.read_id(ENTITY_ID_COUNTER_KEY)? + 1;
-        self.store
-            .insert(vec![ENTITY_ID_COUNTER_KEY], encode_id(next_id_value))
-            .map_err(|_| LigatureError("Could not increment Dataset Counter".to_string()))?;
-        Ok(Entity(next_id_value))
-    }
 
-    fn add_statement(&self, statement: &Statement) -> Result<PersistedStatement, LigatureError> {
-        let context = self.new_entity()?;
+    /// Adds a Statement in a context without checking that its Entity isn't a context.
+    pub(crate) fn insert_statement(
+        &self,
+        statement: &Statement,
+        context: Entity,
+    ) -> Result<PersistedStatement, WriteError> {
+        if self.entity_kind(&statement.entity)?.is_none() {
+            if let Some(error) = self.invalid_entity(&statement.entity)? {
+                return Err(WriteError::InvalidEntity(error));
+            }
+        }
+        if let Value::Entity(entity) = &statement.value {
+            if let Some(error) = self.invalid_entity(entity)? {
+                return Err(WriteError::InvalidEntity(error));
+            }
+        }
+        if let Some(violation) = self.validate_statement(statement)?.into_iter().next() {
+            return Err(WriteError::SchemaViolation(violation));
+        }
         let statement_id_set = self.lookup_statement_id_set(statement, &context)?;
         let permutations = encode_statement_permutations(&statement_id_set);
 
@@ -213,117 +416,224 @@ impl WriteTx for LigatureSledWriteTx {
         })
     }
 
-    fn remove_statement(
+    /// Removes every permutation of a Statement, and its String Literal Value if it is large and no longer used.
+    pub(crate) fn remove_statement_id_set(
         &self,
-        persisted_statement: &PersistedStatement,
-    ) -> Result<bool, LigatureError> {
-        // let prefix = prepend(CEAV_PREFIX, encode_id(persisted_statement.context.0));
-        // let lookup: Vec<Result<(sled::IVec, sled::IVec), sled::Error>> =
-        //     self.store.scan_prefix(prefix).collect();
-        // if lookup.len() > 1 {
-        //     panic!(
-        //         "Invalid state of Ligature, Contexts must be unique, {:?}!!!",
-        //         persisted_statement.context.0
-        //     ); //TODO not sure the best way to handle this
-        // }
-        // if lookup.len() == 0 {
-        //     return Ok(false);
-        // }
-        // let potential_match_encoded: Vec<u8> = lookup
-        //     .first()
-        //     .ok_or(LigatureError(format!(
-        //         "Error creating Statements permutations for {:?}",
-        //         persisted_statement
-        //     )))?
-        //     .clone()
-        //     .map_err(|_| {
-        //         LigatureError(format!(
-        //             "Error creating Statements permutations for {:?}",
-        //             persisted_statement
-        //         ))
-        //     })?
-        //     .0
-        //     .to_vec();
-        let statement_id_set = self.lookup_statement_id_set(
-            &persisted_statement.statement,
-            &persisted_statement.context,
-        )?;
-        let encoded_statement_permutations: Vec<Vec<u8>> =
-            encode_statement_permutations(&statement_id_set);
-        let encoded_eavc = encoded_statement_permutations.first().ok_or(LigatureError(format!(
-            "Error creating Statements permutations for {:?}",
-            persisted_statement
-        )))?.clone();
-        let res = self.store.get(encoded_eavc);
-
-        match res {
-            Ok(value) => {
-                for encoded_statement in encoded_statement_permutations.iter() {
-                    self.store.remove(encoded_statement.to_vec()).map_err(|_| {
-                        LigatureError(format!(
-                            "Could not remove Statement permutation {:?} for {:?}",
-                            encoded_statement, persisted_statement
-                        ))
-                    })?;
-                }
-                Ok(true)
+        statement_id_set: &StatementIDSet,
+    ) -> Result<(), LigatureError> {
+        for permutation in encode_statement_permutations(statement_id_set) {
+            self.store.remove(permutation).map_err(|_| {
+                LigatureError(format!(
+                    "Could not remove Statement with context {}",
+                    statement_id_set.context_id
+                ))
+            })?;
+        }
+        if statement_id_set.value_prefix == STRING_VALUE_PREFIX {
+            self.remove_unused_large_literal(decode_id(statement_id_set.value_body.clone())?)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces all Values an Entity has for an Attribute with the given Value.
+    /// Returns the Statements that were removed, the new Statement always gets a new context.
+    /// Context metadata about the removed Statements is removed too once their contexts are empty.
+    pub fn set_value(
+        &self,
+        entity: &Entity,
+        attribute: &Attribute,
+        value: &Value,
+    ) -> Result<Vec<PersistedStatement>, LigatureError> {
+        let entity_id = self.check_entity(entity)?;
+        let mut removed = vec![];
+        if let Some(attribute_id) = self.lookup_attribute_id(attribute)? {
+            let mut prefix = prepend(EAVC_PREFIX, encode_id(entity_id));
+            prefix.extend(encode_id(attribute_id));
+            let keys = self
+                .store
+                .scan_prefix(prefix)
+                .map(|entry| entry.map(|(key, _)| key))
+                .collect::<Result<Vec<sled::IVec>, LigatureError>>()?;
+            for key in keys {
+                let statement_id_set = decode_permutation(&key)?;
+                removed.push(PersistedStatement {
+                    statement: Statement {
+                        entity: entity.clone(),
+                        attribute: attribute.clone(),
+                        value: self.load_value(
+                            statement_id_set.value_prefix,
+                            statement_id_set.value_body.clone(),
+                        )?,
+                    },
+                    context: Entity(statement_id_set.context_id),
+                });
+                self.remove_statement_id_set(&statement_id_set)?;
+                self.remove_unused_context_metadata(&Entity(statement_id_set.context_id))?;
+            }
+        }
+        self.add_statement(&Statement {
+            entity: entity.clone(),
+            attribute: attribute.clone(),
+            value: value.clone(),
+        })?;
+        Ok(removed)
+    }
+
+    /// Allocates a context that Statements can be added to with add_statement_in_context.
+    pub fn new_context(&self) -> Result<Entity, LigatureError> {
+        self.allocate_entity(EntityKind::Context)
+    }
+
+    /// Adds a Statement in an existing context instead of a new one, so Statements can be grouped.
+    /// Adding the same Statement to a context twice only stores it once.
+    pub fn add_statement_in_context(
+        &self,
+        statement: &Statement,
+        context: &Entity,
+    ) -> Result<PersistedStatement, LigatureError> {
+        self.check_entity(&statement.entity)?;
+        if self.entity_kind(context)? != Some(EntityKind::Context) {
+            return Err(LigatureError(format!(
+                "Entity {} isn't a context.",
+                context.0
+            )));
+        }
+        Ok(self.insert_statement(statement, context.clone())?)
+    }
+
+    /// Allocates a new Entity.
+    pub fn new_entity(&self) -> Result<Entity, LigatureError> {
+        self.allocate_entity(EntityKind::Entity)
+    }
+
+    /// Adds a Statement in a new context.
+    pub fn add_statement(&self, statement: &Statement) -> Result<PersistedStatement, LigatureError> {
+        Ok(self.try_add_statement(statement)?)
+    }
+
+    /// Like add_statement, but a Statement that can't be added fails with the reason
+    /// instead of an error that looks like any other.
+    pub fn try_add_statement(
+        &self,
+        statement: &Statement,
+    ) -> Result<PersistedStatement, WriteError> {
+        if let Some(error) = self.invalid_entity(&statement.entity)? {
+            return Err(WriteError::InvalidEntity(error));
+        }
+        let context = self.allocate_entity(EntityKind::Context)?;
+        self.insert_statement(statement, context)
+    }
+
+    /// Removes every Statement matching the given Entity, Attribute and Value and returns how many were removed.
+    /// If a limit is given and more Statements than that match, nothing is removed and an error is returned.
+    pub fn remove_matching(
+        &self,
+        entity: Option<Entity>,
+        attribute: Option<Attribute>,
+        value: Option<Value>,
+        limit: Option<usize>,
+    ) -> Result<usize, LigatureError> {
+        let attribute_id = match attribute {
+            Some(attribute) => match self.lookup_attribute_id(&attribute)? {
+                Some(id) => Some(id),
+                None => return Ok(0),
             },
-            Err(e) => return Ok(false)
+            None => None,
+        };
+        let value = match value {
+            Some(value) => match self.lookup_value(&value)? {
+                Some(value) => Some(value),
+                None => return Ok(0),
+            },
+            None => None,
+        };
+        let prefix = keys::match_prefix(entity.map(|e| e.0), attribute_id, value.as_ref());
+        let mut keys = vec![];
+        for entry in self.store.scan_prefix(prefix) {
+            keys.push(entry?.0);
+            if let Some(limit) = limit {
+                if keys.len() > limit {
+                    return Err(LigatureError(format!(
+                        "More than {} Statements match, none were removed.",
+                        limit
+                    )));
+                }
+            }
+        }
+        for key in keys.iter() {
+            self.remove_statement_id_set(&decode_permutation(key)?)?;
         }
+        Ok(keys.len())
+    }
 
-        // if potential_match_encoded
-        //     != encoded_statement_permutations
-        //         .last()
-        //         .ok_or(LigatureError(format!(
-        //             "Error creating Statements permutations for {:?}",
-        //             persisted_statement
-        //         )))?
-        //         .clone()
-        // {
-        //     return Ok(false);
-        // }
-
-        //TODO clean up by checking if the attribute is used in any remaining statements by checking AEVC
-        // let attribute_prefix = prepend(
-        //     AEVC_PREFIX,
-        //     statement_id_set.attribute_id.to_be_bytes().to_vec(), //TODO should be encode_id
-        // );
-        // let attribute_lookup: Vec<Result<(sled::IVec, sled::IVec), sled::Error>> =
-        //     self.store.scan_prefix(attribute_prefix).collect();
-        // if attribute_lookup.len() == 0 { //if it isn't used in other Statements then delete the Attribute
-        //     let attribute_name_to_id_key = prepend(ATTRIBUTE_NAME_TO_ID_PREFIX, encode_attribute(&persisted_statement.statement.attribute));
-        //     let attribute_id_to_name_key = prepend(ATTRIBUTE_ID_TO_NAME_PREFIX, encode_id(statement_id_set.attribute_id));
-
-        //     self.store.remove(attribute_name_to_id_key); //TODO sanity check result
-        //     self.store.remove(attribute_id_to_name_key); //TODO sanity check result
-        // }
-
-        //TODO clean up by checking if the Value is a String Literal
-        //TODO if it is check if it is being used in any other Statements by checking VAEC
-        //TODO if it isn't then delete the String Literal
-        // if statement_id_set.value_prefix == STRING_VALUE_PREFIX {
-        //     let value_prefix = prepend(VEAC_PREFIX, statement_id_set.value_body.clone());
-        //     let value_lookup: Vec<Result<(sled::IVec, sled::IVec), sled::Error>> =
-        //         self.store.scan_prefix(value_prefix).collect();
-        //     if value_lookup.len() == 0 { //if it isn't used in other Statements then delete the Attribute
-        //         let string_liteal_value = match &persisted_statement.statement.value {
-        //             Value::StringLiteral(value) => {
-        //                 let string_literal_value_to_id_key = prepend(STRING_LITERAL_VALUE_TO_ID_PREFIX, encode_string_literal(&value));
-        //                 let string_literal_id_to_value_key = prepend(STRING_LITERAL_ID_TO_VALUE_PREFIX, statement_id_set.value_body);
-
-        //                 self.store.remove(string_literal_value_to_id_key); //TODO sanity check result
-        //                 self.store.remove(string_literal_id_to_value_key); //TODO sanity check result        
-        //             },
-        //             _ => {
-        //                 return Err(LigatureError("Excepted StringLiteral".to_string()));
-        //             }
-        //         };
-        //     }
-        // }
+    /// Removes a Statement and returns whether it existed.
+    /// Ids are only looked up, so removing a Statement that was never added doesn't write anything.
+    pub fn remove_statement(
+        &self,
+        persisted_statement: &PersistedStatement,
+    ) -> Result<bool, LigatureError> {
+        let statement = &persisted_statement.statement;
+        let attribute_id = match self.lookup_attribute_id(&statement.attribute)? {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+        let (value_prefix, value_body) = match self.lookup_value(&statement.value)? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        let statement_id_set = StatementIDSet {
+            entity_id: statement.entity.0,
+            attribute_id: attribute_id,
+            value_prefix: value_prefix,
+            value_body: value_body,
+            context_id: persisted_statement.context.0,
+        };
+        // the first permutation is EAVC, checking one permutation is enough
+        let exists = match encode_statement_permutations(&statement_id_set).first() {
+            Some(eavc) => self
+                .store
+                .get(eavc)
+                .map_err(|_| {
+                    LigatureError(format!("Error looking up Statement {:?}", persisted_statement))
+                })?
+                .is_some(),
+            None => false,
+        };
+        if exists {
+            self.remove_statement_id_set(&statement_id_set)?;
+        }
+        Ok(exists)
     }
 
-    fn cancel(&self) -> Result<(), LigatureError> {
+    /// Discards every write made in this transaction.
+    pub fn cancel(&self) -> Result<(), LigatureError> {
         self.active.set(false);
         Ok(())
     }
 }
+
+impl WriteTx for LigatureSledWriteTx {
+    fn new_entity(&self) -> Result<Entity, LigatureError> {
+        LigatureSledWriteTx::new_entity(self)
+    }
+
+    fn add_statement(
+        &self,
+        statement: &ligature::Statement,
+    ) -> Result<ligature::PersistedStatement, LigatureError> {
+        let persisted_statement =
+            LigatureSledWriteTx::add_statement(self, &statement.clone().into())?;
+        ligature::PersistedStatement::try_from(persisted_statement)
+    }
+
+    fn remove_statement(
+        &self,
+        persisted_statement: &ligature::PersistedStatement,
+    ) -> Result<bool, LigatureError> {
+        LigatureSledWriteTx::remove_statement(self, &persisted_statement.clone().into())
+    }
+
+    fn cancel(&self) -> Result<(), LigatureError> {
+        LigatureSledWriteTx::cancel(self)
+    }
+}
@@ -0,0 +1,424 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+#[cfg(test)]
+mod tests {
+    use ligature::{Attribute, Dataset, Ligature, LigatureError};
+    use ligature_kv::{
+        decode_id, encode_attribute, encode_dataset, encode_id, encode_statement_permutations,
+        encode_string_literal, prepend, StatementIDSet, ATTRIBUTE_ID_COUNTER_KEY,
+        ATTRIBUTE_ID_TO_NAME_PREFIX, ATTRIBUTE_NAME_TO_ID_PREFIX, DATASET_PREFIX,
+        ENTITY_ID_COUNTER_KEY, FLOAT_VALUE_PREFIX, INTEGER_VALUE_PREFIX,
+        STRING_LITERAL_ID_COUNTER_KEY, STRING_LITERAL_ID_TO_VALUE_PREFIX,
+        STRING_LITERAL_VALUE_TO_ID_PREFIX, STRING_VALUE_PREFIX,
+    };
+    use ligature_sled::{
+        Aggregate, AggregateResult, LigatureSled, PersistedStatement, Range, Statement, Value,
+        FORMAT_VERSION, FORMAT_VERSION_KEY,
+    };
+    use sha2::{Digest, Sha256};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn dataset(name: &str) -> Dataset {
+        Dataset::new(name).expect("")
+    }
+
+    /// A directory for a fixture store that is removed when dropped.
+    /// Every fixture gets its own directory so tests and concurrent test runs don't share stores.
+    struct FixturePath(PathBuf);
+
+    impl FixturePath {
+        fn new(name: &str) -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            let path = std::env::temp_dir().join(format!(
+                "ligature-sled-fixture-{}-{}-{}-{}",
+                name,
+                std::process::id(),
+                nanos,
+                NEXT.fetch_add(1, Ordering::SeqCst)
+            ));
+            FixturePath(path)
+        }
+
+        fn open(&self) -> Result<LigatureSled, sled::Error> {
+            LigatureSled::new(self.0.to_str().unwrap().to_string())
+        }
+
+        fn open_read_only(&self) -> Result<LigatureSled, sled::Error> {
+            LigatureSled::open_read_only(self.0.to_str().unwrap().to_string())
+        }
+
+        fn raw(&self) -> sled::Db {
+            sled::open(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for FixturePath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes a store in format version 1, before the format was versioned.
+    /// Datasets are stored in trees named after the Dataset and have an empty entry in the root tree.
+    /// test/test contains a single Statement, Entity 1 has the name "Juniper" in Context 2.
+    fn unversioned_fixture(path: &FixturePath) {
+        legacy_dataset(path, "test/test");
+    }
+
+    /// Writes a Dataset in format version 1 with the contents of test/test in unversioned_fixture.
+    fn legacy_dataset(path: &FixturePath, name: &str) {
+        let store = path.raw();
+        store
+            .insert(
+                prepend(DATASET_PREFIX, encode_dataset(&dataset(name))),
+                vec![],
+            )
+            .unwrap();
+        let tree = store.open_tree(name).unwrap();
+        tree.insert(vec![ENTITY_ID_COUNTER_KEY], encode_id(2)).unwrap();
+        tree.insert(vec![ATTRIBUTE_ID_COUNTER_KEY], encode_id(1)).unwrap();
+        tree.insert(vec![STRING_LITERAL_ID_COUNTER_KEY], encode_id(1)).unwrap();
+        let name = Attribute::new("name").unwrap();
+        tree.insert(
+            prepend(ATTRIBUTE_NAME_TO_ID_PREFIX, encode_attribute(&name)),
+            encode_id(1),
+        )
+        .unwrap();
+        tree.insert(
+            prepend(ATTRIBUTE_ID_TO_NAME_PREFIX, encode_id(1)),
+            encode_attribute(&name),
+        )
+        .unwrap();
+        let juniper = "Juniper".to_string();
+        tree.insert(
+            prepend(STRING_LITERAL_VALUE_TO_ID_PREFIX, encode_string_literal(&juniper)),
+            encode_id(1),
+        )
+        .unwrap();
+        tree.insert(
+            prepend(STRING_LITERAL_ID_TO_VALUE_PREFIX, encode_id(1)),
+            encode_string_literal(&juniper),
+        )
+        .unwrap();
+        let statement = StatementIDSet {
+            entity_id: 1,
+            attribute_id: 1,
+            value_prefix: STRING_VALUE_PREFIX,
+            value_body: encode_id(1),
+            context_id: 2,
+        };
+        for permutation in encode_statement_permutations(&statement) {
+            tree.insert(permutation, vec![]).unwrap();
+        }
+        store.flush().unwrap();
+    }
+
+    fn raw_format_version(path: &FixturePath) -> Option<u64> {
+        path.raw()
+            .get(vec![FORMAT_VERSION_KEY])
+            .unwrap()
+            .map(|version| decode_id(version.to_vec()).unwrap())
+    }
+
+    fn all_statements(
+        instance: &LigatureSled,
+        dataset: &Dataset,
+    ) -> Result<Vec<PersistedStatement>, LigatureError> {
+        instance.query_sled(dataset, |tx| tx.all_statements().collect())
+    }
+
+    #[test]
+    fn new_stores_use_the_current_format() -> Result<(), LigatureError> {
+        let path = FixturePath::new("new");
+        {
+            let instance = path.open().unwrap();
+            instance.create_dataset(&dataset("test/test"))?;
+        }
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION));
+        Ok(())
+    }
+
+    #[test]
+    fn unversioned_stores_are_migrated() -> Result<(), LigatureError> {
+        let path = FixturePath::new("unversioned");
+        unversioned_fixture(&path);
+        {
+            let instance = path.open().unwrap();
+            let statements = all_statements(&instance, &dataset("test/test"))?;
+            assert_eq!(
+                statements,
+                vec![PersistedStatement {
+                    statement: Statement {
+                        entity: ligature::Entity(1),
+                        attribute: Attribute::new("name")?,
+                        value: Value::StringLiteral("Juniper".to_string()),
+                    },
+                    context: ligature::Entity(2),
+                }]
+            );
+            // Entities were registered, the context of the Statement isn't an Entity
+            let exists = instance.query_sled(&dataset("test/test"), |tx| {
+                Ok((
+                    tx.entity_exists(&ligature::Entity(1))?,
+                    tx.entity_exists(&ligature::Entity(2))?,
+                ))
+            })?;
+            assert_eq!(exists, (true, false));
+            // the counts were recorded
+            let stats = instance.dataset_stats(&dataset("test/test"))?;
+            assert_eq!((stats.statement_count, stats.entity_count), (1, 1));
+            assert_eq!(
+                stats.attribute_statement_counts,
+                vec![(Attribute::new("name")?, 1)]
+            );
+            // counters were migrated with the rest of the Dataset
+            let entity = instance.write_sled(&dataset("test/test"), |tx| tx.new_entity())?;
+            assert_eq!(entity.0, 3);
+            // the Bytes Literal counter was added
+            instance.write_sled(&dataset("test/test"), |tx| {
+                tx.add_statement(&Statement {
+                    entity: ligature::Entity(1),
+                    attribute: Attribute::new("hash")?,
+                    value: Value::BytesLiteral(vec![0xca, 0xfe]),
+                })
+            })?;
+        }
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION));
+        assert!(!path.raw().tree_names().contains(&sled::IVec::from("test/test")));
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_migrations_resume() -> Result<(), LigatureError> {
+        let path = FixturePath::new("interrupted");
+        unversioned_fixture(&path);
+        {
+            // a migration that stopped part of the way through copying the Dataset's tree
+            let store = path.raw();
+            store.insert(vec![1], encode_id(0)).unwrap();
+            let partial = store.open_tree("__ligature_sled__dataset_1").unwrap();
+            partial.insert(vec![ENTITY_ID_COUNTER_KEY], encode_id(0)).unwrap();
+            store.flush().unwrap();
+        }
+        {
+            let instance = path.open().unwrap();
+            assert_eq!(all_statements(&instance, &dataset("test/test"))?.len(), 1);
+        }
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION));
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_trees_named_like_dataset_trees_are_migrated() -> Result<(), LigatureError> {
+        let path = FixturePath::new("legacy-names");
+        legacy_dataset(&path, "__ligature_sled__dataset_1");
+        legacy_dataset(&path, "__ligature_sled__dataset_3");
+        legacy_dataset(&path, "test/test");
+        {
+            let instance = path.open().unwrap();
+            for name in &[
+                "__ligature_sled__dataset_1",
+                "__ligature_sled__dataset_3",
+                "test/test",
+            ] {
+                assert_eq!(all_statements(&instance, &dataset(name))?.len(), 1);
+            }
+        }
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION));
+        // the root tree and the tree of each Dataset
+        assert_eq!(path.raw().tree_names().len(), 4);
+        Ok(())
+    }
+
+    fn numbers(instance: &LigatureSled) -> Result<(Vec<Value>, Vec<Value>), LigatureError> {
+        instance.query_sled(&dataset("test/test"), |tx| {
+            let read = |range: Range| -> Result<Vec<Value>, LigatureError> {
+                tx.match_statements_range(None, None, range)
+                    .map(|statement| statement.map(|statement| statement.statement.value))
+                    .collect()
+            };
+            Ok((
+                read(Range::IntegerLiteralRange {
+                    start: -10,
+                    end: 10,
+                })?,
+                read(Range::FloatLiteralRange {
+                    start: -10.0,
+                    end: 10.0,
+                })?,
+            ))
+        })
+    }
+
+    #[test]
+    fn numbers_are_encoded_in_order() -> Result<(), LigatureError> {
+        let path = FixturePath::new("numbers");
+        unversioned_fixture(&path);
+        {
+            // Integers and Floats as they were stored before format version 10
+            let store = path.raw();
+            let tree = store.open_tree("test/test").unwrap();
+            tree.insert(vec![ENTITY_ID_COUNTER_KEY], encode_id(6))
+                .unwrap();
+            let values = vec![
+                (INTEGER_VALUE_PREFIX, 3i64.to_be_bytes()),
+                (INTEGER_VALUE_PREFIX, (-5i64).to_be_bytes()),
+                (FLOAT_VALUE_PREFIX, 2.5f64.to_be_bytes()),
+                (FLOAT_VALUE_PREFIX, (-7.5f64).to_be_bytes()),
+            ];
+            for (context_id, (value_prefix, value_body)) in (3..).zip(values) {
+                let statement = StatementIDSet {
+                    entity_id: 1,
+                    attribute_id: 1,
+                    value_prefix: value_prefix,
+                    value_body: value_body.to_vec(),
+                    context_id: context_id,
+                };
+                for permutation in encode_statement_permutations(&statement) {
+                    tree.insert(permutation, vec![]).unwrap();
+                }
+            }
+            store.flush().unwrap();
+        }
+        let expected = (
+            vec![Value::IntegerLiteral(-5), Value::IntegerLiteral(3)],
+            vec![Value::FloatLiteral(-7.5), Value::FloatLiteral(2.5)],
+        );
+        {
+            let instance = path.open().unwrap();
+            assert_eq!(numbers(&instance)?, expected);
+            let (min, max) = instance.query_sled(&dataset("test/test"), |tx| {
+                Ok((
+                    tx.aggregate(None, None, None, Aggregate::Min)?,
+                    tx.aggregate(None, None, None, Aggregate::Max)?,
+                ))
+            })?;
+            assert_eq!(min, AggregateResult::Min(Some(Value::FloatLiteral(-7.5))));
+            assert_eq!(max, AggregateResult::Max(Some(Value::IntegerLiteral(3))));
+        }
+        {
+            // a migration that stopped after re-encoding the Dataset but before recording version 10
+            // markers the version 10 migration writes in the root tree start with 3
+            let store = path.raw();
+            assert_eq!(store.scan_prefix(vec![3]).count(), 0);
+            store
+                .insert(vec![FORMAT_VERSION_KEY], encode_id(9))
+                .unwrap();
+            store.insert(prepend(3, encode_id(1)), vec![]).unwrap();
+            store.flush().unwrap();
+        }
+        {
+            let instance = path.open().unwrap();
+            assert_eq!(numbers(&instance)?, expected);
+        }
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION));
+        assert_eq!(path.raw().scan_prefix(vec![3]).count(), 0);
+        Ok(())
+    }
+
+    /// Writes a large String Literal as it was stored before format version 13,
+    /// keyed by the start of the string followed by its hash.
+    fn old_large_literal(tree: &sled::Tree, id: u64, value: &str) {
+        let encoded = encode_string_literal(value);
+        let hash = Sha256::digest(&encoded).to_vec();
+        let mut key = prepend(STRING_LITERAL_VALUE_TO_ID_PREFIX, encoded[..1024].to_vec());
+        key.extend(hash.clone());
+        tree.insert(key, encode_id(id)).unwrap();
+        let mut reference = hash.clone();
+        reference.extend(encode_id(encoded.len() as u64));
+        tree.insert(prepend(21, encode_id(id)), reference).unwrap();
+        for (index, chunk) in encoded.chunks(64 * 1024).enumerate() {
+            let mut chunk_key = prepend(22, hash.clone());
+            chunk_key.extend((index as u32).to_be_bytes().to_vec());
+            tree.insert(chunk_key, chunk.to_vec()).unwrap();
+        }
+    }
+
+    #[test]
+    fn large_literals_are_keyed_by_hash() -> Result<(), LigatureError> {
+        let path = FixturePath::new("large-literals");
+        unversioned_fixture(&path);
+        let used: String = (0..100_000)
+            .map(|i| ((b'a' + (i % 26) as u8) as char))
+            .collect();
+        let mut unused = used.clone();
+        unused.push('!');
+        {
+            // String Literal 2 is used by a Statement, 3 was left behind by a removed one
+            let store = path.raw();
+            let tree = store.open_tree("test/test").unwrap();
+            tree.insert(vec![ENTITY_ID_COUNTER_KEY], encode_id(3))
+                .unwrap();
+            tree.insert(vec![STRING_LITERAL_ID_COUNTER_KEY], encode_id(3))
+                .unwrap();
+            old_large_literal(&tree, 2, &used);
+            old_large_literal(&tree, 3, &unused);
+            let statement = StatementIDSet {
+                entity_id: 1,
+                attribute_id: 1,
+                value_prefix: STRING_VALUE_PREFIX,
+                value_body: encode_id(2),
+                context_id: 3,
+            };
+            for permutation in encode_statement_permutations(&statement) {
+                tree.insert(permutation, vec![]).unwrap();
+            }
+            store.flush().unwrap();
+        }
+        {
+            let instance = path.open().unwrap();
+            let expected = used.clone();
+            let (matched, stats) = instance.query_sled(&dataset("test/test"), move |tx| {
+                let matched: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, Some(Value::StringLiteral(expected.clone())))
+                    .collect::<Result<_, _>>()?;
+                Ok((matched, tx.stats()?))
+            })?;
+            assert_eq!(matched.len(), 1);
+            assert_eq!(matched[0].context, ligature::Entity(3));
+            assert_eq!(stats.string_literal_count, 2);
+        }
+        // only the chunks of the used String Literal are left
+        let store = path.raw();
+        let chunks: usize = store
+            .tree_names()
+            .into_iter()
+            .map(|name| store.open_tree(name).unwrap().scan_prefix(vec![22]).count())
+            .sum();
+        assert_eq!(chunks, 2);
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION));
+        Ok(())
+    }
+
+    #[test]
+    fn newer_formats_are_refused() {
+        let path = FixturePath::new("newer");
+        {
+            let store = path.raw();
+            store
+                .insert(vec![FORMAT_VERSION_KEY], encode_id(FORMAT_VERSION + 1))
+                .unwrap();
+            store.flush().unwrap();
+        }
+        assert!(path.open().is_err());
+        assert!(path.open_read_only().is_err());
+        assert_eq!(raw_format_version(&path), Some(FORMAT_VERSION + 1));
+    }
+
+    #[test]
+    fn read_only_opens_dont_migrate() {
+        let path = FixturePath::new("read-only");
+        unversioned_fixture(&path);
+        assert!(path.open_read_only().is_err());
+        assert_eq!(raw_format_version(&path), None);
+        assert!(path.raw().tree_names().contains(&sled::IVec::from("test/test")));
+    }
+}
@@ -0,0 +1,232 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+#![cfg(feature = "server")]
+
+#[cfg(test)]
+mod tests {
+    use ligature::LigatureError;
+    use ligature_sled::server::LigatureServer;
+    use ligature_sled::LigatureSled;
+    use serde_json::{json, Value as Json};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+
+    struct TestServer {
+        server: Arc<LigatureServer>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl TestServer {
+        fn start() -> Result<Self, LigatureError> {
+            let instance = LigatureSled::temp(None)
+                .map_err(|_| LigatureError("Could not open temp store.".to_string()))?;
+            let server = Arc::new(LigatureServer::new(instance, "127.0.0.1:0")?);
+            let running = server.clone();
+            let handle = std::thread::spawn(move || running.run());
+            Ok(Self {
+                server,
+                handle: Some(handle),
+            })
+        }
+
+        fn address(&self) -> SocketAddr {
+            self.server.address()
+        }
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            self.server.unblock();
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    /// Sends an HTTP/1.0 request so the response is terminated by closing the connection.
+    fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Vec<Json>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body_start = response.find("\r\n\r\n").unwrap() + 4;
+        let lines = response[body_start..]
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (status, lines)
+    }
+
+    #[test]
+    fn create_list_and_delete_datasets() -> Result<(), LigatureError> {
+        let server = TestServer::start()?;
+        let address = server.address();
+        assert_eq!(request(address, "POST", "/datasets/test%2Ftest", "").0, 201);
+        assert_eq!(request(address, "POST", "/datasets/test%2Ftest2", "").0, 201);
+        assert_eq!(request(address, "POST", "/datasets/other", "").0, 201);
+        let (status, datasets) = request(address, "GET", "/datasets", "");
+        assert_eq!(status, 200);
+        assert_eq!(datasets.len(), 3);
+        let (_, datasets) = request(address, "GET", "/datasets?prefix=test%2F", "");
+        assert_eq!(
+            datasets,
+            vec![
+                json!({ "dataset": "test/test" }),
+                json!({ "dataset": "test/test2" })
+            ]
+        );
+        assert_eq!(request(address, "DELETE", "/datasets/other", "").0, 204);
+        let (_, datasets) = request(address, "GET", "/datasets?from=a&to=z", "");
+        assert_eq!(datasets.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_datasets_are_not_found() -> Result<(), LigatureError> {
+        let server = TestServer::start()?;
+        let address = server.address();
+        assert_eq!(request(address, "GET", "/datasets/missing/statements", "").0, 404);
+        assert_eq!(request(address, "POST", "/datasets/missing/statements", "[]").0, 404);
+        assert_eq!(request(address, "DELETE", "/datasets/missing", "").0, 404);
+        Ok(())
+    }
+
+    #[test]
+    fn batched_writes_and_queries() -> Result<(), LigatureError> {
+        let server = TestServer::start()?;
+        let address = server.address();
+        request(address, "POST", "/datasets/test", "");
+        let batch = json!([
+            { "op": "new_entity" },
+            { "op": "new_entity" },
+            { "op": "add_statement", "entity": { "new": 0 }, "attribute": "name", "value": { "string": "Juniper" } },
+            { "op": "add_statement", "entity": { "new": 0 }, "attribute": "friend", "value": { "entity": { "new": 1 } } },
+            { "op": "add_statement", "entity": { "new": 1 }, "attribute": "age", "value": { "integer": 7 } }
+        ]);
+        let (status, results) =
+            request(address, "POST", "/datasets/test/statements", &batch.to_string());
+        assert_eq!(status, 200);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], json!({ "entity": 1 }));
+        assert_eq!(results[1], json!({ "entity": 2 }));
+
+        let (status, statements) = request(address, "GET", "/datasets/test/statements", "");
+        assert_eq!(status, 200);
+        assert_eq!(statements.len(), 3);
+        let (_, statements) = request(address, "GET", "/datasets/test/statements?entity=1", "");
+        assert_eq!(statements.len(), 2);
+        let (_, statements) = request(
+            address,
+            "GET",
+            "/datasets/test/statements?value=%7B%22string%22%3A%22Juniper%22%7D",
+            "",
+        );
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0]["attribute"], json!("name"));
+
+        let (_, all) = request(address, "GET", "/datasets/test/statements", "");
+        let (status, page) = request(address, "GET", "/datasets/test/statements?limit=2", "");
+        assert_eq!(status, 200);
+        assert_eq!(page[..2], all[..2]);
+        let next = page[2]["next"].as_str().unwrap().to_string();
+        let (_, page) = request(
+            address,
+            "GET",
+            &format!("/datasets/test/statements?limit=2&cursor={}", next),
+            "",
+        );
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0], all[2]);
+        let (status, _) = request(address, "GET", "/datasets/test/statements?cursor=zz", "");
+        assert_eq!(status, 400);
+        let (status, _) = request(address, "GET", "/datasets/test/statements?limit=0", "");
+        assert_eq!(status, 400);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_batches_are_atomic() -> Result<(), LigatureError> {
+        let server = TestServer::start()?;
+        let address = server.address();
+        request(address, "POST", "/datasets/test", "");
+        let batch = json!([
+            { "op": "new_entity" },
+            { "op": "add_statement", "entity": { "new": 0 }, "attribute": "name", "value": { "string": "Juniper" } },
+            { "op": "add_statement", "entity": 42, "attribute": "name", "value": { "string": "Clarice" } }
+        ]);
+        let (status, _) = request(address, "POST", "/datasets/test/statements", &batch.to_string());
+        assert_eq!(status, 400);
+        let (_, statements) = request(address, "GET", "/datasets/test/statements", "");
+        assert!(statements.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn removing_missing_statements() -> Result<(), LigatureError> {
+        let server = TestServer::start()?;
+        let address = server.address();
+        request(address, "POST", "/datasets/test", "");
+        let batch = json!([
+            { "op": "new_entity" },
+            { "op": "add_statement", "entity": { "new": 0 }, "attribute": "name", "value": { "string": "Juniper" } }
+        ]);
+        let (_, results) = request(address, "POST", "/datasets/test/statements", &batch.to_string());
+        let context = results[1]["context"].clone();
+        let batch = json!([
+            { "op": "remove_statement", "entity": 1, "attribute": "name", "value": { "string": "Clarice" }, "context": context },
+            { "op": "remove_statement", "entity": 42, "attribute": "name", "value": { "string": "Juniper" }, "context": context },
+            { "op": "remove_statement", "entity": 1, "attribute": "name", "value": { "string": "Juniper" }, "context": context },
+            { "op": "remove_statement", "entity": 1, "attribute": "name", "value": { "string": "Juniper" }, "context": context }
+        ]);
+        let (status, results) =
+            request(address, "POST", "/datasets/test/statements", &batch.to_string());
+        assert_eq!(status, 200);
+        assert_eq!(
+            results,
+            vec![
+                json!({ "removed": false }),
+                json!({ "removed": false }),
+                json!({ "removed": true }),
+                json!({ "removed": false })
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sparql_queries() -> Result<(), LigatureError> {
+        let server = TestServer::start()?;
+        let address = server.address();
+        request(address, "POST", "/datasets/test", "");
+        let batch = json!([
+            { "op": "new_entity" },
+            { "op": "add_statement", "entity": { "new": 0 }, "attribute": "name", "value": { "string": "Juniper" } },
+            { "op": "add_statement", "entity": { "new": 0 }, "attribute": "age", "value": { "integer": 7 } }
+        ]);
+        request(address, "POST", "/datasets/test/statements", &batch.to_string());
+        let (status, solutions) = request(
+            address,
+            "POST",
+            "/datasets/test/sparql",
+            "SELECT ?name WHERE { ?e <name> ?name ; <age> ?age FILTER(?age > 5) }",
+        );
+        assert_eq!(status, 200);
+        assert_eq!(solutions, vec![json!({ "name": { "string": "Juniper" } })]);
+        let (status, _) = request(address, "POST", "/datasets/test/sparql", "SELECT");
+        assert_eq!(status, 400);
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+#[cfg(test)]
+mod tests {
+    use ligature::{Attribute, Dataset, Ligature, LigatureError};
+    use ligature_sled::{Binding, LigatureSled, SparqlQuery, SparqlResults, Statement, Value};
+
+    /// Creates a temporary store with three people where carol has no age.
+    fn people() -> Result<(LigatureSled, Dataset), LigatureError> {
+        let instance = LigatureSled::temp(None)?;
+        let dataset = Dataset::new("test/sparql")?;
+        instance.create_dataset(&dataset)?;
+        instance.write_sled(&dataset, |tx| {
+            let alice = tx.named_entity("alice")?;
+            let bob = tx.named_entity("bob")?;
+            let carol = tx.named_entity("carol")?;
+            let people = vec![
+                (&alice, "Alice", Some(34)),
+                (&bob, "Bob", Some(17)),
+                (&carol, "Carol", None),
+            ];
+            for (person, name, age) in people {
+                tx.add_statement(&Statement {
+                    entity: person.clone(),
+                    attribute: Attribute::new("name")?,
+                    value: Value::StringLiteral(name.to_string()),
+                })?;
+                if let Some(age) = age {
+                    tx.add_statement(&Statement {
+                        entity: person.clone(),
+                        attribute: Attribute::new("age")?,
+                        value: Value::IntegerLiteral(age),
+                    })?;
+                }
+            }
+            for (from, to) in vec![(&alice, &bob), (&alice, &carol), (&bob, &carol)] {
+                tx.add_statement(&Statement {
+                    entity: from.clone(),
+                    attribute: Attribute::new("knows")?,
+                    value: Value::Entity(to.clone()),
+                })?;
+            }
+            Ok(())
+        })?;
+        Ok((instance, dataset))
+    }
+
+    fn select(query: &str) -> Result<SparqlResults, LigatureError> {
+        let (instance, dataset) = people()?;
+        let query = SparqlQuery::parse(query)?;
+        instance.query_sled(&dataset, |tx| tx.evaluate_sparql(&query))
+    }
+
+    fn column(results: &SparqlResults, variable: &str) -> Vec<Option<Value>> {
+        results
+            .solutions
+            .iter()
+            .map(|solution| match solution.get(variable) {
+                Some(Binding::Value(value)) => Some(value.clone()),
+                Some(Binding::Attribute(attribute)) => {
+                    Some(Value::StringLiteral(attribute.name().to_string()))
+                }
+                None => None,
+            })
+            .collect()
+    }
+
+    fn string(value: &str) -> Option<Value> {
+        Some(Value::StringLiteral(value.to_string()))
+    }
+
+    #[test]
+    fn select_basic_graph_patterns() -> Result<(), LigatureError> {
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { :alice :knows ?friend . ?friend :name ?name } ORDER BY ?name",
+        )?;
+        assert_eq!(results.variables, vec!["name".to_string()]);
+        assert_eq!(
+            column(&results, "name"),
+            vec![string("Bob"), string("Carol")]
+        );
+        let results = select(
+            "SELECT * { ?person <name> ?name ; <knows> ?a , ?b . ?a <name> \"Bob\" . ?b <name> 'Carol' }",
+        )?;
+        assert_eq!(
+            results.variables,
+            vec!["person", "name", "a", "b"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<String>>()
+        );
+        assert_eq!(column(&results, "name"), vec![string("Alice")]);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_iris_match_nothing() -> Result<(), LigatureError> {
+        let results = select("PREFIX : <> SELECT ?name WHERE { :dave :name ?name }")?;
+        assert!(results.solutions.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn filter_numbers_and_strings() -> Result<(), LigatureError> {
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { ?p :name ?name ; :age ?age FILTER(?age >= 18) }",
+        )?;
+        assert_eq!(column(&results, "name"), vec![string("Alice")]);
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { ?p :age ?age ; :name ?name FILTER(?age<20.5 && ?name != \"Alice\") }",
+        )?;
+        assert_eq!(column(&results, "name"), vec![string("Bob")]);
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { ?p :name ?name FILTER(?name > \"Alice\" && !(?p = :carol)) }",
+        )?;
+        assert_eq!(column(&results, "name"), vec![string("Bob")]);
+        Ok(())
+    }
+
+    #[test]
+    fn optional_groups() -> Result<(), LigatureError> {
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name ?age WHERE {
+               ?p :name ?name .
+               OPTIONAL { ?p :age ?age FILTER(?age > 20) }
+             } ORDER BY ?name",
+        )?;
+        assert_eq!(
+            column(&results, "age"),
+            vec![Some(Value::IntegerLiteral(34)), None, None]
+        );
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { ?p :name ?name OPTIONAL { ?p :age ?age } FILTER(!BOUND(?age)) }",
+        )?;
+        assert_eq!(column(&results, "name"), vec![string("Carol")]);
+        Ok(())
+    }
+
+    #[test]
+    fn order_limit_and_offset() -> Result<(), LigatureError> {
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { ?p :name ?name OPTIONAL { ?p :age ?age } } ORDER BY DESC(?age)",
+        )?;
+        assert_eq!(
+            column(&results, "name"),
+            vec![string("Alice"), string("Bob"), string("Carol")]
+        );
+        let results = select(
+            "PREFIX : <>
+             SELECT ?name WHERE { ?p :name ?name } ORDER BY ASC(?name) LIMIT 1 OFFSET 1",
+        )?;
+        assert_eq!(column(&results, "name"), vec![string("Bob")]);
+        Ok(())
+    }
+
+    #[test]
+    fn order_entities_by_id() -> Result<(), LigatureError> {
+        let instance = LigatureSled::temp(None)?;
+        let dataset = Dataset::new("test/sparql")?;
+        instance.create_dataset(&dataset)?;
+        // every Statement gets a context from the same counter so the Entities reach two digit ids
+        instance.write_sled(&dataset, |tx| {
+            for i in 0..8 {
+                let entity = tx.new_entity()?;
+                tx.add_statement(&Statement {
+                    entity: entity,
+                    attribute: Attribute::new("n")?,
+                    value: Value::IntegerLiteral(i),
+                })?;
+            }
+            Ok(())
+        })?;
+        let query = SparqlQuery::parse("SELECT ?n WHERE { ?e <n> ?n } ORDER BY DESC(?e)")?;
+        let results = instance.query_sled(&dataset, |tx| tx.evaluate_sparql(&query))?;
+        assert_eq!(
+            column(&results, "n"),
+            (0..8)
+                .rev()
+                .map(|i| Some(Value::IntegerLiteral(i)))
+                .collect::<Vec<Option<Value>>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn count_solutions() -> Result<(), LigatureError> {
+        let results = select("PREFIX : <> SELECT (COUNT(*) AS ?n) WHERE { ?a :knows ?b }")?;
+        assert_eq!(results.variables, vec!["n".to_string()]);
+        assert_eq!(column(&results, "n"), vec![Some(Value::IntegerLiteral(3))]);
+        let results =
+            select("PREFIX : <> SELECT (COUNT(DISTINCT ?a) AS ?n) WHERE { ?a :knows ?b }")?;
+        assert_eq!(column(&results, "n"), vec![Some(Value::IntegerLiteral(2))]);
+        let results = select(
+            "PREFIX : <> SELECT (COUNT(?age) AS ?n) { ?p :name ?name OPTIONAL { ?p :age ?age } }",
+        )?;
+        assert_eq!(column(&results, "n"), vec![Some(Value::IntegerLiteral(2))]);
+        let results = select("PREFIX : <> SELECT DISTINCT ?a WHERE { ?a :knows ?b }")?;
+        assert_eq!(results.solutions.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_queries_are_errors() {
+        assert!(SparqlQuery::parse("SELECT ?a").is_err());
+        assert!(SparqlQuery::parse("SELECT ?a WHERE { ex:a ?b ?c }").is_err());
+        assert!(SparqlQuery::parse("SELECT ?a WHERE { ?a ?b ?c } LIMIT").is_err());
+        assert!(SparqlQuery::parse("SELECT ?a WHERE { ?a ?b ?c FILTER(?a <) }").is_err());
+        assert!(SparqlQuery::parse("SELECT ?a WHERE { ?a <not an attribute> ?c }").is_err());
+    }
+}
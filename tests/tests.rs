@@ -4,10 +4,16 @@
 
 #[cfg(test)]
 mod tests {
-    use ligature::{
-        Attribute, Dataset, Ligature, LigatureError, PersistedStatement, Statement, Value,
+    use ligature::{Attribute, Dataset, Entity, Ligature, LigatureError};
+    use ligature_sled::{
+        Aggregate, AggregateResult, Atom, AttributeSchema, Binding, Bindings, Cardinality,
+        Cursor, DatasetStats, Direction, Edge, EvaluationLimits, GroupBy, LigatureSled, Page,
+        PageRequest, PersistedStatement, Program, Range, ReferencePolicy, SchemaViolation,
+        Statement, StatementPattern, Term, Traversal, Value, ValueType, WriteError,
+        READ_ONLY_ERROR,
     };
-    use ligature_sled::LigatureSled;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     fn dataset(name: &str) -> Dataset {
         Dataset::new(name).expect("")
@@ -17,6 +23,22 @@ mod tests {
         LigatureSled::temp(None).unwrap()
     }
 
+    /// A path in the temp directory that no other test or test run uses.
+    fn unique_temp_path(name: &str) -> std::path::PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "ligature-sled-{}-{}-{}-{}",
+            name,
+            std::process::id(),
+            nanos,
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ))
+    }
+
     #[test]
     fn create_and_close_store() {
         let instance = instance();
@@ -118,7 +140,7 @@ mod tests {
         let test_dataset = dataset("test/test");
         instance.create_dataset(&test_dataset)?;
         let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
         assert!(res.is_empty());
         Ok(())
     }
@@ -128,14 +150,11 @@ mod tests {
         let instance = instance();
         let test_dataset = dataset("test/test");
         instance.create_dataset(&test_dataset)?;
-        let (entity1, entity2) = instance.write(
-            &test_dataset,
-            Box::new(|tx| {
-                let entity1 = tx.new_entity()?;
-                let entity2 = tx.new_entity()?;
-                Ok((entity1, entity2))
-            }),
-        )?;
+        let (entity1, entity2) = instance.write_sled(&test_dataset, |tx| {
+            let entity1 = tx.new_entity()?;
+            let entity2 = tx.new_entity()?;
+            Ok((entity1, entity2))
+        })?;
         assert_eq!(entity1.0, 1);
         assert_eq!(entity2.0, 2);
         assert!(entity1 != entity2);
@@ -147,51 +166,48 @@ mod tests {
         let instance = instance();
         let test_dataset = dataset("test/test");
         instance.create_dataset(&test_dataset)?;
-        instance.write(
-            &test_dataset,
-            Box::new(|tx| {
-                let entity = tx.new_entity()?;
-                let attribute = Attribute::new("name")?;
-                let value = Value::StringLiteral("Juniper".to_string());
-                let string_statement = Statement {
-                    entity: entity.clone(),
-                    attribute: attribute.clone(),
-                    value: value.clone(),
-                };
-
-                let entity2 = tx.new_entity()?;
-                let attribute2 = Attribute::new("connection")?;
-                let entity3 = tx.new_entity()?;
-                let entity_statement = Statement {
-                    entity: entity2.clone(),
-                    attribute: attribute2.clone(),
-                    value: Value::Entity(entity3.clone()),
-                };
-
-                let integer = Value::IntegerLiteral(4200);
-                let integer_statement = Statement {
-                    entity: entity2.clone(),
-                    attribute: attribute2.clone(),
-                    value: integer.clone(),
-                };
-
-                let float = Value::FloatLiteral(42.2);
-                let float_statement = Statement {
-                    entity: entity3.clone(),
-                    attribute: attribute2.clone(),
-                    value: float.clone(),
-                };
-
-                tx.add_statement(&string_statement)?;
-                tx.add_statement(&entity_statement)?;
-                tx.add_statement(&integer_statement)?;
-                tx.add_statement(&float_statement)?;
+        instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let attribute = Attribute::new("name")?;
+            let value = Value::StringLiteral("Juniper".to_string());
+            let string_statement = Statement {
+                entity: entity.clone(),
+                attribute: attribute.clone(),
+                value: value.clone(),
+            };
 
-                Ok(())
-            }),
-        )?;
+            let entity2 = tx.new_entity()?;
+            let attribute2 = Attribute::new("connection")?;
+            let entity3 = tx.new_entity()?;
+            let entity_statement = Statement {
+                entity: entity2.clone(),
+                attribute: attribute2.clone(),
+                value: Value::Entity(entity3.clone()),
+            };
+
+            let integer = Value::IntegerLiteral(4200);
+            let integer_statement = Statement {
+                entity: entity2.clone(),
+                attribute: attribute2.clone(),
+                value: integer.clone(),
+            };
+
+            let float = Value::FloatLiteral(42.2);
+            let float_statement = Statement {
+                entity: entity3.clone(),
+                attribute: attribute2.clone(),
+                value: float.clone(),
+            };
+
+            tx.add_statement(&string_statement)?;
+            tx.add_statement(&entity_statement)?;
+            tx.add_statement(&integer_statement)?;
+            tx.add_statement(&float_statement)?;
+
+            Ok(())
+        })?;
         let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
         assert_eq!(res.len(), 4); //TODO check instance not just number
                                   //TODO check context on persisted statements
         Ok(())
@@ -202,230 +218,2240 @@ mod tests {
         let instance = instance();
         let test_dataset = dataset("test/test");
         instance.create_dataset(&test_dataset)?;
-        let (ps1, ps2, ps3, ps4) = instance.write(
-            &test_dataset,
-            Box::new(|tx| {
-                let entity = tx.new_entity()?;
-                let attribute = Attribute::new("name")?;
-                let value = Value::StringLiteral("Juniper".to_string());
-                let string_statement = Statement {
-                    entity: entity.clone(),
-                    attribute: attribute.clone(),
-                    value: value.clone(),
-                };
-
-                let entity2 = tx.new_entity()?;
-                let attribute2 = Attribute::new("connection")?;
-                let entity3 = tx.new_entity()?;
-                let entity_statement = Statement {
-                    entity: entity2.clone(),
-                    attribute: attribute2.clone(),
-                    value: Value::Entity(entity3.clone()),
-                };
-
-                let integer = Value::IntegerLiteral(4200);
-                let integer_statement = Statement {
-                    entity: entity2.clone(),
-                    attribute: attribute2.clone(),
-                    value: integer.clone(),
-                };
-
-                let float = Value::FloatLiteral(42.2);
-                let float_statement = Statement {
-                    entity: entity3.clone(),
-                    attribute: attribute2.clone(),
-                    value: float.clone(),
-                };
-
-                let s1 = tx.add_statement(&string_statement)?;
-                let s2 = tx.add_statement(&entity_statement)?;
-                let s3 = tx.add_statement(&integer_statement)?;
-                let s4 = tx.add_statement(&float_statement)?;
-
-                tx.remove_statement(&s2);
-
-                Ok((s1, s2, s3, s4))
-            }),
-        )?;
+        let (ps1, ps2, ps3, ps4) = instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let attribute = Attribute::new("name")?;
+            let value = Value::StringLiteral("Juniper".to_string());
+            let string_statement = Statement {
+                entity: entity.clone(),
+                attribute: attribute.clone(),
+                value: value.clone(),
+            };
+
+            let entity2 = tx.new_entity()?;
+            let attribute2 = Attribute::new("connection")?;
+            let entity3 = tx.new_entity()?;
+            let entity_statement = Statement {
+                entity: entity2.clone(),
+                attribute: attribute2.clone(),
+                value: Value::Entity(entity3.clone()),
+            };
+
+            let integer = Value::IntegerLiteral(4200);
+            let integer_statement = Statement {
+                entity: entity2.clone(),
+                attribute: attribute2.clone(),
+                value: integer.clone(),
+            };
+
+            let float = Value::FloatLiteral(42.2);
+            let float_statement = Statement {
+                entity: entity3.clone(),
+                attribute: attribute2.clone(),
+                value: float.clone(),
+            };
+
+            let s1 = tx.add_statement(&string_statement)?;
+            let s2 = tx.add_statement(&entity_statement)?;
+            let s3 = tx.add_statement(&integer_statement)?;
+            let s4 = tx.add_statement(&float_statement)?;
+
+            tx.remove_statement(&s2);
+
+            Ok((s1, s2, s3, s4))
+        })?;
         let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
         assert_eq!(res.len(), 3); //TODO check instance not just number
                                   //TODO check context on persisted statements
 
         let ps1c = ps1.clone();
         let ps3c = ps3.clone();
-        instance.write(
-            &test_dataset,
-            Box::new(move |tx| {
-                tx.remove_statement(&ps1c);
-                tx.remove_statement(&ps3c);
-                Ok(())
-            }),
-        )?;
+        instance.write_sled(&test_dataset, move |tx| {
+            tx.remove_statement(&ps1c);
+            tx.remove_statement(&ps3c);
+            Ok(())
+        })?;
         let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
         assert_eq!(res.len(), 1); //TODO check instance not just number
                                   //TODO check context on persisted statements
 
         let ps1c = ps1.clone();
         let ps3c = ps3.clone();
         let ps4c = ps4.clone();
-        instance.write(
-            &test_dataset,
-            Box::new(move |tx| {
-                tx.remove_statement(&ps1c);
-                tx.remove_statement(&ps3c);
-                tx.remove_statement(&ps4c);
-                Ok(())
-            }),
-        )?;
+        instance.write_sled(&test_dataset, move |tx| {
+            tx.remove_statement(&ps1c);
+            tx.remove_statement(&ps3c);
+            tx.remove_statement(&ps4c);
+            Ok(())
+        })?;
         let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
         assert!(res.is_empty()); //TODO check instance not just number
                                   //TODO check context on persisted statements
         Ok(())
     }
 
+    #[test]
+    fn removing_missing_statements_writes_nothing() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let before = instance.query_sled(&test_dataset, |tx| tx.stats())?;
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            let missing = |entity: u64, attribute: &str, value: Value| {
+                Ok(PersistedStatement {
+                    statement: Statement {
+                        entity: Entity(entity),
+                        attribute: Attribute::new(attribute)?,
+                        value: value,
+                    },
+                    context: Entity(2),
+                })
+            };
+            Ok(vec![
+                tx.remove_statement(&missing(1, "name", Value::IntegerLiteral(1))?)?,
+                tx.remove_statement(&missing(42, "name", Value::IntegerLiteral(1))?)?,
+                tx.remove_statement(&missing(
+                    1,
+                    "name",
+                    Value::StringLiteral("Juniper".to_string()),
+                )?)?,
+                tx.remove_statement(&missing(1, "name", Value::Entity(Entity(42)))?)?,
+            ])
+        })?;
+        assert_eq!(removed, vec![false, false, false, false]);
+        let after = instance.query_sled(&test_dataset, |tx| tx.stats())?;
+        assert_eq!(before, after);
+        Ok(())
+    }
+
     #[test]
     fn allow_canceling_a_write_tx() -> Result<(), LigatureError> {
         let instance = instance();
         let test_dataset = dataset("test/test");
         instance.create_dataset(&test_dataset)?;
-        let (ps1, ps2) = instance.write(
-            &test_dataset,
-            Box::new(|tx| {
-                let entity = tx.new_entity()?;
-                let attribute = Attribute::new("name")?;
-                let value = Value::StringLiteral("Juniper".to_string());
-                let string_statement = Statement {
-                    entity: entity.clone(),
-                    attribute: attribute.clone(),
-                    value: value.clone(),
-                };
+        let (ps1, ps2) = instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let attribute = Attribute::new("name")?;
+            let value = Value::StringLiteral("Juniper".to_string());
+            let string_statement = Statement {
+                entity: entity.clone(),
+                attribute: attribute.clone(),
+                value: value.clone(),
+            };
 
-                let entity2 = tx.new_entity()?;
-                let attribute2 = Attribute::new("connection")?;
-                let entity3 = tx.new_entity()?;
-                let entity_statement = Statement {
-                    entity: entity2.clone(),
-                    attribute: attribute2.clone(),
-                    value: Value::Entity(entity3.clone()),
-                };
+            let entity2 = tx.new_entity()?;
+            let attribute2 = Attribute::new("connection")?;
+            let entity3 = tx.new_entity()?;
+            let entity_statement = Statement {
+                entity: entity2.clone(),
+                attribute: attribute2.clone(),
+                value: Value::Entity(entity3.clone()),
+            };
 
-                let s1 = tx.add_statement(&string_statement)?;
-                let s2 = tx.add_statement(&entity_statement)?;
+            let s1 = tx.add_statement(&string_statement)?;
+            let s2 = tx.add_statement(&entity_statement)?;
 
-                Ok((s1, s2))
-            }),
-        )?;
+            Ok((s1, s2))
+        })?;
         let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
         assert_eq!(res.len(), 2); //TODO check instance not just number
                                   //TODO check context on persisted statements
 
-        instance.write(
+        instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let attribute = Attribute::new("name")?;
+            let value = Value::StringLiteral("Clarice".to_string());
+            let string_statement = Statement {
+                entity: entity.clone(),
+                attribute: attribute.clone(),
+                value: value.clone(),
+            };
+
+            tx.add_statement(&string_statement)?;
+            tx.cancel()?;
+            Ok(())
+        })?;
+        let res: Vec<PersistedStatement> =
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
+        assert_eq!(res.len(), 2); //TODO check instance not just number
+                                  //TODO check context on persisted statements
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_handles_cannot_modify() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: entity,
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral("Juniper".to_string()),
+            })?;
+            Ok(())
+        })?;
+        let read_only = instance.read_only_handle()?;
+        let read_only2 = read_only.read_only_handle()?;
+        assert!(read_only.is_read_only());
+        assert!(!instance.is_read_only());
+        let read_only_error = Err(LigatureError(READ_ONLY_ERROR.to_string()));
+        assert_eq!(read_only.create_dataset(&dataset("test/test2")), read_only_error);
+        assert_eq!(read_only.delete_dataset(&test_dataset), read_only_error);
+        assert_eq!(
+            read_only.write_sled(&test_dataset, |tx| tx.new_entity().map(|_| ())),
+            read_only_error
+        );
+        assert_eq!(read_only.all_datasets().count(), 1);
+        assert!(!instance.dataset_exists(&dataset("test/test2"))?);
+        for handle in &[read_only, read_only2] {
+            let res: Vec<PersistedStatement> =
+                handle.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
+            assert_eq!(res.len(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn open_read_only_requires_an_existing_store() -> Result<(), LigatureError> {
+        let path = unique_temp_path("read-only-test");
+        let path = path.to_str().unwrap().to_string();
+        assert!(LigatureSled::open_read_only(path.clone()).is_err());
+        {
+            let instance = LigatureSled::new(path.clone()).unwrap();
+            instance.create_dataset(&dataset("test/test"))?;
+        }
+        {
+            let read_only = LigatureSled::open_read_only(path.clone()).unwrap();
+            assert!(read_only.dataset_exists(&dataset("test/test"))?);
+            assert_eq!(
+                read_only.delete_dataset(&dataset("test/test")),
+                Err(LigatureError(READ_ONLY_ERROR.to_string()))
+            );
+        }
+        std::fs::remove_dir_all(&path).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn dataset_stats() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let empty = instance.dataset_stats(&test_dataset)?;
+        assert_eq!(empty.statement_count, 0);
+        assert_eq!(empty.entity_count, 0);
+        assert!(empty.attribute_statement_counts.is_empty());
+        instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let entity2 = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral("Juniper".to_string()),
+            })?;
+            tx.add_statement(&Statement {
+                entity: entity2.clone(),
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral("Clarice".to_string()),
+            })?;
+            tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: Attribute::new("connection")?,
+                value: Value::Entity(entity2.clone()),
+            })?;
+            Ok(())
+        })?;
+        let stats: DatasetStats = instance.dataset_stats(&test_dataset)?;
+        assert_eq!(stats.statement_count, 3);
+        assert_eq!(stats.entity_count, 2);
+        assert_eq!(stats.attribute_count, 2);
+        assert_eq!(
+            stats.attribute_statement_counts,
+            vec![
+                (Attribute::new("name")?, 2),
+                (Attribute::new("connection")?, 1)
+            ]
+        );
+        assert_eq!(stats.string_literal_count, 2);
+        assert_eq!(stats.entity_id_counter, 5); // two Entities and three Contexts
+        assert_eq!(stats.attribute_id_counter, 2);
+        assert_eq!(stats.string_literal_id_counter, 2);
+        assert_eq!(stats.bytes_literal_count, 0);
+        assert_eq!(stats.tagged_literal_count, 0);
+        assert!(stats.total_bytes > empty.total_bytes);
+        instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: Attribute::new("data")?,
+                value: Value::BytesLiteral(vec![0xca, 0xfe]),
+            })?;
+            tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: Attribute::new("name")?,
+                value: Value::LanguageLiteral("chat".to_string(), "fr".to_string()),
+            })?;
+            Ok(())
+        })?;
+        let stats = instance.dataset_stats(&test_dataset)?;
+        assert_eq!(stats.bytes_literal_count, 1);
+        assert_eq!(stats.tagged_literal_count, 1);
+        assert_eq!(stats.bytes_literal_id_counter, 1);
+        assert_eq!(stats.tagged_literal_id_counter, 1);
+        assert_eq!(stats.statement_count, 5);
+        assert_eq!(stats.entity_count, 3);
+        // the counts follow removals, a failed write doesn't change them and contexts aren't Entities
+        let res: Result<(), LigatureError> = instance.write_sled(&test_dataset, |tx| {
+            tx.new_entity()?;
+            tx.remove_matching(None, Some(Attribute::new("name")?), None, None)?;
+            Err(LigatureError("rolled back".to_string()))
+        });
+        assert!(res.is_err());
+        assert_eq!(instance.dataset_stats(&test_dataset)?, stats);
+        instance.write_sled(&test_dataset, |tx| {
+            tx.new_context()?;
+            tx.delete_entity(&Entity(1), ReferencePolicy::Cascade)?;
+            tx.remove_matching(None, Some(Attribute::new("data")?), None, None)?;
+            Ok(())
+        })?;
+        let stats = instance.dataset_stats(&test_dataset)?;
+        assert_eq!(stats.statement_count, 2);
+        assert_eq!(stats.entity_count, 2);
+        assert_eq!(
+            stats.attribute_statement_counts,
+            vec![(Attribute::new("name")?, 2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dataset_size_follows_writes() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let name = Attribute::new("name")?;
+        let statements = instance.write_sled(&test_dataset, |tx| {
+            let graph = tx.new_context()?;
+            let mut statements = vec![];
+            for value in vec!["Juniper", "Clarice"] {
+                statements.push(tx.add_statement_in_context(
+                    &Statement {
+                        entity: tx.new_entity()?,
+                        attribute: name.clone(),
+                        value: Value::StringLiteral(value.to_string()),
+                    },
+                    &graph,
+                )?);
+            }
+            Ok(statements)
+        })?;
+        let added = instance.dataset_stats(&test_dataset)?.total_bytes;
+        instance.write_sled(&test_dataset, |tx| tx.remove_statement(&statements[0]))?;
+        let removed = instance.dataset_stats(&test_dataset)?.total_bytes;
+        assert!(removed < added);
+        // a failed write doesn't change the size
+        let res: Result<(), LigatureError> = instance.write_sled(&test_dataset, |tx| {
+            tx.add_statement_in_context(&statements[0].statement, &statements[0].context)?;
+            Err(LigatureError("rolled back".to_string()))
+        });
+        assert!(res.is_err());
+        assert_eq!(instance.dataset_stats(&test_dataset)?.total_bytes, removed);
+        // the dictionaries already have every entry so adding the Statement back restores the size
+        instance.write_sled(&test_dataset, |tx| {
+            tx.add_statement_in_context(&statements[0].statement, &statements[0].context)
+        })?;
+        assert_eq!(instance.dataset_stats(&test_dataset)?.total_bytes, added);
+        Ok(())
+    }
+
+    fn add_name(
+        instance: &LigatureSled,
+        dataset: &Dataset,
+        name: &'static str,
+    ) -> Result<(), LigatureError> {
+        instance.write_sled(dataset, move |tx| {
+            let entity = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: entity,
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral(name.to_string()),
+            })?;
+            Ok(())
+        })
+    }
+
+    fn statement_count(
+        instance: &LigatureSled,
+        dataset: &Dataset,
+    ) -> Result<usize, LigatureError> {
+        let res: Vec<PersistedStatement> =
+            instance.query_sled(dataset, |tx| tx.all_statements().collect())?;
+        Ok(res.len())
+    }
+
+    #[test]
+    fn copy_dataset() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        let copy = dataset("test/copy");
+        instance.create_dataset(&test_dataset)?;
+        add_name(&instance, &test_dataset, "Juniper")?;
+        instance.copy_dataset(&test_dataset, &copy)?;
+        assert!(instance.dataset_exists(&copy)?);
+        assert_eq!(statement_count(&instance, &copy)?, 1);
+        add_name(&instance, &copy, "Clarice")?;
+        assert_eq!(statement_count(&instance, &test_dataset)?, 1);
+        assert_eq!(statement_count(&instance, &copy)?, 2);
+        // counters are copied so new Entities don't reuse ids
+        assert_eq!(instance.dataset_stats(&copy)?.entity_id_counter, 4);
+        assert!(instance.copy_dataset(&test_dataset, &copy).is_err());
+        assert!(instance
+            .copy_dataset(&dataset("missing"), &dataset("test/copy2"))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rename_dataset() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        let renamed = dataset("test/renamed");
+        let other = dataset("test/other");
+        instance.create_dataset(&test_dataset)?;
+        instance.create_dataset(&other)?;
+        add_name(&instance, &test_dataset, "Juniper")?;
+        assert!(instance.rename_dataset(&test_dataset, &other).is_err());
+        instance.rename_dataset(&test_dataset, &renamed)?;
+        assert!(!instance.dataset_exists(&test_dataset)?);
+        assert!(instance.dataset_exists(&renamed)?);
+        assert_eq!(statement_count(&instance, &renamed)?, 1);
+        assert_eq!(statement_count(&instance, &other)?, 0);
+        instance.create_dataset(&test_dataset)?;
+        assert_eq!(statement_count(&instance, &test_dataset)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn orphaned_dataset_trees_are_removed_on_open() -> Result<(), LigatureError> {
+        let path = unique_temp_path("orphan-test");
+        {
+            let instance = LigatureSled::new(path.to_str().unwrap().to_string()).unwrap();
+            instance.create_dataset(&dataset("test/test"))?;
+        }
+        {
+            // simulate a copy that stopped before the Dataset entry was written
+            let store = sled::open(&path).unwrap();
+            let partial = store.open_tree("__ligature_sled__dataset_2").unwrap();
+            partial.insert(vec![1], vec![]).unwrap();
+            store.open_tree("unrelated").unwrap();
+        }
+        {
+            LigatureSled::new(path.to_str().unwrap().to_string()).unwrap();
+        }
+        {
+            let store = sled::open(&path).unwrap();
+            let names = store.tree_names();
+            assert!(names.contains(&sled::IVec::from("__ligature_sled__dataset_1")));
+            assert!(!names.contains(&sled::IVec::from("__ligature_sled__dataset_2")));
+            assert!(names.contains(&sled::IVec::from("unrelated")));
+        }
+        std::fs::remove_dir_all(&path).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn dataset_names_dont_collide_with_sled_trees() -> Result<(), LigatureError> {
+        let instance = instance();
+        let default_tree = dataset("__sled__default");
+        instance.create_dataset(&default_tree)?;
+        add_name(&instance, &default_tree, "Juniper")?;
+        assert_eq!(statement_count(&instance, &default_tree)?, 1);
+        let res: Vec<Result<Dataset, LigatureError>> = instance.all_datasets().collect();
+        assert_eq!(res, vec![Ok(default_tree)]);
+        Ok(())
+    }
+
+    #[test]
+    fn find_statement_for_a_given_context() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let persisted = instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: entity,
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral("Juniper".to_string()),
+            })
+        })?;
+        let context = persisted.context.clone();
+        let res =
+            instance.query_sled(&test_dataset, move |tx| tx.statement_for_context(&context))?;
+        assert_eq!(res, Some(persisted));
+        let res =
+            instance.query_sled(&test_dataset, |tx| tx.statement_for_context(&Entity(100)))?;
+        assert_eq!(res, None);
+        Ok(())
+    }
+
+    #[test]
+    fn statements_in_shared_contexts() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let name = Attribute::new("name")?;
+        let (graph, statements) = instance.write_sled(&test_dataset, |tx| {
+            let graph = tx.new_context()?;
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            let mut statements = vec![];
+            for (entity, value) in vec![(&juniper, "Juniper"), (&clarice, "Clarice")] {
+                statements.push(tx.add_statement_in_context(
+                    &Statement {
+                        entity: entity.clone(),
+                        attribute: name.clone(),
+                        value: Value::StringLiteral(value.to_string()),
+                    },
+                    &graph,
+                )?);
+            }
+            // adding a Statement to a context twice stores it once
+            tx.add_statement_in_context(&statements[0].statement, &graph)?;
+            // only contexts can be used
+            assert!(tx
+                .add_statement_in_context(&statements[0].statement, &juniper)
+                .is_err());
+            tx.add_statement(&Statement {
+                entity: juniper.clone(),
+                attribute: Attribute::new("age")?,
+                value: Value::IntegerLiteral(31),
+            })?;
+            Ok((graph, statements))
+        })?;
+        let in_graph = instance.query_sled(&test_dataset, |tx| {
+            tx.statements_for_context(&graph)
+                .collect::<Result<Vec<PersistedStatement>, LigatureError>>()
+        })?;
+        assert_eq!(in_graph, statements);
+        assert!(instance
+            .query_sled(&test_dataset, |tx| tx.statement_for_context(&graph))
+            .is_err());
+        assert_eq!(statement_count(&instance, &test_dataset)?, 3);
+        instance.write_sled(&test_dataset, |tx| tx.remove_statement(&statements[0]))?;
+        let in_graph = instance.query_sled(&test_dataset, |tx| {
+            tx.statements_for_context(&graph)
+                .collect::<Result<Vec<PersistedStatement>, LigatureError>>()
+        })?;
+        assert_eq!(in_graph, vec![statements[1].clone()]);
+        assert_eq!(
+            instance.query_sled(&test_dataset, |tx| tx.statement_for_context(&graph))?,
+            Some(statements[1].clone())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn matching_statements_in_datasets() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let (valjean, javert) = instance.write_sled(&test_dataset, |tx| {
+            let valjean = tx.new_entity()?;
+            let javert = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: valjean.clone(),
+                attribute: Attribute::new("nationality")?,
+                value: Value::StringLiteral("French".to_string()),
+            })?;
+            tx.add_statement(&Statement {
+                entity: valjean.clone(),
+                attribute: Attribute::new("prisonNumber")?,
+                value: Value::IntegerLiteral(24601),
+            })?;
+            tx.add_statement(&Statement {
+                entity: javert.clone(),
+                attribute: Attribute::new("nationality")?,
+                value: Value::StringLiteral("French".to_string()),
+            })?;
+            Ok((valjean, javert))
+        })?;
+        let (v, j) = (valjean.clone(), javert.clone());
+        let (french, prison_number, valjeans, javert_french, unknown, all) =
+            instance.query_sled(&test_dataset, move |tx| {
+                let french: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, Some(Value::StringLiteral("French".to_string())))
+                    .collect::<Result<_, _>>()?;
+                let prison_number: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, Some(Value::IntegerLiteral(24601)))
+                    .collect::<Result<_, _>>()?;
+                let valjeans: Vec<PersistedStatement> = tx
+                    .match_statements(Some(v.clone()), None, None)
+                    .collect::<Result<_, _>>()?;
+                let javert_french: Vec<PersistedStatement> = tx
+                    .match_statements(
+                        Some(j.clone()),
+                        Some(Attribute::new("nationality")?),
+                        Some(Value::StringLiteral("French".to_string())),
+                    )
+                    .collect::<Result<_, _>>()?;
+                let unknown: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, Some(Value::StringLiteral("Spanish".to_string())))
+                    .collect::<Result<_, _>>()?;
+                let all: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, None)
+                    .collect::<Result<_, _>>()?;
+                Ok((french, prison_number, valjeans, javert_french, unknown, all))
+            })?;
+        assert_eq!(french.len(), 2);
+        assert_eq!(prison_number.len(), 1);
+        assert_eq!(prison_number[0].statement.entity, valjean);
+        assert_eq!(valjeans.len(), 2);
+        assert_eq!(javert_french.len(), 1);
+        assert_eq!(javert_french[0].statement.entity, javert);
+        assert!(unknown.is_empty());
+        assert_eq!(all.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn match_attributes_prefix() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            for name in &["name", "nationality", "prisonNumber"] {
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: Attribute::new(name)?,
+                    value: Value::IntegerLiteral(1),
+                })?;
+            }
+            Ok(())
+        })?;
+        let res: Vec<Attribute> = instance.query_sled(&test_dataset, |tx| {
+            tx.match_attributes_prefix("na").collect()
+        })?;
+        assert_eq!(
+            res,
+            vec![Attribute::new("name")?, Attribute::new("nationality")?]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn matching_statements_with_literals_and_ranges_in_datasets() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let (valjean, javert) = instance.write_sled(&test_dataset, |tx| {
+            let valjean = tx.new_entity()?;
+            let javert = tx.new_entity()?;
+            let trout = tx.new_entity()?;
+            for (entity, nationality, prison_number) in &[
+                (&valjean, "French", 24601),
+                (&javert, "French", 24602),
+                (&trout, "American", -24603),
+            ] {
+                tx.add_statement(&Statement {
+                    entity: (*entity).clone(),
+                    attribute: Attribute::new("nationality")?,
+                    value: Value::StringLiteral(nationality.to_string()),
+                })?;
+                tx.add_statement(&Statement {
+                    entity: (*entity).clone(),
+                    attribute: Attribute::new("prisonNumber")?,
+                    value: Value::IntegerLiteral(*prison_number),
+                })?;
+                tx.add_statement(&Statement {
+                    entity: (*entity).clone(),
+                    attribute: Attribute::new("height")?,
+                    value: Value::FloatLiteral(*prison_number as f64 / 10000.0),
+                })?;
+            }
+            Ok((valjean, javert))
+        })?;
+        let v = valjean.clone();
+        let counts = instance.query_sled(&test_dataset, move |tx| {
+            let count = |entity, range| {
+                tx.match_statements_range(entity, None, range)
+                    .collect::<Result<Vec<PersistedStatement>, _>>()
+                    .map(|statements| statements.len())
+            };
+            Ok(vec![
+                count(
+                    None,
+                    Range::StringLiteralRange {
+                        start: "French".to_string(),
+                        end: "German".to_string(),
+                    },
+                )?,
+                count(
+                    None,
+                    Range::IntegerLiteralRange {
+                        start: 24601,
+                        end: 24603,
+                    },
+                )?,
+                count(
+                    Some(v.clone()),
+                    Range::IntegerLiteralRange {
+                        start: 24601,
+                        end: 24603,
+                    },
+                )?,
+                count(
+                    None,
+                    Range::IntegerLiteralRange {
+                        start: -30000,
+                        end: 24602,
+                    },
+                )?,
+                count(
+                    None,
+                    Range::IntegerLiteralRange {
+                        start: 24603,
+                        end: 24601,
+                    },
+                )?,
+                count(
+                    None,
+                    Range::FloatLiteralRange {
+                        start: -3.0,
+                        end: 2.46015,
+                    },
+                )?,
+            ])
+        })?;
+        assert_eq!(counts, vec![2, 2, 1, 2, 0, 2]);
+        let french: Vec<PersistedStatement> = instance.query_sled(&test_dataset, |tx| {
+            tx.match_statements_range(
+                None,
+                Some(Attribute::new("nationality")?),
+                Range::StringLiteralRange {
+                    start: "French".to_string(),
+                    end: "German".to_string(),
+                },
+            )
+            .collect()
+        })?;
+        let mut entities: Vec<_> = french
+            .iter()
+            .map(|ps| ps.statement.entity.clone())
+            .collect();
+        entities.sort_by_key(|entity| entity.0);
+        assert_eq!(entities, vec![valjean, javert]);
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_bytes_and_timestamp_values() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let values = vec![
+            Value::BooleanLiteral(true),
+            Value::BooleanLiteral(false),
+            Value::BytesLiteral(vec![0xca, 0xfe]),
+            Value::BytesLiteral(vec![]),
+            Value::TimestampLiteral(1_600_000_000_000_000_000),
+            Value::TimestampLiteral(-1_000),
+        ];
+        let stored = values.clone();
+        instance.write_sled(&test_dataset, move |tx| {
+            let entity = tx.new_entity()?;
+            for value in &stored {
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: Attribute::new("value")?,
+                    value: value.clone(),
+                })?;
+            }
+            Ok(())
+        })?;
+        let matched = values.clone();
+        let counts = instance.query_sled(&test_dataset, move |tx| {
+            let mut counts = vec![];
+            for value in &matched {
+                let statements: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, Some(value.clone()))
+                    .collect::<Result<_, _>>()?;
+                assert_eq!(&statements[0].statement.value, value);
+                counts.push(statements.len());
+            }
+            let unknown: Vec<PersistedStatement> = tx
+                .match_statements(None, None, Some(Value::BytesLiteral(vec![1])))
+                .collect::<Result<_, _>>()?;
+            counts.push(unknown.len());
+            Ok(counts)
+        })?;
+        assert_eq!(counts, vec![1, 1, 1, 1, 1, 1, 0]);
+        let (bytes, timestamps) = instance.query_sled(&test_dataset, |tx| {
+            let bytes: Vec<PersistedStatement> = tx
+                .match_statements_range(
+                    None,
+                    None,
+                    Range::BytesLiteralRange {
+                        start: vec![0xca],
+                        end: vec![0xcb],
+                    },
+                )
+                .collect::<Result<_, _>>()?;
+            let timestamps: Vec<PersistedStatement> = tx
+                .match_statements_range(
+                    None,
+                    None,
+                    Range::TimestampLiteralRange {
+                        start: -2_000,
+                        end: 1_600_000_000_000_000_000,
+                    },
+                )
+                .collect::<Result<_, _>>()?;
+            Ok((bytes, timestamps))
+        })?;
+        assert_eq!(bytes.len(), 1);
+        assert_eq!(bytes[0].statement.value, Value::BytesLiteral(vec![0xca, 0xfe]));
+        assert_eq!(timestamps.len(), 1);
+        assert_eq!(timestamps[0].statement.value, Value::TimestampLiteral(-1_000));
+        Ok(())
+    }
+
+    #[test]
+    fn ligature_traits_use_ligature_values() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let statement = instance.write(
             &test_dataset,
             Box::new(|tx| {
                 let entity = tx.new_entity()?;
-                let attribute = Attribute::new("name")?;
-                let value = Value::StringLiteral("Clarice".to_string());
-                let string_statement = Statement {
-                    entity: entity.clone(),
-                    attribute: attribute.clone(),
+                let persisted = tx.add_statement(&ligature::Statement {
+                    entity: entity,
+                    attribute: Attribute::new("age")?,
+                    value: ligature::Value::IntegerLiteral(24601),
+                })?;
+                Ok(persisted.statement)
+            }),
+        )?;
+        let statements: Vec<PersistedStatement> =
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
+        assert_eq!(statements[0].statement, statement.clone().into());
+        instance.write_sled(&test_dataset, |tx| {
+            tx.add_statement(&Statement {
+                entity: statement.entity.clone(),
+                attribute: Attribute::new("active")?,
+                value: Value::BooleanLiteral(true),
+            })
+        })?;
+        // Statements with Values ligature doesn't have can't be read through QueryTx
+        let read: Result<Vec<ligature::PersistedStatement>, LigatureError> =
+            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()));
+        assert!(read.is_err());
+        let ages: Vec<ligature::PersistedStatement> = instance.query(
+            &test_dataset,
+            Box::new(|tx| {
+                tx.match_statements(None, Some(Attribute::new("age")?), None)
+                    .collect()
+            }),
+        )?;
+        assert_eq!(ages.len(), 1);
+        assert_eq!(ages[0].statement, statement);
+        Ok(())
+    }
+
+    #[test]
+    fn language_and_typed_literals() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let (cat, dog) = instance.write_sled(&test_dataset, |tx| {
+            let cat = tx.new_entity()?;
+            let dog = tx.new_entity()?;
+            for (entity, value) in &[
+                (
+                    &cat,
+                    Value::LanguageLiteral("chat".to_string(), "fr".to_string()),
+                ),
+                (
+                    &cat,
+                    Value::LanguageLiteral("cat".to_string(), "en".to_string()),
+                ),
+                (&cat, Value::StringLiteral("chat".to_string())),
+                (
+                    &dog,
+                    Value::LanguageLiteral("dog".to_string(), "en".to_string()),
+                ),
+                (
+                    &dog,
+                    Value::TypedLiteral("chat".to_string(), "http://example.com/word".to_string()),
+                ),
+            ] {
+                tx.add_statement(&Statement {
+                    entity: (*entity).clone(),
+                    attribute: Attribute::new("label")?,
                     value: value.clone(),
-                };
+                })?;
+            }
+            Ok((cat, dog))
+        })?;
+        let (c, d) = (cat.clone(), dog.clone());
+        let (chat, cat_english, english, words) = instance.query_sled(&test_dataset, move |tx| {
+            let chat: Vec<PersistedStatement> = tx
+                .match_statements(
+                    None,
+                    None,
+                    Some(Value::LanguageLiteral("chat".to_string(), "fr".to_string())),
+                )
+                .collect::<Result<_, _>>()?;
+            let cat_english: Vec<PersistedStatement> = tx
+                .match_language_literals(Some(c.clone()), Some(Attribute::new("label")?), "en")
+                .collect::<Result<_, _>>()?;
+            let english: Vec<PersistedStatement> = tx
+                .match_language_literals(None, None, "en")
+                .collect::<Result<_, _>>()?;
+            let words: Vec<PersistedStatement> = tx
+                .match_typed_literals(Some(d.clone()), None, "http://example.com/word")
+                .collect::<Result<_, _>>()?;
+            Ok((chat, cat_english, english, words))
+        })?;
+        assert_eq!(chat.len(), 1);
+        assert_eq!(chat[0].statement.entity, cat);
+        assert_eq!(cat_english.len(), 1);
+        assert_eq!(
+            cat_english[0].statement.value,
+            Value::LanguageLiteral("cat".to_string(), "en".to_string())
+        );
+        assert_eq!(english.len(), 2);
+        assert_eq!(words.len(), 1);
+        assert_eq!(
+            words[0].statement.value,
+            Value::TypedLiteral("chat".to_string(), "http://example.com/word".to_string())
+        );
+        let stats = instance.dataset_stats(&test_dataset)?;
+        assert_eq!(stats.string_literal_count, 1);
+        Ok(())
+    }
 
-                tx.add_statement(&string_statement)?;
-                tx.cancel()?;
-                Ok(())
-            }))?;
-        let res: Vec<PersistedStatement> =
-            instance.query(&test_dataset, Box::new(|tx| tx.all_statements().collect()))?;
-        assert_eq!(res.len(), 2); //TODO check instance not just number
-                                  //TODO check context on persisted statements
+    #[test]
+    fn large_string_literals() -> Result<(), LigatureError> {
+        use std::io::Read;
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let large: String = (0..200_000).map(|i| ((b'a' + (i % 26) as u8) as char)).collect();
+        let mut other = large.clone();
+        other.push('!');
+        let (first, second) = (large.clone(), other.clone());
+        let (entity, contexts, shared) = instance.write_sled(&test_dataset, move |tx| {
+            let entity = tx.new_entity()?;
+            let mut contexts = vec![];
+            for value in &[
+                first.clone(),
+                second.clone(),
+                first.clone(),
+                "small".to_string(),
+            ] {
+                let statement = tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: Attribute::new(&format!("text{}", contexts.len()))?,
+                    value: Value::StringLiteral(value.clone()),
+                })?;
+                contexts.push(statement.context);
+            }
+            let shared = tx.new_context()?;
+            for value in &[second.clone(), first.clone()] {
+                tx.add_statement_in_context(
+                    &Statement {
+                        entity: entity.clone(),
+                        attribute: Attribute::new("shared")?,
+                        value: Value::StringLiteral(value.clone()),
+                    },
+                    &shared,
+                )?;
+            }
+            Ok((entity, contexts, shared))
+        })?;
+        let owner = entity.clone();
+        let expected = large.clone();
+        let (matched, read, small, lengths, stats) =
+            instance.query_sled(&test_dataset, move |tx| {
+                let matched: Vec<PersistedStatement> = tx
+                    .match_statements(None, None, Some(Value::StringLiteral(expected.clone())))
+                    .collect::<Result<_, _>>()?;
+                let text = |i: usize| Attribute::new(&format!("text{}", i));
+                let mut readers = tx.string_literal_readers(&entity, &text(1)?, &contexts[1])?;
+                assert_eq!(readers.len(), 1);
+                assert_eq!(readers[0].len(), 200_001);
+                let mut read = String::new();
+                readers[0]
+                    .read_to_string(&mut read)
+                    .map_err(|_| LigatureError("Error reading.".to_string()))?;
+                let mut small = String::new();
+                tx.string_literal_readers(&entity, &text(3)?, &contexts[3])?[0]
+                    .read_to_string(&mut small)
+                    .map_err(|_| LigatureError("Error reading.".to_string()))?;
+                assert!(tx
+                    .string_literal_readers(&entity, &text(0)?, &contexts[3])?
+                    .is_empty());
+                let lengths: Vec<u64> = tx
+                    .string_literal_readers(&entity, &Attribute::new("shared")?, &shared)?
+                    .iter()
+                    .map(|reader| reader.len())
+                    .collect();
+                Ok((matched, read, small, lengths, tx.stats()?))
+            })?;
+        assert_eq!(matched.len(), 3);
+        assert_eq!(
+            matched[0].statement.value,
+            Value::StringLiteral(large.clone())
+        );
+        assert_eq!(read, other);
+        assert_eq!(small, "small");
+        // every String Literal in the shared context is read, in dictionary id order
+        assert_eq!(lengths, vec![200_000, 200_001]);
+        assert_eq!(stats.string_literal_count, 3);
+        // each large string is stored once
+        assert!(stats.total_bytes < 450_000);
+
+        // the chunks go with the last Statement that uses a large string
+        let removed = large.clone();
+        instance.write_sled(&test_dataset, move |tx| {
+            tx.remove_matching(
+                None,
+                None,
+                Some(Value::StringLiteral(removed.clone())),
+                None,
+            )
+        })?;
+        let after = instance.dataset_stats(&test_dataset)?;
+        assert_eq!(after.string_literal_count, 2);
+        assert!(after.total_bytes < stats.total_bytes - 200_000);
+        let added = large.clone();
+        instance.write_sled(&test_dataset, move |tx| {
+            tx.add_statement(&Statement {
+                entity: owner.clone(),
+                attribute: Attribute::new("text0")?,
+                value: Value::StringLiteral(added.clone()),
+            })
+        })?;
+        let expected = large.clone();
+        let matched = instance.query_sled(&test_dataset, move |tx| {
+            tx.match_statements(None, None, Some(Value::StringLiteral(expected.clone())))
+                .collect::<Result<Vec<PersistedStatement>, _>>()
+        })?;
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].statement.value, Value::StringLiteral(large));
+        Ok(())
+    }
+
+    #[test]
+    fn attribute_schemas() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let email = Attribute::new("email")?;
+        let schema = AttributeSchema {
+            value_type: ValueType::String,
+            cardinality: Cardinality::One,
+            unique: true,
+        };
+        let violations = instance.write_sled(&test_dataset, |tx| {
+            tx.set_attribute_schema(&email, schema)?;
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            let statement = |entity: &Entity, value: Value| Statement {
+                entity: entity.clone(),
+                attribute: email.clone(),
+                value: value,
+            };
+            tx.add_statement(&statement(&juniper, Value::StringLiteral("j@example.com".to_string())))?;
+            // adding the same Value again doesn't break the cardinality
+            tx.add_statement(&statement(&juniper, Value::StringLiteral("j@example.com".to_string())))?;
+            let violations = vec![
+                tx.validate_statement(&statement(&juniper, Value::IntegerLiteral(1)))?,
+                tx.validate_statement(&statement(&juniper, Value::StringLiteral("other@example.com".to_string())))?,
+                tx.validate_statement(&statement(&clarice, Value::StringLiteral("j@example.com".to_string())))?,
+                tx.validate_statement(&statement(&clarice, Value::StringLiteral("c@example.com".to_string())))?,
+            ];
+            assert_eq!(
+                tx.try_add_statement(&statement(&clarice, Value::StringLiteral("j@example.com".to_string()))),
+                Err(WriteError::SchemaViolation(SchemaViolation::NotUnique {
+                    attribute: email.clone(),
+                    value: Value::StringLiteral("j@example.com".to_string()),
+                }))
+            );
+            assert_eq!(
+                tx.try_add_statement(&statement(&Entity(42), Value::StringLiteral("x@example.com".to_string()))),
+                Err(WriteError::InvalidEntity(LigatureError(
+                    "Entity 42 doesn't exist in this Dataset.".to_string()
+                )))
+            );
+            Ok(violations)
+        })?;
+        assert_eq!(
+            violations[0],
+            vec![
+                SchemaViolation::WrongValueType {
+                    attribute: email.clone(),
+                    expected: ValueType::String,
+                    found: ValueType::Integer,
+                },
+                SchemaViolation::CardinalityOne {
+                    attribute: email.clone(),
+                    entity: Entity(1),
+                },
+            ]
+        );
+        assert_eq!(
+            violations[1],
+            vec![SchemaViolation::CardinalityOne {
+                attribute: email.clone(),
+                entity: Entity(1),
+            }]
+        );
+        assert_eq!(
+            violations[2],
+            vec![SchemaViolation::NotUnique {
+                attribute: email.clone(),
+                value: Value::StringLiteral("j@example.com".to_string()),
+            }]
+        );
+        assert!(violations[3].is_empty());
+
+        // existing Statements are checked when a schema is set
+        let res = instance.write_sled(&test_dataset, |tx| {
+            tx.set_attribute_schema(&email, AttributeSchema::new(ValueType::Integer))
+        });
+        assert!(res.is_err());
+        let schemas: Vec<(Attribute, AttributeSchema)> =
+            instance.query_sled(&test_dataset, |tx| tx.attribute_schemas().collect())?;
+        assert_eq!(schemas, vec![(email.clone(), schema)]);
+        instance.write_sled(&test_dataset, |tx| tx.remove_attribute_schema(&email))?;
+        let removed = instance.query_sled(&test_dataset, |tx| tx.attribute_schema(&email))?;
+        assert_eq!(removed, None);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_or_create_entities_by_unique_attributes() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let sku = Attribute::new("sku")?;
+        let name = Attribute::new("name")?;
+        let (first, again, other) = instance.write_sled(&test_dataset, |tx| {
+            tx.set_attribute_schema(
+                &sku,
+                AttributeSchema {
+                    value_type: ValueType::String,
+                    cardinality: Cardinality::One,
+                    unique: true,
+                },
+            )?;
+            let first =
+                tx.resolve_or_create_entity(&sku, &Value::StringLiteral("A-1".to_string()))?;
+            let again =
+                tx.resolve_or_create_entity(&sku, &Value::StringLiteral("A-1".to_string()))?;
+            let other =
+                tx.resolve_or_create_entity(&sku, &Value::StringLiteral("B-2".to_string()))?;
+            // Attributes that aren't unique can't be used as keys
+            assert!(tx
+                .resolve_or_create_entity(&name, &Value::StringLiteral("Juniper".to_string()))
+                .is_err());
+            // a second Entity can't use the same key
+            assert!(tx
+                .add_statement(&Statement {
+                    entity: other.clone(),
+                    attribute: sku.clone(),
+                    value: Value::StringLiteral("A-1".to_string()),
+                })
+                .is_err());
+            Ok((first, again, other))
+        })?;
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+        let (resolved, missing) = instance.query_sled(&test_dataset, |tx| {
+            Ok((
+                tx.resolve_entity(&sku, &Value::StringLiteral("A-1".to_string()))?,
+                tx.resolve_entity(&sku, &Value::StringLiteral("C-3".to_string()))?,
+            ))
+        })?;
+        assert_eq!(resolved, Some(first.clone()));
+        assert_eq!(missing, None);
+        let later = instance.write_sled(&test_dataset, |tx| {
+            tx.resolve_or_create_entity(&sku, &Value::StringLiteral("A-1".to_string()))
+        })?;
+        assert_eq!(later, first);
+        Ok(())
+    }
+
+    #[test]
+    fn set_value_replaces_values() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let status = Attribute::new("status")?;
+        let (entity, first) = instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let first = tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: status.clone(),
+                value: Value::StringLiteral("draft".to_string()),
+            })?;
+            let removed = tx.set_value(
+                &entity,
+                &Attribute::new("name")?,
+                &Value::StringLiteral("Juniper".to_string()),
+            )?;
+            assert!(removed.is_empty());
+            Ok((entity, first))
+        })?;
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.set_value(&entity, &status, &Value::StringLiteral("published".to_string()))
+        })?;
+        assert_eq!(removed, vec![first]);
+        let statuses: Vec<PersistedStatement> = instance.query_sled(&test_dataset, |tx| {
+            tx.match_statements(None, Some(Attribute::new("status")?), None)
+                .collect()
+        })?;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(
+            statuses[0].statement.value,
+            Value::StringLiteral("published".to_string())
+        );
+        assert_eq!(statement_count(&instance, &test_dataset)?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn set_value_removes_context_metadata() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let status = Attribute::new("status")?;
+        let source = Attribute::new("source")?;
+        let (entity, first, graph) = instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let first = tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: status.clone(),
+                value: Value::StringLiteral("draft".to_string()),
+            })?;
+            let imported = tx.add_context_metadata(
+                &first.context,
+                &source,
+                &Value::StringLiteral("import".to_string()),
+            )?;
+            // metadata about the metadata goes with it
+            tx.add_context_metadata(
+                &imported.context,
+                &Attribute::new("confidence")?,
+                &Value::IntegerLiteral(1),
+            )?;
+            // a shared context keeps its metadata while it still holds a Statement
+            let graph = tx.new_context()?;
+            for (attribute, value) in vec![
+                (status.clone(), "reviewed"),
+                (Attribute::new("name")?, "Juniper"),
+            ] {
+                tx.add_statement_in_context(
+                    &Statement {
+                        entity: entity.clone(),
+                        attribute: attribute,
+                        value: Value::StringLiteral(value.to_string()),
+                    },
+                    &graph,
+                )?;
+            }
+            tx.add_context_metadata(&graph, &source, &Value::StringLiteral("review".to_string()))?;
+            Ok((entity, first, graph))
+        })?;
+        instance.write_sled(&test_dataset, |tx| {
+            tx.set_value(&entity, &status, &Value::StringLiteral("published".to_string()))
+        })?;
+        let (first_metadata, graph_metadata, imported) =
+            instance.query_sled(&test_dataset, |tx| {
+                Ok((
+                    tx.context_metadata(&first.context)
+                        .collect::<Result<Vec<PersistedStatement>, LigatureError>>()?,
+                    tx.context_metadata(&graph)
+                        .collect::<Result<Vec<PersistedStatement>, LigatureError>>()?,
+                    tx.match_statements_by_metadata(
+                        &source,
+                        &Value::StringLiteral("import".to_string()),
+                    )
+                    .count(),
+                ))
+            })?;
+        assert!(first_metadata.is_empty());
+        assert_eq!(graph_metadata.len(), 1);
+        assert_eq!(imported, 0);
+        // the new status, the name in graph and the metadata about graph
+        assert_eq!(statement_count(&instance, &test_dataset)?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_matching_statements() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let name = Attribute::new("name")?;
+        let age = Attribute::new("age")?;
+        let (juniper, clarice) = instance.write_sled(&test_dataset, |tx| {
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            for (entity, value) in vec![
+                (&juniper, Value::StringLiteral("Juniper".to_string())),
+                (&clarice, Value::StringLiteral("Clarice".to_string())),
+            ] {
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: name.clone(),
+                    value: value,
+                })?;
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: age.clone(),
+                    value: Value::IntegerLiteral(31),
+                })?;
+            }
+            Ok((juniper, clarice))
+        })?;
+        let over_limit = instance.write_sled(&test_dataset, |tx| {
+            tx.remove_matching(None, Some(age.clone()), None, Some(1))
+        });
+        assert!(over_limit.is_err());
+        assert_eq!(statement_count(&instance, &test_dataset)?, 4);
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            let missing = tx.remove_matching(
+                None,
+                Some(Attribute::new("missing")?),
+                None,
+                None,
+            )?;
+            assert_eq!(missing, 0);
+            tx.remove_matching(Some(juniper.clone()), None, None, Some(2))
+        })?;
+        assert_eq!(removed, 2);
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.remove_matching(None, None, Some(Value::IntegerLiteral(31)), None)
+        })?;
+        assert_eq!(removed, 1);
+        let remaining: Vec<PersistedStatement> =
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].statement.entity, clarice);
+        Ok(())
+    }
+
+    #[test]
+    fn delete_entities() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let name = Attribute::new("name")?;
+        let knows = Attribute::new("knows")?;
+        let (juniper, clarice) = instance.write_sled(&test_dataset, |tx| {
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            tx.add_statement(&Statement {
+                entity: juniper.clone(),
+                attribute: name.clone(),
+                value: Value::StringLiteral("Juniper".to_string()),
+            })?;
+            tx.add_statement(&Statement {
+                entity: clarice.clone(),
+                attribute: knows.clone(),
+                value: Value::Entity(juniper.clone()),
+            })?;
+            tx.add_statement(&Statement {
+                entity: clarice.clone(),
+                attribute: Attribute::new("age")?,
+                value: Value::IntegerLiteral(31),
+            })?;
+            Ok((juniper, clarice))
+        })?;
+        let res = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::Error)
+        });
+        assert!(res.is_err());
+        assert_eq!(statement_count(&instance, &test_dataset)?, 3);
+
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::LeaveDangling)
+        })?;
+        assert_eq!(removed, 1);
+        assert_eq!(statement_count(&instance, &test_dataset)?, 2);
+
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::Cascade)
+        })?;
+        assert_eq!(removed, 1);
+        let remaining: Vec<PersistedStatement> =
+            instance.query_sled(&test_dataset, |tx| tx.all_statements().collect())?;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].statement.attribute, Attribute::new("age")?);
+
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&clarice, ReferencePolicy::Error)
+        })?;
+        assert_eq!(removed, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn delete_entities_in_shared_contexts() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let juniper = instance.write_sled(&test_dataset, |tx| {
+            let graph = tx.new_context()?;
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            let statements = vec![
+                (&juniper, "name", Value::StringLiteral("Juniper".to_string())),
+                (&juniper, "age", Value::IntegerLiteral(31)),
+                (&clarice, "knows", Value::Entity(juniper.clone())),
+            ];
+            for (entity, attribute, value) in statements {
+                tx.add_statement_in_context(
+                    &Statement {
+                        entity: entity.clone(),
+                        attribute: Attribute::new(attribute)?,
+                        value: value,
+                    },
+                    &graph,
+                )?;
+            }
+            Ok(juniper)
+        })?;
+        // the reference shares a context with Juniper's own Statements but still stops the deletion
+        let res = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::Error)
+        });
+        assert!(res.is_err());
+        assert_eq!(statement_count(&instance, &test_dataset)?, 3);
+
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::Cascade)
+        })?;
+        assert_eq!(removed, 3);
+        assert_eq!(statement_count(&instance, &test_dataset)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn delete_entities_removes_context_metadata() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let source = Attribute::new("source")?;
+        let (juniper, graph) = instance.write_sled(&test_dataset, |tx| {
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            let name = tx.add_statement(&Statement {
+                entity: juniper.clone(),
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral("Juniper".to_string()),
+            })?;
+            tx.add_context_metadata(
+                &name.context,
+                &source,
+                &Value::StringLiteral("import".to_string()),
+            )?;
+            // a shared context keeps its metadata while it still holds a Statement
+            let graph = tx.new_context()?;
+            let statements = vec![
+                ("knows", Value::Entity(juniper.clone())),
+                ("age", Value::IntegerLiteral(31)),
+            ];
+            for (attribute, value) in statements {
+                tx.add_statement_in_context(
+                    &Statement {
+                        entity: clarice.clone(),
+                        attribute: Attribute::new(attribute)?,
+                        value: value,
+                    },
+                    &graph,
+                )?;
+            }
+            tx.add_context_metadata(&graph, &source, &Value::StringLiteral("review".to_string()))?;
+            Ok((juniper, graph))
+        })?;
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::Cascade)
+        })?;
+        // the name, the reference and the metadata about the name's context
+        assert_eq!(removed, 3);
+        let (imported, graph_metadata) = instance.query_sled(&test_dataset, |tx| {
+            Ok((
+                tx.match_statements_by_metadata(
+                    &source,
+                    &Value::StringLiteral("import".to_string()),
+                )
+                .count(),
+                tx.context_metadata(&graph)
+                    .collect::<Result<Vec<PersistedStatement>, LigatureError>>()?,
+            ))
+        })?;
+        assert_eq!(imported, 0);
+        assert_eq!(graph_metadata.len(), 1);
+        assert_eq!(statement_count(&instance, &test_dataset)?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn only_registered_entities_can_be_used() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let name = Attribute::new("name")?;
+        let (entity, context) = instance.write_sled(&test_dataset, |tx| {
+            let entity = tx.new_entity()?;
+            let persisted = tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: name.clone(),
+                value: Value::StringLiteral("Juniper".to_string()),
+            })?;
+            Ok((entity, persisted.context))
+        })?;
+        let exists = instance.query_sled(&test_dataset, |tx| {
+            Ok(vec![
+                tx.entity_exists(&entity)?,
+                tx.entity_exists(&context)?,
+                tx.entity_exists(&Entity(0))?,
+                tx.entity_exists(&Entity(100))?,
+            ])
+        })?;
+        assert_eq!(exists, vec![true, false, false, false]);
+        for invalid in vec![Entity(0), context.clone(), Entity(100)] {
+            let res = instance.write_sled(&test_dataset, |tx| {
+                tx.add_statement(&Statement {
+                    entity: invalid.clone(),
+                    attribute: name.clone(),
+                    value: Value::StringLiteral("Clarice".to_string()),
+                })
+            });
+            assert!(res.is_err());
+            let res = instance.write_sled(&test_dataset, |tx| {
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: Attribute::new("knows")?,
+                    value: Value::Entity(invalid.clone()),
+                })
+            });
+            assert!(res.is_err());
+        }
+        instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&entity, ReferencePolicy::Error)
+        })?;
+        let exists = instance.query_sled(&test_dataset, |tx| tx.entity_exists(&entity))?;
+        assert!(!exists);
+        let res = instance.write_sled(&test_dataset, |tx| {
+            tx.add_statement(&Statement {
+                entity: entity.clone(),
+                attribute: name.clone(),
+                value: Value::StringLiteral("Juniper".to_string()),
+            })
+        });
+        assert!(res.is_err());
         Ok(())
     }
 
-    // #[test]
-    // fn find_statement_for_a_given_context() -> Result<(), LigatureError> {
-    //     todo!()
-    // }
-
-    //   #[test]
-    //   fn matching_statements_in_datasets() {
-    //        let instance = LigatureMock::new();
-    //     let res = createLigature.instance.use { instance  =>
-    //     lateinit var valjean: Node
-    //     lateinit var javert: Node
-    //     instance.write.use { tx =>
-    //       valjean = tx.newNode(test_dataset)
-    //       javert = tx.newNode(test_dataset)
-    //       tx.addStatement(test_dataset, Statement(valjean, Predicate("nationality"), StringLiteral("French")))
-    //       tx.addStatement(test_dataset, Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601)))
-    //       tx.addStatement(test_dataset, Statement(javert, Predicate("nationality"), StringLiteral("French")))
-    //     }
-    //     instance.read.use { tx =>
-    //       tx.matchStatements(test_dataset, null, null, StringLiteral("French"))
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("nationality"), StringLiteral("French")),
-    //                   Statement(javert, Predicate("nationality"), StringLiteral("French"))
-    //       )
-    //       tx.matchStatements(test_dataset, null, null, IntegerLiteral(24601))
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601))
-    //       )
-    //       tx.matchStatements(test_dataset, valjean)
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("nationality"), StringLiteral("French")),
-    //                   Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601))
-    //       )
-    //       tx.matchStatements(test_dataset, javert, Predicate("nationality"), StringLiteral("French"))
-    //               .toSet() shouldBe setOf(
-    //                   Statement(javert, Predicate("nationality"), StringLiteral("French"))
-    //       )
-    //       tx.matchStatements(test_dataset, null, null, null)
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("nationality"), StringLiteral("French")),
-    //                   Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601)),
-    //                   Statement(javert, Predicate("nationality"), StringLiteral("French"))
-    //       )
-    //     }
-    //   }
-
-    //   #[test]
-    //   fn matching_statements_with_literals_and_ranges_in_datasets() {
-    //        let instance = LigatureMock::new();
-
-    //     let res = createLigature.instance.use { instance  =>
-    //     lateinit var valjean: Node
-    //     lateinit var javert: Node
-    //     lateinit var trout: Node
-    //     instance.write.use { tx =>
-    //       valjean = tx.newNode(test_dataset)
-    //       javert = tx.newNode(test_dataset)
-    //       trout = tx.newNode(test_dataset)
-    //       tx.addStatement(test_dataset, Statement(valjean, Predicate("nationality"), StringLiteral("French")))
-    //       tx.addStatement(test_dataset, Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601)))
-    //       tx.addStatement(test_dataset, Statement(javert, Predicate("nationality"), StringLiteral("French")))
-    //       tx.addStatement(test_dataset, Statement(javert, Predicate("prisonNumber"), IntegerLiteral(24602)))
-    //       tx.addStatement(test_dataset, Statement(trout, Predicate("nationality"), StringLiteral("American")))
-    //       tx.addStatement(test_dataset, Statement(trout, Predicate("prisonNumber"), IntegerLiteral(24603)))
-    //     }
-    //     instance.read.use { tx =>
-    //       tx.matchStatements(test_dataset, null, null, StringLiteralRange("French", "German"))
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("nationality"), StringLiteral("French")),
-    //                   Statement(javert, Predicate("nationality"), StringLiteral("French"))
-    //       )
-    //       tx.matchStatements(test_dataset, null, null, IntegerLiteralRange(24601, 24603))
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601)),
-    //                   Statement(javert, Predicate("prisonNumber"), IntegerLiteral(24602))
-    //       )
-    //       tx.matchStatements(test_dataset, valjean, null, IntegerLiteralRange(24601, 24603))
-    //               .toSet() shouldBe setOf(
-    //                   Statement(valjean, Predicate("prisonNumber"), IntegerLiteral(24601))
-    //       )
-    //     }
-    //   }
+    #[test]
+    fn named_entities() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let (juniper, clarice) = instance.write_sled(&test_dataset, |tx| {
+            let juniper = tx.named_entity("https://example.com/juniper")?;
+            let clarice = tx.named_entity("https://example.com/clarice")?;
+            assert_eq!(tx.named_entity("https://example.com/juniper")?, juniper);
+            assert_eq!(
+                tx.entity_name(&clarice)?,
+                Some("https://example.com/clarice".to_string())
+            );
+            assert!(tx.named_entity("").is_err());
+            tx.named_entity("urn:uuid:0")?;
+            Ok((juniper, clarice))
+        })?;
+        assert_ne!(juniper, clarice);
+        let (found, name, matches) = instance.query_sled(&test_dataset, |tx| {
+            Ok((
+                tx.lookup_named_entity("https://example.com/juniper")?,
+                tx.entity_name(&juniper)?,
+                tx.match_entity_names_prefix("https://")
+                    .collect::<Result<Vec<(String, Entity)>, LigatureError>>()?,
+            ))
+        })?;
+        assert_eq!(found, Some(juniper.clone()));
+        assert_eq!(name, Some("https://example.com/juniper".to_string()));
+        assert_eq!(
+            matches,
+            vec![
+                ("https://example.com/clarice".to_string(), clarice.clone()),
+                ("https://example.com/juniper".to_string(), juniper.clone()),
+            ]
+        );
+        let renamed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&juniper, ReferencePolicy::Error)?;
+            tx.named_entity("https://example.com/juniper")
+        })?;
+        assert_ne!(renamed, juniper);
+        let name = instance.query_sled(&test_dataset, |tx| tx.entity_name(&juniper))?;
+        assert_eq!(name, None);
+        Ok(())
+    }
+
+    #[test]
+    fn context_metadata() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let source = Attribute::new("source")?;
+        let crm = Value::StringLiteral("crm".to_string());
+        let (from_crm, other, metadata) = instance.write_sled(&test_dataset, |tx| {
+            let juniper = tx.new_entity()?;
+            let from_crm = tx.add_statement(&Statement {
+                entity: juniper.clone(),
+                attribute: Attribute::new("name")?,
+                value: Value::StringLiteral("Juniper".to_string()),
+            })?;
+            let other = tx.add_statement(&Statement {
+                entity: juniper.clone(),
+                attribute: Attribute::new("age")?,
+                value: Value::IntegerLiteral(31),
+            })?;
+            let metadata = tx.add_context_metadata(&from_crm.context, &source, &crm)?;
+            tx.add_context_metadata(
+                &other.context,
+                &source,
+                &Value::StringLiteral("import".to_string()),
+            )?;
+            // contexts can only be used as Entities through add_context_metadata
+            assert!(tx
+                .add_statement(&Statement {
+                    entity: from_crm.context.clone(),
+                    attribute: source.clone(),
+                    value: crm.clone(),
+                })
+                .is_err());
+            assert!(tx.add_context_metadata(&juniper, &source, &crm).is_err());
+            Ok((from_crm, other, metadata))
+        })?;
+        let (found, metadata_found) = instance.query_sled(&test_dataset, |tx| {
+            Ok((
+                tx.match_statements_by_metadata(&source, &crm)
+                    .collect::<Result<Vec<PersistedStatement>, LigatureError>>()?,
+                tx.context_metadata(&from_crm.context)
+                    .collect::<Result<Vec<PersistedStatement>, LigatureError>>()?,
+            ))
+        })?;
+        assert_eq!(found, vec![from_crm.clone()]);
+        assert_eq!(metadata_found, vec![metadata]);
+        // deleting a context removes its Statement and metadata
+        let removed = instance.write_sled(&test_dataset, |tx| {
+            tx.delete_entity(&other.context, ReferencePolicy::Error)
+        })?;
+        assert_eq!(removed, 2);
+        assert_eq!(statement_count(&instance, &test_dataset)?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn basic_graph_patterns() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let person = Value::StringLiteral("Person".to_string());
+        let (juniper, acme) = instance.write_sled(&test_dataset, |tx| {
+            let juniper = tx.new_entity()?;
+            let clarice = tx.new_entity()?;
+            let acme = tx.new_entity()?;
+            let globex = tx.new_entity()?;
+            let statements = vec![
+                (&juniper, "type", person.clone()),
+                (&clarice, "type", person.clone()),
+                (&juniper, "employer", Value::Entity(acme.clone())),
+                (&clarice, "employer", Value::Entity(globex.clone())),
+                (&acme, "country", Value::StringLiteral("FR".to_string())),
+                (&globex, "country", Value::StringLiteral("US".to_string())),
+            ];
+            for (entity, attribute, value) in statements {
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: Attribute::new(attribute)?,
+                    value: value,
+                })?;
+            }
+            Ok((juniper, acme))
+        })?;
+        let variable = |name: &str| Term::Variable(name.to_string());
+        let patterns = vec![
+            StatementPattern {
+                entity: variable("person"),
+                attribute: Term::Bound(Attribute::new("type")?),
+                value: Term::Bound(person.clone()),
+            },
+            StatementPattern {
+                entity: variable("person"),
+                attribute: Term::Bound(Attribute::new("employer")?),
+                value: variable("employer"),
+            },
+            StatementPattern {
+                entity: variable("employer"),
+                attribute: Term::Bound(Attribute::new("country")?),
+                value: Term::Bound(Value::StringLiteral("FR".to_string())),
+            },
+        ];
+        let (results, plan) = instance.query_sled(&test_dataset, |tx| {
+            Ok((
+                tx.match_patterns(patterns.clone())
+                    .collect::<Result<Vec<Bindings>, LigatureError>>()?,
+                tx.plan_patterns(patterns.clone())?,
+            ))
+        })?;
+        let mut expected = Bindings::new();
+        expected.insert("person".to_string(), Binding::Value(Value::Entity(juniper)));
+        expected.insert("employer".to_string(), Binding::Value(Value::Entity(acme)));
+        assert_eq!(results, vec![expected]);
+        // the country pattern matches one Statement so it is evaluated first
+        assert_eq!(plan[0], patterns[2]);
+        assert_eq!(plan[1], patterns[1]);
+
+        let attributes: Vec<Bindings> = instance.query_sled(&test_dataset, |tx| {
+            tx.match_patterns(vec![StatementPattern {
+                entity: variable("entity"),
+                attribute: variable("attribute"),
+                value: Term::Bound(Value::StringLiteral("US".to_string())),
+            }])
+            .collect()
+        })?;
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(
+            attributes[0].get("attribute"),
+            Some(&Binding::Attribute(Attribute::new("country")?))
+        );
+        let missing: Vec<Bindings> = instance.query_sled(&test_dataset, |tx| {
+            tx.match_patterns(vec![StatementPattern {
+                entity: variable("entity"),
+                attribute: Term::Bound(Attribute::new("missing")?),
+                value: variable("value"),
+            }])
+            .collect()
+        })?;
+        assert!(missing.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn datalog_rules() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        // d's parent is c, c's is b and b's is a
+        let entities = instance.write_sled(&test_dataset, |tx| {
+            let entities = vec![
+                tx.new_entity()?,
+                tx.new_entity()?,
+                tx.new_entity()?,
+                tx.new_entity()?,
+            ];
+            for pair in entities.windows(2) {
+                tx.add_statement(&Statement {
+                    entity: pair[1].clone(),
+                    attribute: Attribute::new("parent")?,
+                    value: Value::Entity(pair[0].clone()),
+                })?;
+            }
+            Ok(entities)
+        })?;
+        let program = Program::parse(
+            "
+            % transitive closure
+            ancestor(X, Y) :- parent(X, Y).
+            ancestor(X, Z) :- parent(X, Y), ancestor(Y, Z).
+            root(X) :- parent(_, X), not parent(X, _).
+            ",
+        )?;
+        let query = Atom::parse(&format!("ancestor(@{}, Y)", entities[3].0))?;
+        let ancestors = instance.query_sled(&test_dataset, |tx| {
+            tx.evaluate_datalog(&program, &query, EvaluationLimits::default())
+        })?;
+        let mut found: Vec<Binding> = ancestors
+            .into_iter()
+            .map(|bindings| bindings["Y"].clone())
+            .collect();
+        found.sort_by_key(|binding| match binding {
+            Binding::Value(Value::Entity(entity)) => entity.0,
+            _ => 0,
+        });
+        assert_eq!(
+            found,
+            entities[..3]
+                .iter()
+                .map(|entity| Binding::Value(Value::Entity(entity.clone())))
+                .collect::<Vec<Binding>>()
+        );
+        let roots = instance.query_sled(&test_dataset, |tx| {
+            tx.evaluate_datalog(
+                &program,
+                &Atom::parse("root(X)")?,
+                EvaluationLimits::default(),
+            )
+        })?;
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0]["X"], Binding::Value(Value::Entity(entities[0].clone())));
+
+        let limited = |query: &str, limits: EvaluationLimits| {
+            instance.query_sled(&test_dataset, |tx| {
+                tx.evaluate_datalog(&program, &Atom::parse(query)?, limits)
+            })
+        };
+        let iterations = |max_iterations: usize| EvaluationLimits {
+            max_iterations: Some(max_iterations),
+            ..EvaluationLimits::default()
+        };
+        // root isn't recursive so it only needs one iteration
+        assert_eq!(limited("root(X)", iterations(1))?.len(), 1);
+        // the chain of three parents closes in three iterations and a fourth finds nothing new
+        assert!(limited("ancestor(X, Y)", iterations(3)).is_err());
+        assert_eq!(limited("ancestor(X, Y)", iterations(4))?.len(), 6);
+        let facts = EvaluationLimits {
+            max_facts: Some(4),
+            ..EvaluationLimits::default()
+        };
+        assert!(limited("ancestor(X, Y)", facts).is_err());
+        // anonymous variables aren't returned
+        let children = limited("ancestor(X, _)", EvaluationLimits::default())?;
+        assert_eq!(children.len(), 6);
+        for bindings in children.iter() {
+            assert_eq!(bindings.keys().collect::<Vec<&String>>(), vec!["X"]);
+        }
+
+        let unstratified = Program::parse(
+            "
+            p(X) :- parent(X, _), not q(X).
+            q(X) :- parent(X, _), not p(X).
+            ",
+        )?;
+        let res = instance.query_sled(&test_dataset, |tx| {
+            tx.evaluate_datalog(
+                &unstratified,
+                &Atom::parse("p(X)")?,
+                EvaluationLimits::default(),
+            )
+        });
+        assert!(res.is_err());
+        let unsafe_rule = Program::parse("p(X, Y) :- parent(X, Z).")?;
+        let res = instance.query_sled(&test_dataset, |tx| {
+            tx.evaluate_datalog(
+                &unsafe_rule,
+                &Atom::parse("p(X, Y)")?,
+                EvaluationLimits::default(),
+            )
+        });
+        assert!(res.is_err());
+        assert!(Program::parse("ancestor(X, Y) :- parent(X, Y)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn graph_traversal() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let (a, b, c, d) = instance.write_sled(&test_dataset, |tx| {
+            let a = tx.new_entity()?;
+            let b = tx.new_entity()?;
+            let c = tx.new_entity()?;
+            let d = tx.new_entity()?;
+            let statements = vec![
+                (&a, "knows", Value::Entity(b.clone())),
+                (&b, "knows", Value::Entity(c.clone())),
+                (&c, "knows", Value::Entity(d.clone())),
+                (&a, "likes", Value::Entity(c.clone())),
+                (&a, "name", Value::StringLiteral("A".to_string())),
+            ];
+            for (entity, attribute, value) in statements {
+                tx.add_statement(&Statement {
+                    entity: entity.clone(),
+                    attribute: Attribute::new(attribute)?,
+                    value: value,
+                })?;
+            }
+            Ok((a, b, c, d))
+        })?;
+        let knows = Traversal {
+            attribute: Some(Attribute::new("knows")?),
+            ..Traversal::default()
+        };
+        let depth_one = Traversal {
+            max_depth: Some(1),
+            ..Traversal::default()
+        };
+        let incoming = Traversal {
+            direction: Direction::Incoming,
+            ..Traversal::default()
+        };
+        instance.query_sled(&test_dataset, |tx| {
+            let ends = |edges: Vec<Edge>| -> Vec<(String, Entity)> {
+                edges
+                    .into_iter()
+                    .map(|edge| (edge.attribute.name().to_string(), edge.to))
+                    .collect()
+            };
+            let out = tx
+                .neighbours(&a, Direction::Outgoing, None)
+                .collect::<Result<Vec<Edge>, LigatureError>>()?;
+            assert_eq!(
+                ends(out),
+                vec![("knows".to_string(), b.clone()), ("likes".to_string(), c.clone())]
+            );
+            let into = tx
+                .neighbours(&c, Direction::Incoming, None)
+                .collect::<Result<Vec<Edge>, LigatureError>>()?;
+            assert_eq!(
+                ends(into),
+                vec![("likes".to_string(), a.clone()), ("knows".to_string(), b.clone())]
+            );
+            let into = tx
+                .neighbours(&c, Direction::Incoming, Some(Attribute::new("likes")?))
+                .collect::<Result<Vec<Edge>, LigatureError>>()?;
+            assert_eq!(ends(into), vec![("likes".to_string(), a.clone())]);
+            assert_eq!(
+                tx.neighbours(&a, Direction::Both, Some(Attribute::new("missing")?))
+                    .count(),
+                0
+            );
+
+            let visits = tx
+                .breadth_first(&a, &Traversal::default())
+                .collect::<Result<Vec<(Entity, usize)>, LigatureError>>()?;
+            assert_eq!(
+                visits,
+                vec![(a.clone(), 0), (b.clone(), 1), (c.clone(), 1), (d.clone(), 2)]
+            );
+            let visits = tx
+                .depth_first(&a, &knows)
+                .collect::<Result<Vec<(Entity, usize)>, LigatureError>>()?;
+            assert_eq!(
+                visits,
+                vec![(a.clone(), 0), (b.clone(), 1), (c.clone(), 2), (d.clone(), 3)]
+            );
+            let reachable = tx
+                .reachable(&a, &depth_one)
+                .collect::<Result<Vec<Entity>, LigatureError>>()?;
+            assert_eq!(reachable, vec![b.clone(), c.clone()]);
+            let reachable = tx
+                .reachable(&d, &incoming)
+                .collect::<Result<Vec<Entity>, LigatureError>>()?;
+            assert_eq!(reachable, vec![c.clone(), a.clone(), b.clone()]);
+
+            let path = tx.shortest_path(&a, &d, &Traversal::default())?.unwrap();
+            assert_eq!(
+                ends(path),
+                vec![("likes".to_string(), c.clone()), ("knows".to_string(), d.clone())]
+            );
+            let path = tx.shortest_path(&a, &d, &knows)?.unwrap();
+            assert_eq!(path.len(), 3);
+            assert_eq!(tx.shortest_path(&a, &d, &depth_one)?, None);
+            assert_eq!(tx.shortest_path(&d, &a, &Traversal::default())?, None);
+            assert_eq!(tx.shortest_path(&d, &a, &incoming)?.map(|p| p.len()), Some(2));
+            assert_eq!(tx.shortest_path(&a, &a, &Traversal::default())?, Some(vec![]));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn aggregation() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let entities = instance.write_sled(&test_dataset, |tx| {
+            let mut entities = vec![];
+            for _ in 0..4 {
+                entities.push(tx.new_entity()?);
+            }
+            let statements = vec![
+                (0, "name", Value::StringLiteral("A".to_string())),
+                (0, "score", Value::IntegerLiteral(34)),
+                (1, "name", Value::StringLiteral("B".to_string())),
+                (1, "score", Value::IntegerLiteral(-5)),
+                (2, "name", Value::StringLiteral("C".to_string())),
+                (2, "score", Value::IntegerLiteral(17)),
+                (2, "score", Value::FloatLiteral(20.5)),
+                (3, "name", Value::StringLiteral("D".to_string())),
+                (3, "score", Value::StringLiteral("unknown".to_string())),
+            ];
+            for (entity, attribute, value) in statements {
+                tx.add_statement(&Statement {
+                    entity: entities[entity].clone(),
+                    attribute: Attribute::new(attribute)?,
+                    value: value,
+                })?;
+            }
+            Ok(entities)
+        })?;
+        let score = Attribute::new("score")?;
+        instance.query_sled(&test_dataset, |tx| {
+            let aggregate = |entity: Option<&Entity>, aggregate: Aggregate| {
+                tx.aggregate(entity.cloned(), Some(score.clone()), None, aggregate)
+            };
+            assert_eq!(aggregate(None, Aggregate::Count)?, AggregateResult::Count(5));
+            assert_eq!(
+                aggregate(None, Aggregate::Min)?,
+                AggregateResult::Min(Some(Value::IntegerLiteral(-5)))
+            );
+            // Min and Max only use numbers, so a score that isn't one is skipped
+            assert_eq!(
+                aggregate(Some(&entities[3]), Aggregate::Min)?,
+                AggregateResult::Min(None)
+            );
+            assert_eq!(
+                aggregate(None, Aggregate::Max)?,
+                AggregateResult::Max(Some(Value::IntegerLiteral(34)))
+            );
+            assert_eq!(
+                aggregate(Some(&entities[1]), Aggregate::Max)?,
+                AggregateResult::Max(Some(Value::IntegerLiteral(-5)))
+            );
+            assert_eq!(
+                aggregate(Some(&entities[2]), Aggregate::Min)?,
+                AggregateResult::Min(Some(Value::IntegerLiteral(17)))
+            );
+            assert_eq!(
+                aggregate(None, Aggregate::Sum)?,
+                AggregateResult::Sum(Value::FloatLiteral(66.5))
+            );
+            assert_eq!(
+                aggregate(Some(&entities[0]), Aggregate::Sum)?,
+                AggregateResult::Sum(Value::IntegerLiteral(34))
+            );
+            assert_eq!(
+                aggregate(None, Aggregate::Average)?,
+                AggregateResult::Average(Some(16.625))
+            );
+            assert_eq!(
+                aggregate(Some(&entities[3]), Aggregate::Average)?,
+                AggregateResult::Average(None)
+            );
+            assert_eq!(
+                tx.aggregate(
+                    None,
+                    Some(score.clone()),
+                    Some(Value::IntegerLiteral(17)),
+                    Aggregate::Count
+                )?,
+                AggregateResult::Count(1)
+            );
+            assert_eq!(
+                tx.aggregate(None, Some(Attribute::new("missing")?), None, Aggregate::Min)?,
+                AggregateResult::Min(None)
+            );
+
+            let per_attribute =
+                tx.aggregate_by(None, None, None, Aggregate::Count, GroupBy::Attribute)?;
+            assert_eq!(
+                per_attribute,
+                vec![
+                    (Binding::Attribute(Attribute::new("name")?), AggregateResult::Count(4)),
+                    (Binding::Attribute(score.clone()), AggregateResult::Count(5)),
+                ]
+            );
+            let per_entity = tx.aggregate_by(
+                None,
+                Some(score.clone()),
+                None,
+                Aggregate::Max,
+                GroupBy::Entity,
+            )?;
+            assert_eq!(per_entity.len(), 4);
+            assert_eq!(
+                per_entity[3],
+                (
+                    Binding::Value(Value::Entity(entities[3].clone())),
+                    AggregateResult::Max(None)
+                )
+            );
+            assert_eq!(
+                per_entity[2],
+                (
+                    Binding::Value(Value::Entity(entities[2].clone())),
+                    AggregateResult::Max(Some(Value::FloatLiteral(20.5)))
+                )
+            );
+            let per_value = tx.aggregate_by(
+                None,
+                Some(score.clone()),
+                None,
+                Aggregate::Count,
+                GroupBy::Value,
+            )?;
+            assert_eq!(per_value.len(), 5);
+            assert!(per_value.contains(&(
+                Binding::Value(Value::IntegerLiteral(-5)),
+                AggregateResult::Count(1)
+            )));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn paginated_statements() -> Result<(), LigatureError> {
+        let instance = instance();
+        let test_dataset = dataset("test/test");
+        instance.create_dataset(&test_dataset)?;
+        let n = Attribute::new("n")?;
+        let entity = instance.write_sled(&test_dataset, |tx| tx.new_entity())?;
+        let add = |values: Vec<i64>| {
+            instance.write_sled(&test_dataset, |tx| {
+                for value in values.iter() {
+                    tx.add_statement(&Statement {
+                        entity: entity.clone(),
+                        attribute: n.clone(),
+                        value: Value::IntegerLiteral(*value),
+                    })?;
+                }
+                Ok(())
+            })
+        };
+        add(vec![-1, 1, 2, 3, 5])?;
+        let values = |page: &Page| -> Vec<Value> {
+            page.statements
+                .iter()
+                .map(|statement| statement.statement.value.clone())
+                .collect()
+        };
+        let integers = |values: Vec<i64>| -> Vec<Value> {
+            values.into_iter().map(Value::IntegerLiteral).collect()
+        };
+        instance.query_sled(&test_dataset, |tx| {
+            let all = tx
+                .all_statements()
+                .collect::<Result<Vec<PersistedStatement>, LigatureError>>()?;
+            let mut request = PageRequest {
+                limit: Some(2),
+                ..PageRequest::default()
+            };
+            let mut pages = vec![];
+            loop {
+                let page = tx.all_statements_page(&request)?;
+                pages.extend(page.statements);
+                match page.next {
+                    Some(next) => request.cursor = Some(Cursor::parse(&next.to_string())?),
+                    None => break,
+                }
+            }
+            assert_eq!(pages, all);
+            let page = tx.all_statements_page(&PageRequest {
+                offset: 1,
+                limit: Some(2),
+                reverse: true,
+                ..PageRequest::default()
+            })?;
+            assert_eq!(page.statements, vec![all[3].clone(), all[2].clone()]);
+
+            let range = || Range::IntegerLiteralRange { start: -1, end: 4 };
+            let page = tx.match_statements_range_page(
+                None,
+                Some(n.clone()),
+                range(),
+                &PageRequest {
+                    limit: Some(2),
+                    ..PageRequest::default()
+                },
+            )?;
+            assert_eq!(values(&page), integers(vec![-1, 1]));
+            let page = tx.match_statements_range_page(
+                None,
+                Some(n.clone()),
+                range(),
+                &PageRequest {
+                    cursor: page.next,
+                    limit: Some(2),
+                    ..PageRequest::default()
+                },
+            )?;
+            assert_eq!(values(&page), integers(vec![2, 3]));
+            assert_eq!(page.next, None);
+            let page = tx.match_statements_range_page(
+                None,
+                Some(n.clone()),
+                range(),
+                &PageRequest {
+                    limit: Some(3),
+                    reverse: true,
+                    ..PageRequest::default()
+                },
+            )?;
+            assert_eq!(values(&page), integers(vec![3, 2, 1]));
+
+            let other_query = tx.match_statements_page(
+                None,
+                Some(n.clone()),
+                None,
+                &PageRequest {
+                    limit: Some(1),
+                    ..PageRequest::default()
+                },
+            )?;
+            let res = tx.all_statements_page(&PageRequest {
+                cursor: other_query.next.clone(),
+                ..PageRequest::default()
+            });
+            assert!(res.is_err());
+            // nothing can match an unknown Attribute, so any cursor gives an empty page
+            let page = tx.match_statements_page(
+                None,
+                Some(Attribute::new("unknown")?),
+                None,
+                &PageRequest {
+                    cursor: other_query.next,
+                    ..PageRequest::default()
+                },
+            )?;
+            assert!(page.statements.is_empty());
+            assert_eq!(page.next, None);
+            assert!(tx
+                .all_statements_page(&PageRequest {
+                    limit: Some(0),
+                    ..PageRequest::default()
+                })
+                .is_err());
+            Ok(())
+        })?;
+        assert!(Cursor::parse("zz").is_err());
+
+        let request = PageRequest {
+            limit: Some(2),
+            ..PageRequest::default()
+        };
+        let first = instance.query_sled(&test_dataset, |tx| {
+            tx.match_statements_page(None, Some(n.clone()), None, &request)
+        })?;
+        assert_eq!(values(&first), integers(vec![1, 2]));
+        // Statements added before and after the cursor don't move the rest of the pages
+        add(vec![0, 4])?;
+        let rest = instance.query_sled(&test_dataset, |tx| {
+            tx.match_statements_page(
+                None,
+                Some(n.clone()),
+                None,
+                &PageRequest {
+                    cursor: first.next.clone(),
+                    ..PageRequest::default()
+                },
+            )
+        })?;
+        assert_eq!(values(&rest), integers(vec![3, 4, 5, -1]));
+        Ok(())
+    }
 }